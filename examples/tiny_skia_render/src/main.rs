@@ -56,7 +56,7 @@ fn main() {
     let font_stack = FontStack::Source("system-ui");
     let font_stack_style = StyleProperty::FontStack(font_stack);
     builder.push_default(&font_stack_style);
-    builder.push_default(&StyleProperty::LineHeight(1.3));
+    builder.push_default(&StyleProperty::LineHeight(1.3.into()));
     builder.push_default(&StyleProperty::FontSize(16.0));
 
     // Set the first 4 characters to bold
@@ -39,7 +39,7 @@ pub struct SystemFonts {
 }
 
 impl SystemFonts {
-    pub fn new() -> Self {
+    pub fn new(scan_cache_path: Option<&Path>) -> Self {
         let android_root: String = std::env::var("ANDROID_ROOT").unwrap_or("/system".to_string());
 
         let scan::ScannedCollection {
@@ -47,7 +47,11 @@ impl SystemFonts {
             families: family_map,
             postscript_names,
             ..
-        } = scan::ScannedCollection::from_paths(Path::new(&android_root).join("fonts").to_str(), 8);
+        } = scan::ScannedCollection::from_paths(
+            Path::new(&android_root).join("fonts").to_str(),
+            8,
+            scan_cache_path,
+        );
         let mut generic_families = GenericFamilyMap::default();
         for (family, names) in DEFAULT_GENERIC_FAMILIES {
             generic_families.set(
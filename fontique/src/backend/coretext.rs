@@ -10,6 +10,7 @@ use objc2::runtime::Bool;
 use objc2_foundation::{
     NSSearchPathDirectory, NSSearchPathDomainMask, NSSearchPathForDirectoriesInDomains,
 };
+use std::path::Path;
 use {
     core_foundation::{
         base::{CFRange, TCFType},
@@ -40,7 +41,7 @@ pub struct SystemFonts {
 }
 
 impl SystemFonts {
-    pub fn new() -> Self {
+    pub fn new(scan_cache_path: Option<&Path>) -> Self {
         let paths = unsafe {
             NSSearchPathForDirectoriesInDomains(
                 NSSearchPathDirectory::NSLibraryDirectory,
@@ -51,7 +52,7 @@ impl SystemFonts {
             .iter()
             .map(|p| format!("{p}/Fonts/"))
         };
-        let scanned = scan::ScannedCollection::from_paths(paths, 8);
+        let scanned = scan::ScannedCollection::from_paths(paths, 8, scan_cache_path);
         let name_map = scanned.family_names;
         let mut generic_families = GenericFamilyMap::default();
         for (family, names) in DEFAULT_GENERIC_FAMILIES {
@@ -5,7 +5,7 @@ use dwrote::{
     Font as DFont, FontCollection, FontFallback, TextAnalysisSource, TextAnalysisSourceMethods,
 };
 use hashbrown::HashMap;
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, path::Path, sync::Arc};
 use winapi::{
     ctypes::wchar_t,
     um::dwrite::{
@@ -49,7 +49,9 @@ unsafe impl Send for SystemFonts {}
 unsafe impl Sync for SystemFonts {}
 
 impl SystemFonts {
-    pub fn new() -> Self {
+    /// The scan cache is not used, since fonts are enumerated by the
+    /// platform rather than by scanning font directories.
+    pub fn new(_scan_cache_path: Option<&Path>) -> Self {
         let collection = FontCollection::get_system(false);
         let mut name_map = FamilyNameMap::default();
         for family in collection.families_iter() {
@@ -24,7 +24,9 @@ pub struct SystemFonts {
 }
 
 impl SystemFonts {
-    pub fn new() -> Self {
+    /// The scan cache is not used, since fonts are enumerated by the
+    /// platform rather than by scanning font directories.
+    pub fn new(_scan_cache_path: Option<&Path>) -> Self {
         Self::try_new().unwrap_or_else(|| Self {
             name_map: Default::default(),
             generic_families: Default::default(),
@@ -15,19 +15,24 @@ use super::{
     fallback::{FallbackKey, FallbackMap},
     family::{FamilyId, FamilyInfo},
     family_name::{FamilyName, FamilyNameMap},
-    font::FontInfo,
+    font::{FontDescriptor, FontInfo},
     generic::GenericFamilyMap,
     source::{SourceId, SourceInfo, SourceKind},
     Blob, GenericFamily, Script,
 };
 use alloc::{string::String, sync::Arc, vec::Vec};
-use core::sync::atomic::AtomicU64;
+use core::sync::atomic::{AtomicU64, Ordering};
 use hashbrown::HashMap;
 #[cfg(feature = "std")]
-use std::sync::{atomic::Ordering, Mutex};
+use std::{path::Path, sync::Mutex};
 
 type FamilyMap = HashMap<FamilyId, Option<FamilyInfo>>;
 
+#[cfg(feature = "std")]
+type ScanCachePath = Path;
+#[cfg(not(feature = "std"))]
+type ScanCachePath = ();
+
 /// Options for a font collection.
 #[derive(Copy, Clone, Debug)]
 pub struct CollectionOptions {
@@ -44,6 +49,9 @@ pub struct CollectionOptions {
     /// If true, the font collection will provide access to system fonts
     /// using platform specific APIs.
     ///
+    /// Backends that scan font directories can cache the results of the
+    /// scan, see [`Collection::with_scan_cache`].
+    ///
     /// The default value is true.
     pub system_fonts: bool,
 }
@@ -68,7 +76,27 @@ impl Collection {
     /// Creates a new collection with the given options.
     pub fn new(options: CollectionOptions) -> Self {
         Self {
-            inner: Inner::new(options),
+            inner: Inner::new(options, None),
+            query_state: Default::default(),
+        }
+    }
+
+    /// Creates a new collection with the given options, caching the results
+    /// of scanning system font directories in the file at `scan_cache_path`.
+    ///
+    /// This only applies to system backends that discover fonts by scanning
+    /// the file system, which are Core Text on Apple platforms and the
+    /// Android backend. When the cache exists, only files that were added or
+    /// modified since the previous scan are read.
+    ///
+    /// DirectWrite and fontconfig enumerate fonts through the platform,
+    /// which keeps its own cache, so the path is ignored and no file is
+    /// written on Windows and Linux. It is also ignored if
+    /// [`system_fonts`](CollectionOptions::system_fonts) is false.
+    #[cfg(feature = "std")]
+    pub fn with_scan_cache(options: CollectionOptions, scan_cache_path: impl AsRef<Path>) -> Self {
+        Self {
+            inner: Inner::new(options, Some(scan_cache_path.as_ref())),
             query_state: Default::default(),
         }
     }
@@ -164,10 +192,58 @@ impl Collection {
 
     /// Registers all fonts that exist in the given data.
     ///
+    /// When the `woff` feature is enabled, WOFF and WOFF2 data is decoded
+    /// to sfnt data before the fonts are registered.
+    ///
     /// Returns a list of pairs each containing the family identifier and fonts
     /// added to that family.
     pub fn register_fonts(&mut self, data: Vec<u8>) -> Vec<(FamilyId, Vec<FontInfo>)> {
-        self.inner.register_fonts(data)
+        self.inner.register_fonts(data, None)
+    }
+
+    /// Registers all fonts that exist in the given data with attributes
+    /// overridden by the descriptor.
+    ///
+    /// Returns a list of pairs each containing the family identifier and fonts
+    /// added to that family.
+    pub fn register_fonts_with_descriptor(
+        &mut self,
+        data: Vec<u8>,
+        descriptor: &FontDescriptor,
+    ) -> Vec<(FamilyId, Vec<FontInfo>)> {
+        self.inner.register_fonts(data, Some(descriptor))
+    }
+
+    /// Returns the generation of the collection.
+    ///
+    /// The generation changes whenever fonts are registered or unregistered
+    /// or the generic families or fallbacks are modified, and is never shared
+    /// by two collections unless one is an unmodified clone of the other. It
+    /// can be used to invalidate data derived from font selection.
+    pub fn generation(&mut self) -> u64 {
+        self.inner.sync_shared();
+        self.inner.data.generation
+    }
+
+    /// Unregisters the family with the given identifier, removing it from
+    /// the generic families and fallbacks.
+    ///
+    /// Only families that were created by [`register_fonts`] can be
+    /// removed. Returns false if there is no such family.
+    ///
+    /// [`register_fonts`]: Self::register_fonts
+    pub fn unregister_family(&mut self, id: FamilyId) -> bool {
+        self.inner.unregister_family(id)
+    }
+
+    /// Unregisters all fonts that were registered from the source with the
+    /// given identifier. Families that are left without fonts are
+    /// unregistered.
+    ///
+    /// The source of a font is available from [`FontInfo::source`].
+    /// Returns false if no fonts were removed.
+    pub fn unregister_source(&mut self, id: SourceId) -> bool {
+        self.inner.unregister_source(id)
     }
 }
 
@@ -191,12 +267,16 @@ struct Inner {
 
 impl Inner {
     /// Creates a new collection with the given options.
-    pub fn new(options: CollectionOptions) -> Self {
-        let system = options.system_fonts.then(System::new);
+    pub fn new(options: CollectionOptions, scan_cache_path: Option<&ScanCachePath>) -> Self {
+        let system = options.system_fonts.then(|| System::new(scan_cache_path));
         let shared = options.shared.then(|| Arc::new(Shared::default()));
+        // Collections with different system fonts must not share a
+        // generation.
+        let mut data = CommonData::default();
+        data.modified();
         Self {
             system,
-            data: CommonData::default(),
+            data,
             shared,
             shared_version: 0,
             fallback_cache: Default::default(),
@@ -299,18 +379,19 @@ impl Inner {
         self.sync_shared();
         #[cfg(feature = "std")]
         if let Some(shared) = &self.shared {
-            shared
-                .data
-                .lock()
-                .unwrap()
-                .generic_families
-                .set(generic, families);
+            let mut data = shared.data.lock().unwrap();
+            data.generic_families.set(generic, families);
+            data.modified();
             shared.bump_version();
         } else {
             self.data.generic_families.set(generic, families);
+            self.data.modified();
         }
         #[cfg(not(feature = "std"))]
-        self.data.generic_families.set(generic, families);
+        {
+            self.data.generic_families.set(generic, families);
+            self.data.modified();
+        }
     }
 
     /// Appends the set of family identifiers to the given generic family.
@@ -322,18 +403,19 @@ impl Inner {
         self.sync_shared();
         #[cfg(feature = "std")]
         if let Some(shared) = &self.shared {
-            shared
-                .data
-                .lock()
-                .unwrap()
-                .generic_families
-                .append(generic, families);
+            let mut data = shared.data.lock().unwrap();
+            data.generic_families.append(generic, families);
+            data.modified();
             shared.bump_version();
         } else {
             self.data.generic_families.append(generic, families);
+            self.data.modified();
         }
         #[cfg(not(feature = "std"))]
-        self.data.generic_families.append(generic, families);
+        {
+            self.data.generic_families.append(generic, families);
+            self.data.modified();
+        }
     }
 
     /// Returns an iterator over the fallback families for the given
@@ -345,8 +427,9 @@ impl Inner {
         let selector = key.into();
         let script = selector.script();
         let lang_key = selector.locale();
+        // Changes to shared data reset the cache.
+        self.sync_shared();
         if self.fallback_cache.script != Some(script) || self.fallback_cache.language != lang_key {
-            self.sync_shared();
             self.fallback_cache.reset();
             #[cfg(feature = "system")]
             if let Some(families) = self.data.fallbacks.get(selector) {
@@ -378,14 +461,20 @@ impl Inner {
         self.sync_shared();
         #[cfg(feature = "std")]
         if let Some(shared) = &self.shared {
-            let result = shared.data.lock().unwrap().fallbacks.set(key, families);
+            let mut data = shared.data.lock().unwrap();
+            let result = data.fallbacks.set(key, families);
+            data.modified();
             shared.bump_version();
             result
         } else {
+            self.data.modified();
             self.data.fallbacks.set(key, families)
         }
         #[cfg(not(feature = "std"))]
-        self.data.fallbacks.set(key, families)
+        {
+            self.data.modified();
+            self.data.fallbacks.set(key, families)
+        }
     }
 
     /// Appends the set of family identifiers to the given fallback key.
@@ -397,31 +486,78 @@ impl Inner {
         self.sync_shared();
         #[cfg(feature = "std")]
         if let Some(shared) = &self.shared {
-            let result = shared.data.lock().unwrap().fallbacks.append(key, families);
+            let mut data = shared.data.lock().unwrap();
+            let result = data.fallbacks.append(key, families);
+            data.modified();
             shared.bump_version();
             result
         } else {
+            self.data.modified();
             self.data.fallbacks.append(key, families)
         }
         #[cfg(not(feature = "std"))]
-        self.data.fallbacks.append(key, families)
+        {
+            self.data.modified();
+            self.data.fallbacks.append(key, families)
+        }
     }
 
-    /// Registers all fonts that exist in the given data.
+    /// Registers all fonts that exist in the given data, with attributes
+    /// optionally overridden by a descriptor.
     ///
     /// Returns a list of pairs each containing the family identifier and fonts
     /// added to that family.
-    pub fn register_fonts(&mut self, data: Vec<u8>) -> Vec<(FamilyId, Vec<FontInfo>)> {
+    pub fn register_fonts(
+        &mut self,
+        data: Vec<u8>,
+        descriptor: Option<&FontDescriptor>,
+    ) -> Vec<(FamilyId, Vec<FontInfo>)> {
         #[cfg(feature = "std")]
         if let Some(shared) = &self.shared {
-            let result = shared.data.lock().unwrap().register_fonts(data);
+            let result = shared.data.lock().unwrap().register_fonts(data, descriptor);
             shared.bump_version();
             result
         } else {
-            self.data.register_fonts(data)
+            self.data.register_fonts(data, descriptor)
+        }
+        #[cfg(not(feature = "std"))]
+        self.data.register_fonts(data, descriptor)
+    }
+
+    /// Unregisters the family with the given identifier.
+    pub fn unregister_family(&mut self, id: FamilyId) -> bool {
+        self.sync_shared();
+        self.fallback_cache.reset();
+        #[cfg(feature = "std")]
+        if let Some(shared) = &self.shared {
+            let result = shared.data.lock().unwrap().unregister_family(id);
+            if result {
+                shared.bump_version();
+            }
+            result
+        } else {
+            self.data.unregister_family(id)
         }
         #[cfg(not(feature = "std"))]
-        self.data.register_fonts(data)
+        self.data.unregister_family(id)
+    }
+
+    /// Unregisters all fonts from the source with the given identifier.
+    pub fn unregister_source(&mut self, id: SourceId) -> bool {
+        self.sync_shared();
+        self.fallback_cache.reset();
+        #[cfg(feature = "std")]
+        if let Some(shared) = &self.shared {
+            let result = shared.data.lock().unwrap().unregister_source(id);
+            if result {
+                shared.bump_version();
+            }
+            result
+        } else {
+            self.data.unregister_source(id)
+        }
+        #[cfg(not(feature = "std"))]
+        self.data.unregister_source(id)
     }
 
     fn sync_shared(&mut self) {
@@ -513,7 +649,10 @@ struct System {
 }
 
 impl System {
-    fn new() -> Self {
+    fn new(#[allow(unused)] scan_cache_path: Option<&ScanCachePath>) -> Self {
+        #[cfg(feature = "system")]
+        let fonts = SystemFonts::new(scan_cache_path);
+        #[cfg(not(feature = "system"))]
         let fonts = SystemFonts::new();
         let family_names = fonts.name_map.clone();
         let generic_families = fonts.generic_families.clone();
@@ -535,10 +674,18 @@ struct CommonData {
     families: FamilyMap,
     generic_families: GenericFamilyMap,
     fallbacks: FallbackMap,
+    /// Identifies the state of the data across all collections.
+    generation: u64,
 }
 
 impl CommonData {
-    fn register_fonts(&mut self, data: Vec<u8>) -> Vec<(FamilyId, Vec<FontInfo>)> {
+    fn register_fonts(
+        &mut self,
+        data: Vec<u8>,
+        descriptor: Option<&FontDescriptor>,
+    ) -> Vec<(FamilyId, Vec<FontInfo>)> {
+        #[cfg(feature = "woff")]
+        let data = crate::woff::decode(&data).unwrap_or(data);
         let blob = Blob::new(Arc::new(data));
         let mut families: HashMap<FamilyId, (FamilyName, Vec<FontInfo>)> = Default::default();
         let mut family_name = String::default();
@@ -546,14 +693,18 @@ impl CommonData {
         super::scan::scan_memory(blob.as_ref(), |scanned_font| {
             use skrifa::raw::types::NameId;
             family_name.clear();
-            let family_chars = scanned_font
-                .english_or_first_name(NameId::TYPOGRAPHIC_FAMILY_NAME)
-                .or_else(|| scanned_font.english_or_first_name(NameId::FAMILY_NAME))
-                .map(|name| name.chars());
-            let Some(family_chars) = family_chars else {
-                return;
-            };
-            family_name.extend(family_chars);
+            if let Some(name) = descriptor.and_then(|descriptor| descriptor.family_name.as_ref()) {
+                family_name.push_str(name);
+            } else {
+                let family_chars = scanned_font
+                    .english_or_first_name(NameId::TYPOGRAPHIC_FAMILY_NAME)
+                    .or_else(|| scanned_font.english_or_first_name(NameId::FAMILY_NAME))
+                    .map(|name| name.chars());
+                let Some(family_chars) = family_chars else {
+                    return;
+                };
+                family_name.extend(family_chars);
+            }
             if family_name.is_empty() {
                 return;
             }
@@ -561,10 +712,14 @@ impl CommonData {
                 id: data_id,
                 kind: SourceKind::Memory(blob.clone()),
             };
-            let Some(font) = FontInfo::from_font_ref(&scanned_font.font, data, scanned_font.index)
+            let Some(mut font) =
+                FontInfo::from_font_ref(&scanned_font.font, data, scanned_font.index)
             else {
                 return;
             };
+            if let Some(descriptor) = descriptor {
+                font.apply_descriptor(descriptor);
+            }
             let name = self.family_names.get_or_insert(&family_name);
             families
                 .entry(name.id())
@@ -581,11 +736,65 @@ impl CommonData {
                 self.families.insert(*id, Some(family));
             }
         }
+        self.modified();
         families
             .into_iter()
             .map(|(id, (_, fonts))| (id, fonts))
             .collect()
     }
+
+    fn unregister_family(&mut self, id: FamilyId) -> bool {
+        // The name map only contains registered families. Cached system
+        // families are never removed.
+        if self.family_names.remove(id).is_none() {
+            return false;
+        }
+        self.families.remove(&id);
+        self.generic_families.remove_family(id);
+        self.fallbacks.remove_family(id);
+        self.modified();
+        true
+    }
+
+    fn unregister_source(&mut self, id: SourceId) -> bool {
+        let mut removed = false;
+        let mut empty_families = Vec::new();
+        for (family_id, family) in &mut self.families {
+            let (Some(name), Some(family)) = (self.family_names.get_by_id(*family_id), family)
+            else {
+                continue;
+            };
+            if family.fonts().iter().all(|font| font.source().id() != id) {
+                continue;
+            }
+            removed = true;
+            let fonts = family
+                .fonts()
+                .iter()
+                .filter(|font| font.source().id() != id)
+                .cloned()
+                .collect::<Vec<_>>();
+            if fonts.is_empty() {
+                empty_families.push(*family_id);
+            } else {
+                *family = FamilyInfo::new(name.clone(), fonts);
+            }
+        }
+        for family_id in empty_families {
+            self.unregister_family(family_id);
+        }
+        if removed {
+            self.modified();
+        }
+        removed
+    }
+
+    /// Assigns a new generation after the fonts, generic families or
+    /// fallbacks have changed.
+    fn modified(&mut self) {
+        static GENERATION: AtomicU64 = AtomicU64::new(1);
+        self.generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
 }
 
 /// Synchronized shared collection data.
@@ -604,3 +813,134 @@ impl Shared {
         self.version.fetch_add(1, Ordering::Release);
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FONT};
+    use crate::{Stretch, Style, Weight};
+
+    #[test]
+    fn generation() {
+        for shared in [false, true] {
+            let mut collection = testing::collection(shared);
+            let mut clone = collection.clone();
+            let initial = collection.generation();
+            assert_eq!(clone.generation(), initial);
+            assert_ne!(testing::collection(shared).generation(), initial);
+            let (family, fonts) = collection.register_fonts(FONT.to_vec()).remove(0);
+            let registered = collection.generation();
+            assert_ne!(registered, initial);
+            assert_eq!(clone.generation() == registered, shared);
+            collection.set_generic_families(GenericFamily::SansSerif, [family].into_iter());
+            let generic = collection.generation();
+            assert_ne!(generic, registered);
+            // Failed removals leave the collection unchanged.
+            assert!(!collection.unregister_family(FamilyId::new()));
+            assert_eq!(collection.generation(), generic);
+            assert!(collection.unregister_source(fonts[0].source().id()));
+            assert_ne!(collection.generation(), generic);
+        }
+    }
+
+    #[test]
+    fn unregister() {
+        for shared in [false, true] {
+            let mut collection = testing::collection(shared);
+            let (family, first) = collection.register_fonts(FONT.to_vec()).remove(0);
+            // Fonts from another source are added to the same family.
+            let (second_family, second) = collection.register_fonts(FONT.to_vec()).remove(0);
+            assert_eq!(second_family, family);
+            assert_eq!(collection.family(family).unwrap().fonts().len(), 2);
+            let mut clone = collection.clone();
+            collection.set_generic_families(GenericFamily::SansSerif, [family].into_iter());
+            collection.set_fallbacks("Latn", [family].into_iter());
+            assert!(collection.unregister_source(first[0].source().id()));
+            assert!(!collection.unregister_source(first[0].source().id()));
+            let fonts = collection.family(family).unwrap().fonts().to_vec();
+            assert_eq!(fonts.len(), 1);
+            assert_eq!(fonts[0].source().id(), second[0].source().id());
+            // Removing the last font of a family removes the family.
+            assert!(collection.unregister_source(second[0].source().id()));
+            assert!(collection.family(family).is_none());
+            assert_eq!(collection.family_id("Parley Test"), None);
+            assert_eq!(collection.family_names().count(), 0);
+            assert_eq!(
+                collection
+                    .generic_families(GenericFamily::SansSerif)
+                    .count(),
+                0
+            );
+            assert_eq!(collection.fallback_families("Latn").count(), 0);
+            assert!(!collection.unregister_family(family));
+            // Only shared clones observe the removal.
+            assert_eq!(clone.family_id("Parley Test").is_none(), shared);
+        }
+    }
+
+    #[test]
+    fn unregister_family() {
+        let mut collection = testing::collection(false);
+        let (family, _) = collection.register_fonts(FONT.to_vec()).remove(0);
+        collection.set_generic_families(GenericFamily::Serif, [family].into_iter());
+        assert!(collection.unregister_family(family));
+        assert!(collection.family_by_name("parley test").is_none());
+        assert_eq!(collection.generic_families(GenericFamily::Serif).count(), 0);
+        // The family can be registered again.
+        let (family, _) = collection.register_fonts(FONT.to_vec()).remove(0);
+        assert_eq!(collection.family_id("Parley Test"), Some(family));
+    }
+
+    #[test]
+    fn unregister_fallback_family() {
+        let mut collection = testing::collection(false);
+        let (family, _) = collection.register_fonts(FONT.to_vec()).remove(0);
+        collection.set_fallbacks("Latn", [family].into_iter());
+        collection.set_fallbacks(("Arab", "fa"), [family].into_iter());
+        assert!(collection.unregister_family(family));
+        // Emptied entries are removed so that the system fallback is consulted
+        // again.
+        let fallbacks = &collection.inner.data.fallbacks;
+        assert!(fallbacks.get("Latn").is_none());
+        assert!(fallbacks.get(("Arab", "fa")).is_none());
+        assert_eq!(collection.fallback_families("Latn").count(), 0);
+    }
+
+    #[test]
+    fn register_with_descriptor() {
+        let mut collection = testing::collection(false);
+        let descriptor = FontDescriptor {
+            style: Some(Style::Italic),
+            stretch: Some(Stretch::CONDENSED),
+            ..testing::descriptor("Custom", Some((700., 300.)), Some(0x41..=0x5A))
+        };
+        let (family, fonts) = testing::register(&mut collection, &descriptor);
+        assert_eq!(collection.family_id("custom"), Some(family));
+        assert_eq!(collection.family_id("Parley Test"), None);
+        let font = &fonts[0];
+        // The range is ordered and contains the weight of the font.
+        assert_eq!(font.weight_range(), (Weight::LIGHT, Weight::BOLD));
+        assert_eq!(font.weight(), Weight::NORMAL);
+        assert_eq!(font.style(), Style::Italic);
+        assert_eq!(font.stretch(), Stretch::CONDENSED);
+        assert_eq!(font.unicode_ranges(), Some(&[0x41..=0x5A][..]));
+        // Weights within the range are not synthesized.
+        let synthesis = font.synthesis(Stretch::CONDENSED, Style::Italic, Weight::BOLD);
+        assert!(!synthesis.embolden());
+        let synthesis = font.synthesis(Stretch::CONDENSED, Style::Italic, Weight::BLACK);
+        assert!(synthesis.embolden());
+        // Attributes that are not specified are read from the font.
+        let descriptor = FontDescriptor {
+            weight: Some((Weight::MEDIUM, Weight::BLACK)),
+            ..Default::default()
+        };
+        let (_, fonts) = testing::register(&mut collection, &descriptor);
+        let font = &fonts[0];
+        assert_eq!(collection.family(family).unwrap().fonts().len(), 1);
+        assert_eq!(font.weight(), Weight::MEDIUM);
+        assert_eq!(font.style(), Style::Normal);
+        assert_eq!(font.stretch(), Stretch::NORMAL);
+        assert_eq!(font.unicode_ranges(), None);
+        assert!(collection.family_id("Parley Test").is_some());
+    }
+}
@@ -13,17 +13,20 @@ use super::{
     super::{Attributes, Blob, FallbackKey, FamilyId, FamilyInfo, GenericFamily, Synthesis},
     Inner,
 };
+use smallvec::SmallVec;
 
 #[derive(Clone, Default)]
 pub(super) struct QueryState {
     families: Vec<CachedFamily>,
     fallback_families: Vec<CachedFamily>,
+    chars: Vec<char>,
 }
 
 impl QueryState {
     fn clear(&mut self) {
         self.families.clear();
         self.fallback_families.clear();
+        self.chars.clear();
     }
 }
 
@@ -101,6 +104,17 @@ impl<'a> Query<'a> {
         }
     }
 
+    /// Sets the characters that the fonts are selected for.
+    ///
+    /// Fonts with unicode ranges are only matched if their ranges contain
+    /// at least one of the characters, so the data of fonts that cover other
+    /// parts of a subsetted family is never loaded. If no characters are
+    /// set, all fonts are matched.
+    pub fn set_chars(&mut self, chars: impl IntoIterator<Item = char>) {
+        self.state.chars.clear();
+        self.state.chars.extend(chars);
+    }
+
     /// Invokes the given callback with all fonts that match the current
     /// settings.
     #[cfg(feature = "std")]
@@ -116,6 +130,13 @@ impl<'a> Query<'a> {
                 Entry::Ok(..) => {}
                 status @ Entry::Vacant => {
                     if let Some(info) = self.collection.family(family.id) {
+                        if info
+                            .fonts()
+                            .iter()
+                            .any(|font| font.unicode_ranges().is_some())
+                        {
+                            family.coverage = Some(Default::default());
+                        }
                         *status = Entry::Ok(info);
                     } else {
                         *status = Entry::Error;
@@ -126,9 +147,29 @@ impl<'a> Query<'a> {
             let Entry::Ok(family_info) = &family.family else {
                 continue;
             };
+            if let Some(coverage) = &mut family.coverage {
+                let chars = &self.state.chars;
+                let covered = family_info
+                    .fonts()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, font)| {
+                        chars.is_empty() || chars.iter().any(|ch| font.covers_char(*ch))
+                    })
+                    .map(|(index, _)| index);
+                if !covered.clone().eq(coverage.iter().copied()) {
+                    // The selected fonts depend on the covered fonts.
+                    coverage.clear();
+                    coverage.extend(covered);
+                    family.best = Entry::Vacant;
+                    family.default = Entry::Vacant;
+                }
+            }
+            let coverage = family.coverage.as_deref();
             let mut best_index = None;
             if let Some(font) = load_font(
                 family_info,
+                coverage,
                 self.attributes,
                 &mut family.best,
                 false,
@@ -146,6 +187,7 @@ impl<'a> Query<'a> {
             }
             if let Some(font) = load_font(
                 family_info,
+                coverage,
                 self.attributes,
                 &mut family.default,
                 true,
@@ -219,6 +261,7 @@ pub struct QueryFont {
 #[cfg(feature = "std")]
 fn load_font<'a>(
     family: &FamilyInfo,
+    coverage: Option<&[usize]>,
     attributes: Attributes,
     font: &'a mut Entry<QueryFont>,
     is_default: bool,
@@ -231,15 +274,32 @@ fn load_font<'a>(
             // Set to error in case we fail. This simplifies
             // the following code.
             *status = Entry::Error;
-            let family_index = if is_default {
-                family.default_font_index()
-            } else {
-                family.match_index(
+            let family_index = match coverage {
+                // Only the fonts that cover the characters of the query are
+                // candidates.
+                Some(coverage) if coverage.len() != family.fonts().len() => {
+                    if is_default {
+                        let index = family.default_font_index();
+                        coverage.contains(&index).then_some(index)?
+                    } else {
+                        crate::matching::match_font_in(
+                            coverage
+                                .iter()
+                                .map(|&index| (index, &family.fonts()[index])),
+                            attributes.stretch,
+                            attributes.style,
+                            attributes.weight,
+                            true,
+                        )?
+                    }
+                }
+                _ if is_default => family.default_font_index(),
+                _ => family.match_index(
                     attributes.stretch,
                     attributes.style,
                     attributes.weight,
                     true,
-                )?
+                )?,
             };
             let font_info = family.fonts().get(family_index)?;
             let blob = font_info.load(Some(source_cache))?;
@@ -267,6 +327,9 @@ struct CachedFamily {
     family: Entry<FamilyInfo>,
     best: Entry<QueryFont>,
     default: Entry<QueryFont>,
+    /// Indices of the fonts that cover the characters of the query, if the
+    /// family has fonts with unicode ranges.
+    coverage: Option<SmallVec<[usize; 4]>>,
 }
 
 impl CachedFamily {
@@ -276,6 +339,7 @@ impl CachedFamily {
             family: Entry::Vacant,
             best: Entry::Vacant,
             default: Entry::Vacant,
+            coverage: None,
         }
     }
 
@@ -291,3 +355,74 @@ enum Entry<T> {
     Vacant,
     Error,
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::{Stretch, Style, Weight};
+
+    /// Returns a collection with a family that is split into fonts for
+    /// lowercase letters in two weights and for uppercase letters.
+    fn subset_collection() -> Collection {
+        let mut collection = testing::collection(false);
+        for (weight, ranges) in [
+            ((100., 400.), 0x61..=0x7A),
+            ((500., 900.), 0x61..=0x7A),
+            ((100., 900.), 0x41..=0x5A),
+        ] {
+            let descriptor = testing::descriptor("Subset", Some(weight), Some(ranges));
+            testing::register(&mut collection, &descriptor);
+        }
+        collection
+    }
+
+    /// Returns the indices of the fonts in the family that match the
+    /// characters.
+    fn matches(collection: &mut Collection, chars: &str, weight: Weight) -> Vec<usize> {
+        let mut source_cache = SourceCache::default();
+        let mut query = collection.query(&mut source_cache);
+        query.set_families(["Subset"]);
+        query.set_attributes(Attributes::new(Stretch::NORMAL, Style::Normal, weight));
+        query.set_chars(chars.chars());
+        let mut fonts = vec![];
+        query.matches_with(|font| {
+            fonts.push(font.family.1);
+            QueryStatus::Continue
+        });
+        fonts
+    }
+
+    #[test]
+    fn unicode_ranges() {
+        let mut collection = subset_collection();
+        assert_eq!(matches(&mut collection, "a", Weight::NORMAL), [0]);
+        // The best match is followed by the default font of the family if
+        // it covers the characters.
+        assert_eq!(matches(&mut collection, "a", Weight::BOLD), [1, 0]);
+        assert_eq!(matches(&mut collection, "A", Weight::BOLD), [2]);
+        // Any character within the ranges of a font is enough.
+        assert_eq!(matches(&mut collection, "1A", Weight::NORMAL), [2]);
+        assert!(matches(&mut collection, "1", Weight::NORMAL).is_empty());
+        // All fonts are candidates when no characters are set.
+        assert_eq!(matches(&mut collection, "", Weight::BOLD), [1, 0]);
+    }
+
+    #[test]
+    fn unicode_ranges_with_cached_query() {
+        let mut collection = subset_collection();
+        let mut source_cache = SourceCache::default();
+        let mut query = collection.query(&mut source_cache);
+        query.set_families(["Subset"]);
+        // The selected font changes with the characters of the query.
+        for (chars, expected) in [("a", 0), ("A", 2), ("b", 0)] {
+            query.set_chars(chars.chars());
+            let mut fonts = vec![];
+            query.matches_with(|font| {
+                fonts.push(font.family.1);
+                QueryStatus::Continue
+            });
+            assert_eq!(fonts, [expected], "{chars:?}");
+        }
+    }
+}
@@ -59,6 +59,27 @@ impl FallbackMap {
         self.set_or_append(key, families, false)
     }
 
+    /// Removes the family identifier from the fallback families for all
+    /// scripts and languages.
+    ///
+    /// Entries that are left without any families are removed so that
+    /// fallback resolution can consult the system again.
+    pub fn remove_family(&mut self, id: FamilyId) {
+        self.fallbacks.retain(|_, entry| {
+            if let Some(families) = &mut entry.default {
+                families.retain(|family| *family != id);
+                if families.is_empty() {
+                    entry.default = None;
+                }
+            }
+            entry.others.retain_mut(|(_, families)| {
+                families.retain(|family| *family != id);
+                !families.is_empty()
+            });
+            entry.default.is_some() || !entry.others.is_empty()
+        });
+    }
+
     fn set_or_append(
         &mut self,
         key: impl Into<FallbackKey>,
@@ -88,6 +88,14 @@ impl FamilyNameMap {
         }
     }
 
+    /// Removes the family with the given identifier along with all of its
+    /// names.
+    pub fn remove(&mut self, id: FamilyId) -> Option<FamilyName> {
+        let name = self.id_map.remove(&id)?;
+        self.name_map.retain(|_, name| name.id != id);
+        Some(name)
+    }
+
     /// Returns an iterator over all of the font family names.
     pub fn iter(&self) -> impl Iterator<Item = &FamilyName> + Clone {
         self.name_map.values()
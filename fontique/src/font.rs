@@ -7,10 +7,12 @@ use super::attributes::{Stretch, Style, Weight};
 use super::source::{SourceInfo, SourceKind};
 #[cfg(feature = "std")]
 use super::{source_cache::SourceCache, Blob};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::ops::RangeInclusive;
 use skrifa::raw::{types::Tag, FontRef, TableProvider as _};
 use smallvec::SmallVec;
 
-type AxisVec = SmallVec<[AxisInfo; 1]>;
+pub(crate) type AxisVec = SmallVec<[AxisInfo; 1]>;
 
 /// Representation of a single font in a family.
 #[derive(Clone, Debug)]
@@ -20,10 +22,34 @@ pub struct FontInfo {
     stretch: Stretch,
     style: Style,
     weight: Weight,
+    weight_range: (Weight, Weight),
+    unicode_ranges: Option<Arc<[RangeInclusive<u32>]>>,
     axes: AxisVec,
     attr_axes: u8,
 }
 
+/// Descriptors that override the attributes read from the tables of
+/// registered fonts, in the manner of a CSS `@font-face` rule.
+///
+/// Attributes that are `None` are read from the font. New attributes may be
+/// added, so descriptors are created with [`Default`] and the attributes to
+/// override are assigned afterwards.
+#[derive(Clone, Default, Debug)]
+#[non_exhaustive]
+pub struct FontDescriptor {
+    /// Name of the family that the fonts are added to.
+    pub family_name: Option<String>,
+    /// Inclusive range of weights supported by the fonts. A font with a
+    /// single weight has a range that starts and ends at that weight.
+    pub weight: Option<(Weight, Weight)>,
+    /// Visual style of the fonts.
+    pub style: Option<Style>,
+    /// Visual width of the fonts.
+    pub stretch: Option<Stretch>,
+    /// Inclusive ranges of the codepoints that the fonts are used for.
+    pub unicode_ranges: Option<Vec<RangeInclusive<u32>>>,
+}
+
 impl FontInfo {
     /// Creates a new font object from the given source and index.
     pub fn from_source(source: SourceInfo, index: u32) -> Option<Self> {
@@ -83,6 +109,28 @@ impl FontInfo {
         self.weight
     }
 
+    /// Returns the inclusive range of weights supported by the font.
+    ///
+    /// This contains only the weight of the font unless a range was
+    /// specified by a [`FontDescriptor`].
+    pub fn weight_range(&self) -> (Weight, Weight) {
+        self.weight_range
+    }
+
+    /// Returns the inclusive ranges of the codepoints that the font is used
+    /// for, or `None` if it may be used for any codepoint.
+    pub fn unicode_ranges(&self) -> Option<&[RangeInclusive<u32>]> {
+        self.unicode_ranges.as_deref()
+    }
+
+    /// Returns true if the character is within the unicode ranges of the
+    /// font, or if the font has no unicode ranges.
+    pub fn covers_char(&self, ch: char) -> bool {
+        self.unicode_ranges.as_ref().map_or(true, |ranges| {
+            ranges.iter().any(|range| range.contains(&(ch as u32)))
+        })
+    }
+
     /// Returns synthesis suggestions for this font with the given attributes.
     pub fn synthesis(&self, stretch: Stretch, style: Style, weight: Weight) -> Synthesis {
         let mut synth = Synthesis::default();
@@ -95,7 +143,7 @@ impl FontInfo {
             if self.has_weight_axis() {
                 synth.vars[len] = (Tag::new(b"wght"), weight.value());
                 len += 1;
-            } else if weight.value() > self.weight.value() {
+            } else if weight.value() > self.weight_range.1.value() {
                 synth.embolden = true;
             }
         }
@@ -169,39 +217,67 @@ impl FontInfo {
 impl FontInfo {
     pub(crate) fn from_font_ref(font: &FontRef, source: SourceInfo, index: u32) -> Option<Self> {
         let (stretch, style, weight) = read_attributes(font);
-        let (axes, attr_axes) = if let Ok(fvar_axes) = font.fvar().and_then(|fvar| fvar.axes()) {
-            let mut axes = SmallVec::<[AxisInfo; 1]>::with_capacity(fvar_axes.len());
-            let mut attrs_axes = 0u8;
-            for fvar_axis in fvar_axes {
-                let axis = AxisInfo {
-                    tag: fvar_axis.axis_tag(),
-                    min: fvar_axis.min_value().to_f32(),
-                    max: fvar_axis.max_value().to_f32(),
-                    default: fvar_axis.default_value().to_f32(),
-                };
-                axes.push(axis);
-                match &axis.tag.to_be_bytes() {
-                    b"wght" => attrs_axes |= WEIGHT_AXIS,
-                    b"wdth" => attrs_axes |= WIDTH_AXIS,
-                    b"slnt" => attrs_axes |= SLANT_AXIS,
-                    b"ital" => attrs_axes |= ITALIC_AXIS,
-                    b"opsz" => attrs_axes |= OPTICAL_SIZE_AXIS,
-                    _ => {}
-                }
+        let axes = read_axes(font);
+        Some(Self::from_attributes(
+            source, index, stretch, style, weight, axes,
+        ))
+    }
+
+    /// Creates a font from attributes that were previously read from the
+    /// tables of the font.
+    pub(crate) fn from_attributes(
+        source: SourceInfo,
+        index: u32,
+        stretch: Stretch,
+        style: Style,
+        weight: Weight,
+        axes: AxisVec,
+    ) -> Self {
+        let mut attr_axes = 0u8;
+        for axis in &axes {
+            match &axis.tag.to_be_bytes() {
+                b"wght" => attr_axes |= WEIGHT_AXIS,
+                b"wdth" => attr_axes |= WIDTH_AXIS,
+                b"slnt" => attr_axes |= SLANT_AXIS,
+                b"ital" => attr_axes |= ITALIC_AXIS,
+                b"opsz" => attr_axes |= OPTICAL_SIZE_AXIS,
+                _ => {}
             }
-            (axes, attrs_axes)
-        } else {
-            (Default::default(), Default::default())
-        };
-        Some(Self {
+        }
+        Self {
             source,
             index,
             stretch,
             style,
             weight,
+            weight_range: (weight, weight),
+            unicode_ranges: None,
             axes,
             attr_axes,
-        })
+        }
+    }
+
+    /// Replaces the attributes of the font with those specified by the
+    /// descriptor.
+    pub(crate) fn apply_descriptor(&mut self, descriptor: &FontDescriptor) {
+        if let Some((min, max)) = descriptor.weight {
+            let (min, max) = if min.value() > max.value() {
+                (max, min)
+            } else {
+                (min, max)
+            };
+            self.weight = Weight::new(self.weight.value().clamp(min.value(), max.value()));
+            self.weight_range = (min, max);
+        }
+        if let Some(style) = descriptor.style {
+            self.style = style;
+        }
+        if let Some(stretch) = descriptor.stretch {
+            self.stretch = stretch;
+        }
+        if let Some(ranges) = &descriptor.unicode_ranges {
+            self.unicode_ranges = Some(ranges.as_slice().into());
+        }
     }
 
     #[allow(unused)]
@@ -219,6 +295,7 @@ impl FontInfo {
         }
         if self.weight == Weight::default() {
             self.weight = weight;
+            self.weight_range = (weight, weight);
         }
     }
 }
@@ -279,7 +356,22 @@ impl Synthesis {
     }
 }
 
-fn read_attributes(font: &FontRef) -> (Stretch, Style, Weight) {
+pub(crate) fn read_axes(font: &FontRef) -> AxisVec {
+    let Ok(fvar_axes) = font.fvar().and_then(|fvar| fvar.axes()) else {
+        return AxisVec::new();
+    };
+    fvar_axes
+        .iter()
+        .map(|fvar_axis| AxisInfo {
+            tag: fvar_axis.axis_tag(),
+            min: fvar_axis.min_value().to_f32(),
+            max: fvar_axis.max_value().to_f32(),
+            default: fvar_axis.default_value().to_f32(),
+        })
+        .collect()
+}
+
+pub(crate) fn read_attributes(font: &FontRef) -> (Stretch, Style, Weight) {
     use skrifa::raw::{
         tables::{
             head::{Head, MacStyle},
@@ -147,4 +147,11 @@ impl GenericFamilyMap {
     pub fn append(&mut self, generic: GenericFamily, families: impl Iterator<Item = FamilyId>) {
         self.map[generic as usize].extend(families);
     }
+
+    /// Removes the family identifier from the lists for all generic families.
+    pub fn remove_family(&mut self, id: FamilyId) {
+        for families in &mut self.map {
+            families.retain(|family| *family != id);
+        }
+    }
 }
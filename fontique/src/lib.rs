@@ -25,9 +25,17 @@ mod scan;
 mod script;
 mod source;
 
+#[cfg(feature = "std")]
+mod scan_cache;
 #[cfg(feature = "std")]
 mod source_cache;
 
+#[cfg(feature = "woff")]
+mod woff;
+
+#[cfg(all(test, feature = "std"))]
+mod testing;
+
 pub use icu_locid::LanguageIdentifier as Language;
 pub use peniko::Blob;
 
@@ -35,7 +43,7 @@ pub use attributes::{Attributes, Stretch, Style, Weight};
 pub use collection::{Collection, CollectionOptions, Query, QueryFamily, QueryFont, QueryStatus};
 pub use fallback::FallbackKey;
 pub use family::{FamilyId, FamilyInfo};
-pub use font::{AxisInfo, FontInfo, Synthesis};
+pub use font::{AxisInfo, FontDescriptor, FontInfo, Synthesis};
 pub use generic::GenericFamily;
 pub use script::Script;
 pub use source::{SourceId, SourceInfo, SourceKind};
@@ -16,12 +16,30 @@ pub fn match_font(
     weight: Weight,
     synthesize_style: bool,
 ) -> Option<usize> {
-    const OBLIQUE_THRESHOLD: f32 = DEFAULT_OBLIQUE_ANGLE;
     match set.len() {
-        0 => return None,
-        1 => return Some(0),
-        _ => {}
+        0 => None,
+        1 => Some(0),
+        _ => match_font_in(
+            set.iter().enumerate(),
+            stretch,
+            style,
+            weight,
+            synthesize_style,
+        ),
     }
+}
+
+/// Selects the best font from the candidates, which are pairs of an index
+/// and a font, and returns the index of the selected candidate.
+pub fn match_font_in<'a>(
+    candidates: impl Iterator<Item = (usize, &'a FontInfo)>,
+    stretch: Stretch,
+    style: Style,
+    weight: Weight,
+    synthesize_style: bool,
+) -> Option<usize> {
+    const OBLIQUE_THRESHOLD: f32 = DEFAULT_OBLIQUE_ANGLE;
+    let weight = weight.value();
     #[derive(Copy, Clone)]
     struct Candidate {
         index: usize,
@@ -30,19 +48,26 @@ pub fn match_font(
         weight: f32,
         has_slnt: bool,
     }
-    let mut set: SmallVec<[Candidate; 16]> = set
-        .iter()
-        .enumerate()
+    let mut set: SmallVec<[Candidate; 16]> = candidates
         .map(|(i, font)| Candidate {
             index: i,
             stretch: (font.stretch().ratio() * 100.0) as i32,
             style: font.style(),
-            weight: font.weight().value(),
+            // Fonts that support a range of weights are matched by the
+            // weight in that range that is closest to the desired weight.
+            weight: {
+                let (min, max) = font.weight_range();
+                weight.clamp(min.value(), max.value())
+            },
             has_slnt: font.has_slant_axis(),
         })
         .collect();
+    match set.len() {
+        0 => return None,
+        1 => return Some(set[0].index),
+        _ => {}
+    }
     let stretch = (stretch.ratio() * 100.0) as i32;
-    let weight = weight.value();
     // font-stretch is tried first:
     let mut use_stretch = set[0].stretch;
     if !set.iter().any(|f| f.stretch == stretch) {
@@ -462,3 +487,54 @@ fn oblique_style(style: Style) -> Option<(Style, f32)> {
         _ => None,
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Returns fonts that support the specified ranges of weights.
+    fn fonts_with_weights(ranges: &[(f32, f32)]) -> Vec<FontInfo> {
+        let mut collection = testing::collection(false);
+        ranges
+            .iter()
+            .map(|&weight| {
+                let descriptor = testing::descriptor("Ranged", Some(weight), None);
+                testing::register(&mut collection, &descriptor).1.remove(0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weight_ranges() {
+        let fonts = fonts_with_weights(&[(100., 400.), (500., 900.)]);
+        let select = |weight| {
+            match_font(
+                &fonts,
+                Stretch::NORMAL,
+                Style::Normal,
+                Weight::new(weight),
+                true,
+            )
+        };
+        assert_eq!(select(300.), Some(0));
+        assert_eq!(select(400.), Some(0));
+        // Weights between 400 and 500 prefer heavier weights up to 500.
+        assert_eq!(select(450.), Some(1));
+        assert_eq!(select(800.), Some(1));
+        assert_eq!(select(950.), Some(1));
+        // Lighter weights prefer lighter fonts.
+        let fonts = fonts_with_weights(&[(200., 300.), (600., 700.)]);
+        let select = |weight| {
+            match_font(
+                &fonts,
+                Stretch::NORMAL,
+                Style::Normal,
+                Weight::new(weight),
+                true,
+            )
+        };
+        assert_eq!(select(350.), Some(0));
+        assert_eq!(select(550.), Some(1));
+    }
+}
@@ -15,7 +15,14 @@ use hashbrown::HashMap;
 use skrifa::raw::{tables::name, types::NameId, FileRef, FontRef, TableProvider as _};
 use smallvec::SmallVec;
 #[cfg(feature = "std")]
-use {super::source::SourcePathMap, std::path::Path};
+use {
+    super::{
+        font::{read_attributes, read_axes},
+        scan_cache::{CachedFont, ScanCache},
+        source::SourcePathMap,
+    },
+    std::{fs::Metadata, path::Path},
+};
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
@@ -34,8 +41,23 @@ pub struct ScannedCollection {
 impl ScannedCollection {
     /// Creates a new collection by scanning the given paths for
     /// font files.
-    pub fn from_paths(paths: impl IntoIterator<Item = impl AsRef<Path>>, max_depth: u32) -> Self {
-        scan_collection(paths, max_depth)
+    ///
+    /// If a cache path is given, the results of previous scans are loaded
+    /// from it and only files that have been added or modified since are
+    /// scanned. The cache is updated afterward.
+    pub fn from_paths(
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+        max_depth: u32,
+        cache_path: Option<&Path>,
+    ) -> Self {
+        let Some(cache_path) = cache_path else {
+            return scan_collection(paths, max_depth, None);
+        };
+        let mut cache = ScanCache::load(cache_path);
+        let collection = scan_collection(paths, max_depth, Some(&mut cache));
+        // Failing to write the cache only means that the next scan is slower.
+        let _ = cache.save(cache_path);
+        collection
     }
 }
 
@@ -78,66 +100,94 @@ pub fn scan_memory<'a>(buf: &'a [u8], mut f: impl FnMut(&ScannedFont<'a>)) {
 fn scan_collection(
     paths: impl IntoIterator<Item = impl AsRef<Path>>,
     max_depth: u32,
+    mut cache: Option<&mut ScanCache>,
 ) -> ScannedCollection {
     let mut collection = ScannedCollection::default();
     let mut families: HashMap<FamilyId, (FamilyName, SmallVec<[FontInfo; 4]>)> = Default::default();
-    let mut postscript_name = String::default();
+    let mut add_fonts = |path: &Path, fonts: &[CachedFont]| {
+        for font in fonts {
+            let [first_name, other_names @ ..] = font.family_names.as_slice() else {
+                continue;
+            };
+            let data = collection.data_paths.get_or_insert(path);
+            let info = FontInfo::from_attributes(
+                data,
+                font.index,
+                font.stretch,
+                font.style,
+                font.weight,
+                font.axes.clone(),
+            );
+            let name = collection.family_names.get_or_insert(first_name);
+            for other_name in other_names {
+                collection.family_names.add_alias(name.id(), other_name);
+            }
+            collection
+                .postscript_names
+                .insert(font.postscript_name.clone(), name.id());
+            families
+                .entry(name.id())
+                .or_insert_with(|| (name.clone(), Default::default()))
+                .1
+                .push(info);
+        }
+    };
+    for path in paths {
+        visit_files(path.as_ref(), max_depth, 0, &mut |path, metadata| {
+            if let Some(cache) = cache.as_deref_mut() {
+                add_fonts(path, cache.get_or_scan(path, metadata, || scan_file(path)));
+            } else {
+                add_fonts(path, &scan_file(path));
+            }
+        });
+    }
+    collection.families.extend(
+        families
+            .drain()
+            .map(|(id, (name, fonts))| (id, FamilyInfo::new(name, fonts))),
+    );
+    collection
+}
+
+#[cfg(feature = "std")]
+/// Scans a single file and returns the fonts that have both family and
+/// PostScript names.
+fn scan_file(path: &Path) -> Vec<CachedFont> {
+    let mut fonts = vec![];
     let mut name_pool = vec![];
-    let mut names = vec![];
-    scan_paths(paths, max_depth, |scanned_font| {
-        let Some(path) = &scanned_font.path else {
-            return;
-        };
-        name_pool.append(&mut names);
-        postscript_name.clear();
+    scan_file_impl(path, &mut |scanned_font| {
+        let mut family_names = vec![];
         if !all_names(
             &scanned_font.name_table,
             NameId::TYPOGRAPHIC_FAMILY_NAME,
             &mut name_pool,
-            &mut names,
+            &mut family_names,
         ) && !all_names(
             &scanned_font.name_table,
             NameId::FAMILY_NAME,
             &mut name_pool,
-            &mut names,
+            &mut family_names,
         ) {
             return;
         }
-        let postscript_chars = scanned_font
+        let Some(postscript_name) = scanned_font
             .english_or_first_name(NameId::POSTSCRIPT_NAME)
-            .map(|name| name.chars());
-        if let Some(chars) = postscript_chars {
-            postscript_name.extend(chars);
-        } else {
-            return;
-        }
-        let data = collection.data_paths.get_or_insert(path);
-        let Some(font) = FontInfo::from_font_ref(&scanned_font.font, data, scanned_font.index)
+            .map(|name| name.chars().collect())
         else {
             return;
         };
-        let [first_name, other_names @ ..] = names.as_slice() else {
-            return;
-        };
-        let name = collection.family_names.get_or_insert(first_name);
-        for other_name in other_names {
-            collection.family_names.add_alias(name.id(), other_name);
-        }
-        collection
-            .postscript_names
-            .insert(postscript_name.clone(), name.id());
-        families
-            .entry(name.id())
-            .or_insert_with(|| (name.clone(), Default::default()))
-            .1
-            .push(font);
+        let (stretch, style, weight) = read_attributes(&scanned_font.font);
+        fonts.push(CachedFont {
+            index: scanned_font.index,
+            family_names,
+            postscript_name,
+            stretch,
+            style,
+            weight,
+            axes: read_axes(&scanned_font.font),
+        });
     });
-    collection.families.extend(
-        families
-            .drain()
-            .map(|(id, (name, fonts))| (id, FamilyInfo::new(name, fonts))),
-    );
-    collection
+    fonts
 }
 
 #[cfg(feature = "std")]
@@ -146,6 +196,20 @@ fn scan_path_impl(
     max_depth: u32,
     f: &mut impl FnMut(&ScannedFont),
     depth: u32,
+) -> Option<()> {
+    visit_files(path, max_depth, depth, &mut |path, _| {
+        scan_file_impl(path, f);
+    })
+}
+
+#[cfg(feature = "std")]
+/// Invokes the given function for each file in the path, descending into
+/// directories up to the maximum depth.
+fn visit_files(
+    path: &Path,
+    max_depth: u32,
+    depth: u32,
+    f: &mut impl FnMut(&Path, &Metadata),
 ) -> Option<()> {
     let metadata = path.metadata().ok()?;
     if metadata.is_dir() {
@@ -153,16 +217,21 @@ fn scan_path_impl(
             return None;
         }
         for entry in std::fs::read_dir(path).ok()?.filter_map(|entry| entry.ok()) {
-            scan_path_impl(entry.path().as_path(), max_depth, f, depth + 1);
+            visit_files(entry.path().as_path(), max_depth, depth + 1, f);
         }
     } else {
-        let file = std::fs::File::open(path).ok()?;
-        let mapped = unsafe { memmap2::Mmap::map(&file) }.ok()?;
-        scan_memory_impl(&mapped, Some(path), f);
+        f(path, &metadata);
     }
     Some(())
 }
 
+#[cfg(feature = "std")]
+fn scan_file_impl(path: &Path, f: &mut impl FnMut(&ScannedFont)) -> Option<()> {
+    let file = std::fs::File::open(path).ok()?;
+    let mapped = unsafe { memmap2::Mmap::map(&file) }.ok()?;
+    scan_memory_impl(&mapped, Some(path), f)
+}
+
 #[cfg(feature = "std")]
 type ScanMemoryPathType<'a> = Option<&'a Path>;
 
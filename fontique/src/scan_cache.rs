@@ -0,0 +1,514 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Persistent cache for the results of scanning font files.
+
+use super::{
+    attributes::{Stretch, Style, Weight},
+    font::{AxisInfo, AxisVec},
+};
+use hashbrown::HashMap;
+use skrifa::raw::types::Tag;
+use std::{
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::UNIX_EPOCH,
+};
+
+const MAGIC: &[u8; 4] = b"FQSC";
+const VERSION: u32 = 1;
+
+/// Font discovered while scanning a file.
+#[derive(Clone, Debug)]
+pub struct CachedFont {
+    /// Index of the font in the file.
+    pub index: u32,
+    /// Family names with the preferred name first.
+    pub family_names: Vec<String>,
+    pub postscript_name: String,
+    pub stretch: Stretch,
+    pub style: Style,
+    pub weight: Weight,
+    pub axes: AxisVec,
+}
+
+/// Modification time and size used to detect changes to a file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct FileStamp {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl FileStamp {
+    fn new(metadata: &Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+struct CachedFile {
+    stamp: Option<FileStamp>,
+    fonts: Vec<CachedFont>,
+}
+
+/// Scanning results keyed by file path.
+///
+/// Entries that are not visited during a scan are dropped when the cache is
+/// saved.
+#[derive(Default)]
+pub struct ScanCache {
+    /// Entries loaded from disk that have not yet been visited.
+    previous: HashMap<PathBuf, CachedFile>,
+    /// Entries visited during the current scan.
+    current: HashMap<PathBuf, CachedFile>,
+    /// True if the cache differs from the one that was loaded.
+    dirty: bool,
+}
+
+impl ScanCache {
+    /// Loads the cache from the given path.
+    ///
+    /// Returns an empty cache if the file is missing, malformed or was
+    /// written by a different version.
+    pub fn load(path: &Path) -> Self {
+        let previous = std::fs::read(path)
+            .ok()
+            .and_then(|data| read_files(&data))
+            .unwrap_or_default();
+        Self {
+            previous,
+            current: Default::default(),
+            dirty: false,
+        }
+    }
+
+    /// Writes the cache to the given path if it has changed since it was
+    /// loaded.
+    pub fn save(&mut self, path: &Path) -> std::io::Result<()> {
+        if !self.dirty && self.previous.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so that concurrent readers never
+        // observe a partially written cache. The name is unique to this
+        // process and save so that concurrent writers don't clobber each
+        // other's temporary files.
+        static SAVE_COUNT: AtomicUsize = AtomicUsize::new(0);
+        let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            SAVE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp_path = path.with_file_name(temp_name);
+        let result = std::fs::write(&temp_path, write_files(&self.current))
+            .and_then(|_| std::fs::rename(&temp_path, path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        result?;
+        self.previous.clear();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Returns the fonts contained in the file at the given path, invoking
+    /// the scan function if the file is not cached or has been modified.
+    pub fn get_or_scan(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        scan: impl FnOnce() -> Vec<CachedFont>,
+    ) -> &[CachedFont] {
+        self.get_or_scan_stamped(path, FileStamp::new(metadata), scan)
+    }
+
+    fn get_or_scan_stamped(
+        &mut self,
+        path: &Path,
+        stamp: Option<FileStamp>,
+        scan: impl FnOnce() -> Vec<CachedFont>,
+    ) -> &[CachedFont] {
+        // Files without a modification time are scanned every time.
+        let cached = self
+            .previous
+            .remove(path)
+            .or_else(|| self.current.remove(path));
+        let file = match cached {
+            Some(file) if stamp.is_some() && file.stamp == stamp => file,
+            _ => {
+                self.dirty = true;
+                CachedFile {
+                    stamp,
+                    fonts: scan(),
+                }
+            }
+        };
+        &self
+            .current
+            .entry(path.into())
+            .insert(file)
+            .into_mut()
+            .fonts
+    }
+}
+
+fn write_files(files: &HashMap<PathBuf, CachedFile>) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(MAGIC);
+    w.u32(VERSION);
+    // Paths that are not valid UTF-8 are rescanned on every launch.
+    let files: Vec<_> = files
+        .iter()
+        .filter_map(|(path, file)| Some((path.to_str()?, file.stamp?, &file.fonts)))
+        .collect();
+    w.u32(files.len() as u32);
+    for (path, stamp, fonts) in files {
+        w.str(path);
+        w.u64(stamp.size);
+        w.u64(stamp.modified_secs);
+        w.u32(stamp.modified_nanos);
+        w.u32(fonts.len() as u32);
+        for font in fonts {
+            w.u32(font.index);
+            w.u32(font.family_names.len() as u32);
+            for name in &font.family_names {
+                w.str(name);
+            }
+            w.str(&font.postscript_name);
+            w.f32(font.stretch.ratio());
+            match font.style {
+                Style::Normal => w.u8(0),
+                Style::Italic => w.u8(1),
+                Style::Oblique(None) => w.u8(2),
+                Style::Oblique(Some(angle)) => {
+                    w.u8(3);
+                    w.f32(angle);
+                }
+            }
+            w.f32(font.weight.value());
+            w.u32(font.axes.len() as u32);
+            for axis in &font.axes {
+                w.bytes(&axis.tag.to_be_bytes());
+                w.f32(axis.min);
+                w.f32(axis.max);
+                w.f32(axis.default);
+            }
+        }
+    }
+    w.0
+}
+
+fn read_files(data: &[u8]) -> Option<HashMap<PathBuf, CachedFile>> {
+    let mut r = Reader(data);
+    if r.bytes(4)? != MAGIC || r.u32()? != VERSION {
+        return None;
+    }
+    let mut files = HashMap::default();
+    for _ in 0..r.u32()? {
+        let path = PathBuf::from(r.string()?);
+        let stamp = Some(FileStamp {
+            size: r.u64()?,
+            modified_secs: r.u64()?,
+            modified_nanos: r.u32()?,
+        });
+        let mut fonts = vec![];
+        for _ in 0..r.u32()? {
+            let index = r.u32()?;
+            let mut family_names = vec![];
+            for _ in 0..r.u32()? {
+                family_names.push(r.string()?);
+            }
+            let postscript_name = r.string()?;
+            let stretch = Stretch::from_ratio(r.f32()?);
+            let style = match r.u8()? {
+                0 => Style::Normal,
+                1 => Style::Italic,
+                2 => Style::Oblique(None),
+                3 => Style::Oblique(Some(r.f32()?)),
+                _ => return None,
+            };
+            let weight = Weight::new(r.f32()?);
+            let mut axes = AxisVec::new();
+            for _ in 0..r.u32()? {
+                axes.push(AxisInfo {
+                    tag: Tag::from_be_bytes(r.bytes(4)?.try_into().ok()?),
+                    min: r.f32()?,
+                    max: r.f32()?,
+                    default: r.f32()?,
+                });
+            }
+            fonts.push(CachedFont {
+                index,
+                family_names,
+                postscript_name,
+                stretch,
+                style,
+                weight,
+                axes,
+            });
+        }
+        files.insert(path, CachedFile { stamp, fonts });
+    }
+    Some(files)
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes(value.as_bytes());
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font(index: u32, style: Style) -> CachedFont {
+        let mut axes = AxisVec::new();
+        axes.push(AxisInfo {
+            tag: Tag::new(b"wght"),
+            min: 100.0,
+            max: 900.0,
+            default: 400.0,
+        });
+        axes.push(AxisInfo {
+            tag: Tag::new(b"slnt"),
+            min: -12.0,
+            max: 0.0,
+            default: 0.0,
+        });
+        CachedFont {
+            index,
+            family_names: vec!["Test Sans".into(), "Test".into()],
+            postscript_name: "TestSans-Regular".into(),
+            stretch: Stretch::CONDENSED,
+            style,
+            weight: Weight::new(350.0),
+            axes,
+        }
+    }
+
+    fn stamp(size: u64, modified_secs: u64) -> Option<FileStamp> {
+        Some(FileStamp {
+            size,
+            modified_secs,
+            modified_nanos: 500,
+        })
+    }
+
+    fn assert_fonts_eq(a: &[CachedFont], b: &[CachedFont]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.index, b.index);
+            assert_eq!(a.family_names, b.family_names);
+            assert_eq!(a.postscript_name, b.postscript_name);
+            assert_eq!(a.stretch, b.stretch);
+            assert_eq!(a.style, b.style);
+            assert_eq!(a.weight, b.weight);
+            assert_eq!(a.axes.len(), b.axes.len());
+            for (a, b) in a.axes.iter().zip(&b.axes) {
+                assert_eq!(a.tag, b.tag);
+                assert_eq!(a.min, b.min);
+                assert_eq!(a.max, b.max);
+                assert_eq!(a.default, b.default);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut files = HashMap::default();
+        let fonts = vec![
+            font(0, Style::Normal),
+            font(1, Style::Italic),
+            font(2, Style::Oblique(None)),
+            font(3, Style::Oblique(Some(-10.5))),
+        ];
+        files.insert(
+            PathBuf::from("/fonts/test.ttc"),
+            CachedFile {
+                stamp: stamp(1234, 5678),
+                fonts: fonts.clone(),
+            },
+        );
+        files.insert(
+            PathBuf::from("/fonts/empty.ttf"),
+            CachedFile {
+                stamp: stamp(0, 1),
+                fonts: vec![],
+            },
+        );
+        // Files without a stamp are not written.
+        files.insert(
+            PathBuf::from("/fonts/unstamped.ttf"),
+            CachedFile {
+                stamp: None,
+                fonts: fonts.clone(),
+            },
+        );
+        let data = write_files(&files);
+        let read = read_files(&data).unwrap();
+        assert_eq!(read.len(), 2);
+        let file = &read[Path::new("/fonts/test.ttc")];
+        assert_eq!(file.stamp, stamp(1234, 5678));
+        assert_fonts_eq(&file.fonts, &fonts);
+        let file = &read[Path::new("/fonts/empty.ttf")];
+        assert_eq!(file.stamp, stamp(0, 1));
+        assert!(file.fonts.is_empty());
+    }
+
+    #[test]
+    fn read_malformed() {
+        let mut files = HashMap::default();
+        files.insert(
+            PathBuf::from("/fonts/test.ttf"),
+            CachedFile {
+                stamp: stamp(1, 2),
+                fonts: vec![font(0, Style::Oblique(Some(14.0)))],
+            },
+        );
+        let data = write_files(&files);
+        for len in 0..data.len() {
+            assert!(read_files(&data[..len]).is_none());
+        }
+        let mut bad_version = data.clone();
+        bad_version[4] ^= 0xFF;
+        assert!(read_files(&bad_version).is_none());
+        let mut bad_magic = data;
+        bad_magic[0] = b'X';
+        assert!(read_files(&bad_magic).is_none());
+    }
+
+    /// Returns true if the cache invoked the scan function.
+    fn scanned(cache: &mut ScanCache, stamp: Option<FileStamp>) -> bool {
+        let mut scanned = false;
+        cache.get_or_scan_stamped(Path::new("/fonts/test.ttf"), stamp, || {
+            scanned = true;
+            vec![font(0, Style::Normal)]
+        });
+        scanned
+    }
+
+    #[test]
+    fn rescan_on_change() {
+        let mut cache = ScanCache::default();
+        assert!(scanned(&mut cache, stamp(100, 10)));
+        assert!(!scanned(&mut cache, stamp(100, 10)));
+        // Size changed.
+        assert!(scanned(&mut cache, stamp(200, 10)));
+        // Modification time changed.
+        assert!(scanned(&mut cache, stamp(200, 11)));
+        let nanos_changed = Some(FileStamp {
+            modified_nanos: 0,
+            ..stamp(200, 11).unwrap()
+        });
+        assert!(scanned(&mut cache, nanos_changed));
+        assert!(!scanned(&mut cache, nanos_changed));
+        // Files without a stamp are always scanned.
+        assert!(scanned(&mut cache, None));
+        assert!(scanned(&mut cache, None));
+    }
+
+    #[test]
+    fn load_and_save() {
+        let dir = std::env::temp_dir().join(format!("fontique-scan-cache-{}", std::process::id()));
+        let font_path = dir.join("font.ttf");
+        let cache_path = dir.join("cache").join("scan.cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&font_path, b"font").unwrap();
+        let scanned = |cache: &mut ScanCache| {
+            let metadata = std::fs::metadata(&font_path).unwrap();
+            let mut scanned = false;
+            let fonts = cache.get_or_scan(&font_path, &metadata, || {
+                scanned = true;
+                vec![font(0, Style::Italic)]
+            });
+            assert_fonts_eq(fonts, &[font(0, Style::Italic)]);
+            scanned
+        };
+        let mut cache = ScanCache::load(&cache_path);
+        assert!(scanned(&mut cache));
+        cache.save(&cache_path).unwrap();
+        let mut cache = ScanCache::load(&cache_path);
+        assert!(!scanned(&mut cache));
+        assert!(!cache.dirty);
+        // Changing the size of the file invalidates its entry.
+        std::fs::write(&font_path, b"a larger font").unwrap();
+        assert!(scanned(&mut cache));
+        cache.save(&cache_path).unwrap();
+        let mut cache = ScanCache::load(&cache_path);
+        assert!(!scanned(&mut cache));
+        // Only the cache itself is left in its directory.
+        let entries = std::fs::read_dir(cache_path.parent().unwrap())
+            .unwrap()
+            .count();
+        assert_eq!(entries, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Helpers shared by the unit tests.
+
+use core::ops::RangeInclusive;
+
+use crate::{Collection, CollectionOptions, FamilyId, FontDescriptor, FontInfo, Weight};
+
+pub(crate) const FONT: &[u8] = include_bytes!("../../testdata/ParleyTest.ttf");
+
+/// Returns a collection without system fonts.
+pub(crate) fn collection(shared: bool) -> Collection {
+    Collection::new(CollectionOptions {
+        shared,
+        system_fonts: false,
+    })
+}
+
+/// Returns a descriptor that adds fonts to the named family, with an
+/// optional range of weights and optional unicode ranges.
+pub(crate) fn descriptor(
+    family_name: &str,
+    weight: Option<(f32, f32)>,
+    unicode_ranges: Option<RangeInclusive<u32>>,
+) -> FontDescriptor {
+    FontDescriptor {
+        family_name: Some(family_name.into()),
+        weight: weight.map(|(min, max)| (Weight::new(min), Weight::new(max))),
+        unicode_ranges: unicode_ranges.map(|ranges| vec![ranges]),
+        ..Default::default()
+    }
+}
+
+/// Registers the test font with the descriptor and returns its family and
+/// fonts.
+pub(crate) fn register(
+    collection: &mut Collection,
+    descriptor: &FontDescriptor,
+) -> (FamilyId, Vec<FontInfo>) {
+    collection
+        .register_fonts_with_descriptor(FONT.to_vec(), descriptor)
+        .remove(0)
+}
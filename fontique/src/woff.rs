@@ -0,0 +1,824 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Decoding of WOFF and WOFF2 font data.
+//!
+//! Both formats are converted back to raw sfnt (or collection) data so that
+//! they can be scanned and loaded like any other font file.
+
+use std::io::Read as _;
+
+type Tag = [u8; 4];
+
+const GLYF: Tag = *b"glyf";
+const LOCA: Tag = *b"loca";
+const HMTX: Tag = *b"hmtx";
+const HHEA: Tag = *b"hhea";
+const HEAD: Tag = *b"head";
+const TTCF: u32 = u32::from_be_bytes(*b"ttcf");
+
+/// Tags with a predefined index in the WOFF2 table directory.
+const KNOWN_TAGS: [&Tag; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// Decodes WOFF or WOFF2 data to sfnt data.
+///
+/// Returns `None` if the data is not in either format or is malformed.
+pub fn decode(data: &[u8]) -> Option<Vec<u8>> {
+    match data.get(..4)? {
+        b"wOFF" => decode_woff(data),
+        b"wOF2" => decode_woff2(data),
+        _ => None,
+    }
+}
+
+fn decode_woff(data: &[u8]) -> Option<Vec<u8>> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let flavor = r.u32()?;
+    // Skip the length.
+    r.skip(4)?;
+    let num_tables = r.u16()? as usize;
+    // Skip the remainder of the 44 byte header.
+    r.skip(30)?;
+    let mut tables = Vec::with_capacity(num_tables);
+    for _ in 0..num_tables {
+        let tag = r.tag()?;
+        let offset = r.u32()? as usize;
+        let comp_length = r.u32()? as usize;
+        let orig_length = r.u32()? as usize;
+        // The checksum is recomputed when the sfnt is written.
+        r.skip(4)?;
+        let comp_data = data.get(offset..offset.checked_add(comp_length)?)?;
+        let table_data = if comp_length < orig_length {
+            let table_data =
+                miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(comp_data, orig_length)
+                    .ok()?;
+            if table_data.len() != orig_length {
+                return None;
+            }
+            table_data
+        } else if comp_length == orig_length {
+            comp_data.to_vec()
+        } else {
+            return None;
+        };
+        tables.push((offset, tag, table_data));
+    }
+    // The directory is sorted by tag, so restore the original order of the
+    // table data from the offsets.
+    tables.sort_by_key(|(offset, ..)| *offset);
+    let tables: Vec<_> = tables
+        .into_iter()
+        .map(|(_, tag, table_data)| (tag, table_data))
+        .collect();
+    let font = SfntFont {
+        flavor,
+        tables: (0..num_tables).collect(),
+    };
+    write_sfnt(&tables, &[font], None)
+}
+
+/// Entry in the WOFF2 table directory.
+struct Woff2Table {
+    tag: Tag,
+    transformed: bool,
+    /// Length of the table data in the decompressed stream.
+    length: usize,
+}
+
+fn decode_woff2(data: &[u8]) -> Option<Vec<u8>> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let flavor = r.u32()?;
+    // Skip the length.
+    r.skip(4)?;
+    let num_tables = r.u16()? as usize;
+    // Skip the reserved field.
+    r.skip(2)?;
+    let total_sfnt_size = r.u32()? as usize;
+    let total_compressed_size = r.u32()? as usize;
+    // Skip the remainder of the 48 byte header.
+    r.skip(24)?;
+    let mut entries = Vec::with_capacity(num_tables);
+    for _ in 0..num_tables {
+        let flags = r.u8()?;
+        let tag = match flags & 0x3F {
+            63 => r.tag()?,
+            index => *KNOWN_TAGS[index as usize],
+        };
+        let version = flags >> 6;
+        let orig_length = r.uint_base128()? as usize;
+        // For glyf and loca, version 0 is the transform and version 3 is
+        // the null transform. The reverse holds for all other tables.
+        let transformed = if tag == GLYF || tag == LOCA {
+            version == 0
+        } else {
+            version != 0
+        };
+        let length = if transformed {
+            r.uint_base128()? as usize
+        } else {
+            orig_length
+        };
+        entries.push(Woff2Table {
+            tag,
+            transformed,
+            length,
+        });
+    }
+    let mut collection_version = None;
+    let fonts = if flavor == TTCF {
+        collection_version = Some(r.u32()?);
+        let num_fonts = r.u16_255()? as usize;
+        let mut fonts = Vec::with_capacity(num_fonts);
+        for _ in 0..num_fonts {
+            let font_num_tables = r.u16_255()? as usize;
+            let flavor = r.u32()?;
+            let mut tables = Vec::with_capacity(font_num_tables);
+            for _ in 0..font_num_tables {
+                let index = r.u16_255()? as usize;
+                if index >= num_tables {
+                    return None;
+                }
+                tables.push(index);
+            }
+            fonts.push(SfntFont { flavor, tables });
+        }
+        fonts
+    } else {
+        vec![SfntFont {
+            flavor,
+            tables: (0..num_tables).collect(),
+        }]
+    };
+    let compressed = r.bytes(total_compressed_size)?;
+    let total_length = entries
+        .iter()
+        .try_fold(0usize, |total, entry| total.checked_add(entry.length))?;
+    // The table lengths are not validated by anything else, so bound them by
+    // the size of the reconstructed font. Transformed tables are usually
+    // smaller than the originals but allow some slack for pathological
+    // glyph data.
+    if total_length > total_sfnt_size.saturating_add(total_sfnt_size / 2) {
+        return None;
+    }
+    // Let the buffer grow as data is decompressed rather than trusting the
+    // header with an up front allocation.
+    let mut stream = Vec::new();
+    // Read one more byte than expected to detect oversized streams.
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(total_length as u64 + 1)
+        .read_to_end(&mut stream)
+        .ok()?;
+    if stream.len() != total_length {
+        return None;
+    }
+    let mut sources = Vec::with_capacity(num_tables);
+    let mut offset = 0;
+    for entry in &entries {
+        sources.push(&stream[offset..offset + entry.length]);
+        offset += entry.length;
+    }
+    let mut tables: Vec<Option<Vec<u8>>> = vec![None; num_tables];
+    // Minimum x coordinates of each glyph, keyed by glyf table index.
+    let mut x_mins: Vec<Option<Vec<i16>>> = vec![None; num_tables];
+    for font in &fonts {
+        let find = |tag: Tag| {
+            font.tables
+                .iter()
+                .copied()
+                .find(|&index| entries[index].tag == tag)
+        };
+        let glyf = find(GLYF);
+        let loca = find(LOCA);
+        if let (Some(glyf), Some(loca)) = (glyf, loca) {
+            if entries[glyf].transformed != entries[loca].transformed {
+                return None;
+            }
+            if entries[glyf].transformed && tables[glyf].is_none() {
+                let decoded = decode_glyf(sources[glyf])?;
+                tables[glyf] = Some(decoded.glyf);
+                tables[loca] = Some(decoded.loca);
+                x_mins[glyf] = Some(decoded.x_mins);
+            }
+        }
+        if let Some(hmtx) = find(HMTX) {
+            if entries[hmtx].transformed && tables[hmtx].is_none() {
+                // The hmtx transform derives side bearings from the glyf
+                // transform, so both must be present.
+                let x_mins = x_mins[glyf?].as_deref()?;
+                let hhea = sources[find(HHEA)?];
+                let num_h_metrics = Reader::new(hhea.get(34..)?).u16()? as usize;
+                tables[hmtx] = Some(decode_hmtx(sources[hmtx], num_h_metrics, x_mins)?);
+            }
+        }
+    }
+    let tables = entries
+        .iter()
+        .zip(tables)
+        .zip(sources)
+        .map(|((entry, table), source)| match table {
+            Some(table) => Some((entry.tag, table)),
+            // Any remaining transformed tables are unsupported.
+            None if entry.transformed => None,
+            None => Some((entry.tag, source.to_vec())),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    write_sfnt(&tables, &fonts, collection_version)
+}
+
+/// Result of reversing the glyf and loca transform.
+struct DecodedGlyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    x_mins: Vec<i16>,
+}
+
+fn decode_glyf(data: &[u8]) -> Option<DecodedGlyf> {
+    let mut r = Reader::new(data);
+    // Skip the reserved field.
+    r.skip(2)?;
+    let option_flags = r.u16()?;
+    let num_glyphs = r.u16()? as usize;
+    let index_format = r.u16()?;
+    let mut stream_sizes = [0; 7];
+    for size in &mut stream_sizes {
+        *size = r.u32()? as usize;
+    }
+    let [n_contour_size, n_points_size, flag_size, glyph_size, composite_size, bbox_size, instruction_size] =
+        stream_sizes;
+    let mut n_contour_stream = Reader::new(r.bytes(n_contour_size)?);
+    let mut n_points_stream = Reader::new(r.bytes(n_points_size)?);
+    let mut flag_stream = Reader::new(r.bytes(flag_size)?);
+    let mut glyph_stream = Reader::new(r.bytes(glyph_size)?);
+    let mut composite_stream = Reader::new(r.bytes(composite_size)?);
+    let mut bbox_stream = Reader::new(r.bytes(bbox_size)?);
+    let mut instruction_stream = Reader::new(r.bytes(instruction_size)?);
+    let bbox_bitmap = bbox_stream.bytes(((num_glyphs + 31) >> 5) << 2)?;
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(r.bytes((num_glyphs + 7) >> 3)?)
+    } else {
+        None
+    };
+    let bit_is_set = |bitmap: &[u8], index: usize| bitmap[index >> 3] & (0x80 >> (index & 7)) != 0;
+    let mut glyf = Vec::with_capacity(data.len() * 2);
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+    let mut x_mins = Vec::with_capacity(num_glyphs);
+    let mut end_points = Vec::new();
+    let mut points = Vec::new();
+    for index in 0..num_glyphs {
+        offsets.push(glyf.len());
+        let n_contours = n_contour_stream.i16()?;
+        let has_bbox = bit_is_set(bbox_bitmap, index);
+        let mut bbox = [0i16; 4];
+        if has_bbox {
+            for value in &mut bbox {
+                *value = bbox_stream.i16()?;
+            }
+        }
+        if n_contours == 0 {
+            // Empty glyphs have no data and must not have a bounding box.
+            if has_bbox {
+                return None;
+            }
+            x_mins.push(0);
+            continue;
+        }
+        if n_contours > 0 {
+            end_points.clear();
+            let mut num_points = 0usize;
+            for _ in 0..n_contours {
+                num_points += n_points_stream.u16_255()? as usize;
+                end_points.push(u16::try_from(num_points.checked_sub(1)?).ok()?);
+            }
+            points.clear();
+            let (mut x, mut y) = (0i32, 0i32);
+            for &flag in flag_stream.bytes(num_points)? {
+                let (dx, dy) = decode_triplet(flag & 0x7F, &mut glyph_stream)?;
+                x += dx;
+                y += dy;
+                points.push((x, y, flag & 0x80 == 0));
+            }
+            let instruction_length = glyph_stream.u16_255()?;
+            let instructions = instruction_stream.bytes(instruction_length as usize)?;
+            if !has_bbox {
+                bbox = compute_bbox(&points)?;
+            }
+            glyf.extend_from_slice(&n_contours.to_be_bytes());
+            for value in bbox {
+                glyf.extend_from_slice(&value.to_be_bytes());
+            }
+            for end_point in &end_points {
+                glyf.extend_from_slice(&end_point.to_be_bytes());
+            }
+            glyf.extend_from_slice(&instruction_length.to_be_bytes());
+            glyf.extend_from_slice(instructions);
+            let overlap = overlap_bitmap.is_some_and(|bitmap| bit_is_set(bitmap, index));
+            encode_points(&points, overlap, &mut glyf)?;
+        } else if n_contours == -1 {
+            // Composite glyphs must have an explicit bounding box.
+            if !has_bbox {
+                return None;
+            }
+            let start = composite_stream.pos;
+            let mut have_instructions = false;
+            loop {
+                let flags = composite_stream.u16()?;
+                have_instructions |= flags & 0x0100 != 0;
+                // Glyph index and arguments.
+                let mut len = if flags & 0x0001 != 0 { 6 } else { 4 };
+                if flags & 0x0008 != 0 {
+                    len += 2;
+                } else if flags & 0x0040 != 0 {
+                    len += 4;
+                } else if flags & 0x0080 != 0 {
+                    len += 8;
+                }
+                composite_stream.skip(len)?;
+                if flags & 0x0020 == 0 {
+                    break;
+                }
+            }
+            glyf.extend_from_slice(&n_contours.to_be_bytes());
+            for value in bbox {
+                glyf.extend_from_slice(&value.to_be_bytes());
+            }
+            glyf.extend_from_slice(&composite_stream.data[start..composite_stream.pos]);
+            if have_instructions {
+                let instruction_length = glyph_stream.u16_255()?;
+                let instructions = instruction_stream.bytes(instruction_length as usize)?;
+                glyf.extend_from_slice(&instruction_length.to_be_bytes());
+                glyf.extend_from_slice(instructions);
+            }
+        } else {
+            return None;
+        }
+        x_mins.push(bbox[0]);
+        pad(&mut glyf);
+    }
+    offsets.push(glyf.len());
+    let loca = if index_format == 0 {
+        let mut loca = Vec::with_capacity(offsets.len() * 2);
+        for offset in offsets {
+            loca.extend_from_slice(&u16::try_from(offset / 2).ok()?.to_be_bytes());
+        }
+        loca
+    } else {
+        let mut loca = Vec::with_capacity(offsets.len() * 4);
+        for offset in offsets {
+            loca.extend_from_slice(&u32::try_from(offset).ok()?.to_be_bytes());
+        }
+        loca
+    };
+    Some(DecodedGlyf { glyf, loca, x_mins })
+}
+
+/// Decodes a point delta from the glyph stream for the given flag.
+fn decode_triplet(flag: u8, r: &mut Reader) -> Option<(i32, i32)> {
+    let with_sign = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+    let f = flag as i32;
+    Some(if flag < 10 {
+        let b0 = r.u8()? as i32;
+        (0, with_sign(flag, ((f & 14) << 7) + b0))
+    } else if flag < 20 {
+        let b0 = r.u8()? as i32;
+        (with_sign(flag, (((f - 10) & 14) << 7) + b0), 0)
+    } else if flag < 84 {
+        let b0 = f - 20;
+        let b1 = r.u8()? as i32;
+        (
+            with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+            with_sign(flag >> 1, 1 + ((b0 & 0x0C) << 2) + (b1 & 0x0F)),
+        )
+    } else if flag < 120 {
+        let b0 = f - 84;
+        let b1 = r.u8()? as i32;
+        let b2 = r.u8()? as i32;
+        (
+            with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+            with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2),
+        )
+    } else if flag < 124 {
+        let b1 = r.u8()? as i32;
+        let b2 = r.u8()? as i32;
+        let b3 = r.u8()? as i32;
+        (
+            with_sign(flag, (b1 << 4) + (b2 >> 4)),
+            with_sign(flag >> 1, ((b2 & 0x0F) << 8) + b3),
+        )
+    } else {
+        let b1 = r.u8()? as i32;
+        let b2 = r.u8()? as i32;
+        let b3 = r.u8()? as i32;
+        let b4 = r.u8()? as i32;
+        (
+            with_sign(flag, (b1 << 8) + b2),
+            with_sign(flag >> 1, (b3 << 8) + b4),
+        )
+    })
+}
+
+fn compute_bbox(points: &[(i32, i32, bool)]) -> Option<[i16; 4]> {
+    let Some(&(x, y, _)) = points.first() else {
+        return Some([0; 4]);
+    };
+    let mut bbox = [x, y, x, y];
+    for &(x, y, _) in points {
+        bbox[0] = bbox[0].min(x);
+        bbox[1] = bbox[1].min(y);
+        bbox[2] = bbox[2].max(x);
+        bbox[3] = bbox[3].max(y);
+    }
+    let mut result = [0; 4];
+    for (value, coord) in result.iter_mut().zip(bbox) {
+        *value = i16::try_from(coord).ok()?;
+    }
+    Some(result)
+}
+
+/// Writes the flags and coordinates of a simple glyph.
+fn encode_points(points: &[(i32, i32, bool)], overlap: bool, out: &mut Vec<u8>) -> Option<()> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+    let encode = |delta: i32, short: u8, same_or_positive: u8, coords: &mut Vec<u8>| {
+        if delta == 0 {
+            Some(same_or_positive)
+        } else if (-255..=255).contains(&delta) {
+            coords.push(delta.unsigned_abs() as u8);
+            Some(short | if delta > 0 { same_or_positive } else { 0 })
+        } else {
+            coords.extend_from_slice(&i16::try_from(delta).ok()?.to_be_bytes());
+            Some(0)
+        }
+    };
+    let mut flags = Vec::with_capacity(points.len());
+    let mut xs = Vec::with_capacity(points.len() * 2);
+    let mut ys = Vec::with_capacity(points.len() * 2);
+    let mut last_flag = None;
+    let mut repeat_count = 0;
+    let (mut x, mut y) = (0, 0);
+    for (i, &(px, py, on_curve)) in points.iter().enumerate() {
+        let mut flag = if on_curve { ON_CURVE } else { 0 };
+        if overlap && i == 0 {
+            flag |= OVERLAP_SIMPLE;
+        }
+        flag |= encode(px - x, X_SHORT, X_SAME_OR_POSITIVE, &mut xs)?;
+        flag |= encode(py - y, Y_SHORT, Y_SAME_OR_POSITIVE, &mut ys)?;
+        (x, y) = (px, py);
+        if last_flag == Some(flag) && repeat_count != 255 {
+            if repeat_count == 0 {
+                *flags.last_mut()? |= REPEAT;
+                flags.push(1);
+            } else {
+                *flags.last_mut()? += 1;
+            }
+            repeat_count += 1;
+        } else {
+            flags.push(flag);
+            last_flag = Some(flag);
+            repeat_count = 0;
+        }
+    }
+    out.extend_from_slice(&flags);
+    out.extend_from_slice(&xs);
+    out.extend_from_slice(&ys);
+    Some(())
+}
+
+fn decode_hmtx(data: &[u8], num_h_metrics: usize, x_mins: &[i16]) -> Option<Vec<u8>> {
+    let num_glyphs = x_mins.len();
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return None;
+    }
+    let mut r = Reader::new(data);
+    let flags = r.u8()?;
+    let mut advances = Reader::new(r.bytes(num_h_metrics * 2)?);
+    // Side bearings are omitted when they match the minimum x coordinates.
+    let mut lsbs = if flags & 1 == 0 {
+        Some(Reader::new(r.bytes(num_h_metrics * 2)?))
+    } else {
+        None
+    };
+    let mut mono_lsbs = if flags & 2 == 0 {
+        Some(Reader::new(r.bytes((num_glyphs - num_h_metrics) * 2)?))
+    } else {
+        None
+    };
+    let mut hmtx = Vec::with_capacity(num_h_metrics * 4 + (num_glyphs - num_h_metrics) * 2);
+    for (index, &x_min) in x_mins.iter().enumerate() {
+        let lsbs = if index < num_h_metrics {
+            hmtx.extend_from_slice(&advances.u16()?.to_be_bytes());
+            &mut lsbs
+        } else {
+            &mut mono_lsbs
+        };
+        let lsb = match lsbs {
+            Some(lsbs) => lsbs.i16()?,
+            None => x_min,
+        };
+        hmtx.extend_from_slice(&lsb.to_be_bytes());
+    }
+    Some(hmtx)
+}
+
+/// Font in an sfnt file or collection.
+struct SfntFont {
+    flavor: u32,
+    /// Indices of the tables used by the font.
+    tables: Vec<usize>,
+}
+
+/// Writes an sfnt file, or a collection if a version is given.
+fn write_sfnt(
+    tables: &[(Tag, Vec<u8>)],
+    fonts: &[SfntFont],
+    collection_version: Option<u32>,
+) -> Option<Vec<u8>> {
+    if fonts.is_empty() {
+        return None;
+    }
+    let mut offset = match collection_version {
+        Some(version) => 12 + fonts.len() * 4 + if version >= 0x0002_0000 { 12 } else { 0 },
+        None => 0,
+    };
+    let mut font_offsets = Vec::with_capacity(fonts.len());
+    for font in fonts {
+        font_offsets.push(u32::try_from(offset).ok()?);
+        offset += 12 + font.tables.len() * 16;
+    }
+    let mut table_offsets = Vec::with_capacity(tables.len());
+    for (_, data) in tables {
+        table_offsets.push(u32::try_from(offset).ok()?);
+        offset += (data.len() + 3) & !3;
+    }
+    let mut out = Vec::with_capacity(offset);
+    if let Some(version) = collection_version {
+        out.extend_from_slice(b"ttcf");
+        out.extend_from_slice(&version.to_be_bytes());
+        out.extend_from_slice(&(fonts.len() as u32).to_be_bytes());
+        for font_offset in &font_offsets {
+            out.extend_from_slice(&font_offset.to_be_bytes());
+        }
+        if version >= 0x0002_0000 {
+            // No digital signature.
+            out.extend_from_slice(&[0; 12]);
+        }
+    }
+    for font in fonts {
+        let num_tables = u16::try_from(font.tables.len()).ok()?;
+        if num_tables == 0 {
+            return None;
+        }
+        let entry_selector = 15 - num_tables.leading_zeros() as u16;
+        let search_range = (1u16 << entry_selector).checked_mul(16)?;
+        let range_shift = num_tables.checked_mul(16)? - search_range;
+        out.extend_from_slice(&font.flavor.to_be_bytes());
+        for value in [num_tables, search_range, entry_selector, range_shift] {
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+        let mut records = font.tables.clone();
+        records.sort_by_key(|&index| tables[index].0);
+        for index in records {
+            let (tag, data) = &tables[index];
+            out.extend_from_slice(tag);
+            out.extend_from_slice(&table_checksum(*tag, data).to_be_bytes());
+            out.extend_from_slice(&table_offsets[index].to_be_bytes());
+            out.extend_from_slice(&u32::try_from(data.len()).ok()?.to_be_bytes());
+        }
+    }
+    let mut head_offset = None;
+    for (tag, data) in tables {
+        if *tag == HEAD && data.len() >= 12 {
+            head_offset = Some(out.len());
+            out.extend_from_slice(&data[..8]);
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&data[12..]);
+        } else {
+            out.extend_from_slice(data);
+        }
+        pad(&mut out);
+    }
+    // Only a single font has a meaningful checksum adjustment.
+    if let (Some(head_offset), None) = (head_offset, collection_version) {
+        let adjustment = 0xB1B0_AFBA_u32.wrapping_sub(checksum(&out));
+        out[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    Some(out)
+}
+
+fn table_checksum(tag: Tag, data: &[u8]) -> u32 {
+    let sum = checksum(data);
+    if tag == HEAD && data.len() >= 12 {
+        // The checksum adjustment is excluded from the head checksum.
+        let adjustment = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+        sum.wrapping_sub(adjustment)
+    } else {
+        sum
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(4);
+    let mut sum = chunks.by_ref().fold(0u32, |sum, chunk| {
+        sum.wrapping_add(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+    });
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        let mut last = [0; 4];
+        last[..remainder.len()].copy_from_slice(remainder);
+        sum = sum.wrapping_add(u32::from_be_bytes(last));
+    }
+    sum
+}
+
+/// Pads the data to a four byte boundary.
+fn pad(data: &mut Vec<u8>) {
+    data.resize((data.len() + 3) & !3, 0);
+}
+
+/// Big endian reader with bounds checking.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn tag(&mut self) -> Option<Tag> {
+        self.bytes(4)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        Some(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    /// Reads a variable length integer of up to five bytes.
+    fn uint_base128(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            // Leading zeros are not allowed.
+            if i == 0 && byte == 0x80 {
+                return None;
+            }
+            if value & 0xFE00_0000 != 0 {
+                return None;
+            }
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Reads a variable length integer of up to three bytes.
+    fn u16_255(&mut self) -> Option<u16> {
+        match self.u8()? {
+            253 => self.u16(),
+            254 => Some(self.u8()? as u16 + 253 * 2),
+            255 => Some(self.u8()? as u16 + 253),
+            code => Some(code as u16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTF: &[u8] = include_bytes!("../../testdata/ParleyTest.ttf");
+    const WOFF: &[u8] = include_bytes!("../../testdata/ParleyTest.woff");
+    const WOFF2: &[u8] = include_bytes!("../../testdata/ParleyTest.woff2");
+
+    #[test]
+    fn decode_woff_matches_ttf() {
+        assert_eq!(decode(WOFF).unwrap(), TTF);
+    }
+
+    #[test]
+    fn decode_woff2_matches_ttf() {
+        assert_eq!(decode(WOFF2).unwrap(), TTF);
+    }
+
+    #[test]
+    fn decode_sfnt() {
+        assert!(decode(TTF).is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        // Skip the lengths that only remove trailing padding.
+        for data in [WOFF, WOFF2] {
+            for len in 0..data.len() - 4 {
+                assert!(decode(&data[..len]).is_none(), "decoded {len} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn woff2_total_sfnt_size_too_small() {
+        let mut data = WOFF2.to_vec();
+        data[16..20].copy_from_slice(&64u32.to_be_bytes());
+        assert!(decode(&data).is_none());
+    }
+
+    #[test]
+    fn woff2_table_length_mismatch() {
+        // The first directory entry is OS/2 with a one byte length.
+        assert_eq!(WOFF2[48], 6);
+        assert_eq!(WOFF2[49], 96);
+        for length in [95, 97] {
+            let mut data = WOFF2.to_vec();
+            data[49] = length;
+            assert!(decode(&data).is_none());
+        }
+    }
+
+    /// Builds a transformed glyf table with a single glyph.
+    fn transformed_glyf(n_points: &[u8], flags: &[u8], glyphs: &[u8]) -> Vec<u8> {
+        let n_contours = 1i16.to_be_bytes();
+        let bbox = [0; 4];
+        let mut data = vec![0, 0, 0, 0, 0, 1, 0, 0];
+        let streams: [&[u8]; 7] = [&n_contours, n_points, flags, glyphs, &[], &bbox, &[]];
+        for stream in streams {
+            data.extend_from_slice(&(stream.len() as u32).to_be_bytes());
+        }
+        for stream in streams {
+            data.extend_from_slice(stream);
+        }
+        data
+    }
+
+    #[test]
+    fn glyf_transform() {
+        // Two points at (10, 0) and (10, 20) without instructions.
+        let data = transformed_glyf(&[2], &[11, 1], &[10, 20, 0]);
+        let decoded = decode_glyf(&data).unwrap();
+        assert_eq!(
+            decoded.glyf,
+            [0, 1, 0, 10, 0, 0, 0, 10, 0, 20, 0, 1, 0, 0, 0x33, 0x35, 10, 20, 0, 0]
+        );
+        assert_eq!(decoded.loca, [0, 0, 0, 10]);
+        assert_eq!(decoded.x_mins, [10]);
+    }
+
+    #[test]
+    fn glyf_transform_malformed() {
+        // Missing instruction length.
+        assert!(decode_glyf(&transformed_glyf(&[2], &[11, 1], &[10, 20])).is_none());
+        // Missing flag.
+        assert!(decode_glyf(&transformed_glyf(&[2], &[11], &[10, 20, 0])).is_none());
+        // Missing point count.
+        assert!(decode_glyf(&transformed_glyf(&[], &[11, 1], &[10, 20, 0])).is_none());
+        // Stream sizes that exceed the table.
+        let data = transformed_glyf(&[2], &[11, 1], &[10, 20, 0]);
+        assert!(decode_glyf(&data[..data.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn woff_bad_offset() {
+        // Point the first table past the end of the file.
+        let mut data = WOFF.to_vec();
+        data[48..52].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(decode(&data).is_none());
+    }
+}
@@ -4,21 +4,32 @@
 //! Context for layout.
 
 #[cfg(not(feature = "std"))]
-use alloc::{vec, vec::Vec};
+use alloc::{string::String, vec, vec::Vec};
+
+use alloc::sync::Arc;
 
 use super::bidi;
+use super::hyphenation::Hyphenator;
 use super::resolve::range::*;
+use super::resolve::tree::*;
 use super::resolve::*;
 use super::style::*;
 use super::FontContext;
 
+use super::layout::data::LayoutData;
+#[cfg(feature = "std")]
+use super::layout::data::ParagraphData;
 #[cfg(feature = "std")]
 use super::layout::Layout;
+use super::layout::{InlineBox, WritingMode};
+#[cfg(feature = "std")]
+use super::shape::ShapeCache;
 
 use swash::shape::ShapeContext;
-use swash::text::cluster::CharInfo;
+use swash::text::cluster::{Boundary, CharInfo};
+use swash::text::Language;
 
-use core::ops::RangeBounds;
+use core::ops::{Range, RangeBounds};
 
 /// Context for building a text layout.
 pub struct LayoutContext<B: Brush = [u8; 4]> {
@@ -26,7 +37,22 @@ pub struct LayoutContext<B: Brush = [u8; 4]> {
     rcx: ResolveContext,
     styles: Vec<RangedStyle<B>>,
     rsb: RangedStyleBuilder<B>,
+    tsb: TreeStyleBuilder<B>,
+    inline_boxes: Vec<InlineBox>,
+    writing_mode: WritingMode,
+    /// True if the text contains characters that require bidi resolution.
+    needs_bidi: bool,
+    /// Bidi levels of all characters, resolved per paragraph.
+    levels: Vec<u8>,
+    preedit: Option<(Range<usize>, Option<Range<usize>>)>,
     info: Vec<(CharInfo, u16)>,
+    hyphenators: Vec<(String, Arc<dyn Hyphenator>)>,
+    hyphenation_points: Vec<usize>,
+    word_breaks: Vec<usize>,
+    /// Data of the previous layout during an incremental rebuild.
+    prev_data: LayoutData<B>,
+    #[cfg(feature = "std")]
+    shape_cache: ShapeCache<B>,
     scx: ShapeContext,
 }
 
@@ -37,18 +63,87 @@ impl<B: Brush> LayoutContext<B> {
             rcx: ResolveContext::default(),
             styles: vec![],
             rsb: RangedStyleBuilder::default(),
+            tsb: TreeStyleBuilder::default(),
+            inline_boxes: vec![],
+            writing_mode: WritingMode::HorizontalTb,
+            needs_bidi: false,
+            levels: vec![],
+            preedit: None,
             info: vec![],
+            hyphenators: vec![],
+            hyphenation_points: vec![],
+            word_breaks: vec![],
+            prev_data: LayoutData::default(),
+            #[cfg(feature = "std")]
+            shape_cache: ShapeCache::default(),
             scx: ShapeContext::default(),
         }
     }
 
+    /// Sets the hyphenator used for text with [`Hyphens::Auto`] in the
+    /// specified language, replacing any existing hyphenator for that
+    /// language.
+    ///
+    /// The language is matched against the primary language subtag of the
+    /// [`Locale`](StyleProperty::Locale) style property, so `"en"` applies
+    /// to both `"en-US"` and `"en-GB"`.
+    pub fn set_hyphenator(&mut self, language: &str, hyphenator: impl Hyphenator + 'static) {
+        let language = hyphenation_key(language);
+        let hyphenator: Arc<dyn Hyphenator> = Arc::new(hyphenator);
+        match self
+            .hyphenators
+            .iter_mut()
+            .find(|(key, _)| *key == language)
+        {
+            Some(entry) => entry.1 = hyphenator,
+            None => self.hyphenators.push((language, hyphenator)),
+        }
+    }
+
+    /// Removes the hyphenator for the specified language.
+    pub fn remove_hyphenator(&mut self, language: &str) {
+        let language = hyphenation_key(language);
+        self.hyphenators.retain(|(key, _)| *key != language);
+    }
+
+    /// Sets the maximum size in bytes of the cache of shaped text, evicting
+    /// the least recently used text if the cache is larger. The cache is
+    /// disabled when the capacity is zero, which is the default.
+    ///
+    /// When enabled, text that is shaped with the same styles and
+    /// surrounding line break opportunities as in a previous layout reuses
+    /// the glyphs of that layout instead of being shaped again.
+    #[cfg(feature = "std")]
+    pub fn set_shape_cache_capacity(&mut self, capacity: usize) {
+        self.shape_cache.set_capacity(capacity);
+    }
+
+    /// Removes all text from the shape cache that has not been used in the
+    /// last `max_age` layouts.
+    #[cfg(feature = "std")]
+    pub fn prune_shape_cache(&mut self, max_age: u64) {
+        self.shape_cache.prune(max_age);
+    }
+
+    /// Removes all text from the shape cache.
+    ///
+    /// Text shaped with a different font collection, or before fonts were
+    /// registered or unregistered, is never reused, so this is only needed
+    /// to release memory.
+    #[cfg(feature = "std")]
+    pub fn clear_shape_cache(&mut self) {
+        self.shape_cache.clear();
+    }
+
     pub fn ranged_builder<'a>(
         &'a mut self,
         fcx: &'a mut FontContext,
         text: &'a str,
         scale: f32,
     ) -> RangedBuilder<B, &'a str> {
-        self.begin(text);
+        self.begin();
+        self.rsb.begin(text.len());
+        self.analyze_text(text);
         #[cfg(feature = "std")]
         fcx.source_cache.prune(128, false);
         RangedBuilder {
@@ -59,82 +154,166 @@ impl<B: Brush> LayoutContext<B> {
         }
     }
 
-    fn begin(&mut self, text: &str) {
+    /// Returns a builder that constructs a layout from a tree of nested
+    /// style spans and text.
+    pub fn tree_builder<'a>(
+        &'a mut self,
+        fcx: &'a mut FontContext,
+        scale: f32,
+    ) -> TreeBuilder<'a, B> {
+        self.begin();
+        self.tsb.begin();
+        #[cfg(feature = "std")]
+        fcx.source_cache.prune(128, false);
+        TreeBuilder {
+            scale,
+            lcx: self,
+            fcx,
+        }
+    }
+
+    fn begin(&mut self) {
         self.rcx.clear();
+        self.writing_mode = WritingMode::HorizontalTb;
+        self.reset();
+    }
+
+    /// Clears the state accumulated for the text of a single layout,
+    /// retaining resolved resources and the writing mode.
+    fn reset(&mut self) {
         self.styles.clear();
-        self.rsb.begin(text.len());
+        self.inline_boxes.clear();
+        self.preedit = None;
+        self.needs_bidi = false;
+        self.levels.clear();
         self.info.clear();
+        self.hyphenation_points.clear();
         self.bidi.clear();
+    }
+
+    fn analyze_text(&mut self, text: &str) {
         let text = if text.is_empty() { " " } else { text };
         let mut a = swash::text::analyze(text.chars());
         for x in a.by_ref() {
             self.info.push((CharInfo::new(x.0, x.1), 0));
         }
-        if a.needs_bidi_resolution() {
-            self.bidi.resolve(
-                text.chars()
-                    .zip(self.info.iter().map(|info| info.0.bidi_class())),
-                Some(0),
-            );
-        }
-    }
-}
-
-impl<B: Brush> Default for LayoutContext<B> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl<B: Brush> Clone for LayoutContext<B> {
-    fn clone(&self) -> Self {
-        // None of the internal state is visible so just return a new instance.
-        Self::new()
-    }
-}
-
-/// Builder for constructing a text layout with ranged attributes.
-pub struct RangedBuilder<'a, B: Brush, T: TextSource> {
-    text: T,
-    scale: f32,
-    lcx: &'a mut LayoutContext<B>,
-    fcx: &'a mut FontContext,
-}
-
-impl<'a, B: Brush, T: TextSource> RangedBuilder<'a, B, T> {
-    pub fn push_default(&mut self, property: &StyleProperty<B>) {
-        let resolved = self.lcx.rcx.resolve(self.fcx, property, self.scale);
-        self.lcx.rsb.push_default(resolved);
+        self.needs_bidi = a.needs_bidi_resolution();
     }
 
-    pub fn push(&mut self, property: &StyleProperty<B>, range: impl RangeBounds<usize>) {
-        let resolved = self.lcx.rcx.resolve(self.fcx, property, self.scale);
-        self.lcx.rsb.push(resolved, range);
+    /// Splits the text into paragraphs and resolves the bidi levels of each
+    /// paragraph independently. Must be called after styles have been
+    /// assigned to the character info.
+    #[cfg(feature = "std")]
+    fn resolve_paragraphs(&mut self, text: &str, paragraphs: &mut Vec<ParagraphData>) {
+        self.levels.clear();
+        let mut has_levels = false;
+        let mut start = (0, 0);
+        let mut chars = text.char_indices().enumerate().peekable();
+        while let Some((char_index, (offset, ch))) = chars.next() {
+            let next = chars.peek().map(|(_, (_, next))| *next);
+            // A paragraph separator ends the paragraph that contains it, but
+            // a carriage return followed by a line feed is a single
+            // separator.
+            let is_separator = self.info[char_index].0.bidi_class() == swash::text::BidiClass::B
+                && !(ch == '\r' && next == Some('\n'));
+            if !is_separator && next.is_some() {
+                continue;
+            }
+            let (start_char, start_offset) = start;
+            let end_char = char_index + 1;
+            let end_offset = offset + ch.len_utf8();
+            let style = &self.styles[self.info[start_char].1 as usize].style;
+            let direction = match style.direction {
+                Direction::Auto => None,
+                Direction::LeftToRight => Some(0),
+                Direction::RightToLeft => Some(1),
+            };
+            // Text without any right-to-left characters only needs to be
+            // resolved in a right-to-left paragraph.
+            let base_level = if self.needs_bidi || direction == Some(1) {
+                self.bidi.resolve(
+                    text[start_offset..end_offset].chars().zip(
+                        self.info[start_char..end_char]
+                            .iter()
+                            .map(|info| info.0.bidi_class()),
+                    ),
+                    direction,
+                );
+                self.levels.extend_from_slice(self.bidi.levels());
+                has_levels = true;
+                self.bidi.base_level()
+            } else {
+                self.levels
+                    .resize(self.levels.len() + end_char - start_char, 0);
+                0
+            };
+            paragraphs.push(ParagraphData {
+                text_range: start_offset..end_offset,
+                base_level,
+                alignment: style.text_align,
+                alignment_last: style.text_align_last,
+                justify: style.text_justify,
+                text_indent: style.text_indent,
+                hanging_indent: style.hanging_indent,
+                line_height_step: style.line_height_step,
+            });
+            start = (end_char, end_offset);
+        }
+        if !has_levels {
+            self.levels.clear();
+        }
     }
 
+    /// Builds a layout for the text using the computed ranged styles.
+    ///
+    /// If `edit` is provided, the layout must contain the previous text with
+    /// the specified range replaced by the given number of bytes, and the
+    /// runs and lines that are unaffected by the edit are reused.
     #[cfg(feature = "std")]
-    pub fn build_into(&mut self, layout: &mut Layout<B>) {
+    fn build_into_layout(
+        &mut self,
+        fcx: &mut FontContext,
+        scale: f32,
+        mut text: &str,
+        layout: &mut Layout<B>,
+        edit: Option<(Range<usize>, usize)>,
+    ) {
+        let mut prev = core::mem::take(&mut self.prev_data);
+        if edit.is_some() {
+            core::mem::swap(&mut prev, &mut layout.data);
+        }
         layout.data.clear();
-        layout.data.scale = self.scale;
-        let lcx = &mut self.lcx;
-        let mut text = self.text.as_str();
+        layout.data.scale = scale;
+        layout.data.generation = fcx.collection.generation();
         let is_empty = text.is_empty();
         if is_empty {
             // Force a layout to have at least one line.
             text = " ";
         }
-        layout.data.has_bidi = !lcx.bidi.levels().is_empty();
-        layout.data.base_level = lcx.bidi.base_level();
+        layout.data.writing_mode = self.writing_mode;
+        if let Some((range, cursor)) = self.preedit.take() {
+            layout.data.preedit = Some(range);
+            layout.data.preedit_cursor = cursor;
+        }
         layout.data.text_len = text.len();
-        let fcx = &mut self.fcx;
-        lcx.rsb.finish(&mut lcx.styles);
+        // Inline boxes are stored in logical order. The sort is stable so
+        // boxes at the same position retain the order in which they were
+        // pushed.
+        self.inline_boxes.sort_by_key(|inline_box| inline_box.index);
+        layout
+            .data
+            .inline_boxes
+            .extend_from_slice(&self.inline_boxes);
         let mut char_index = 0;
-        for (i, style) in lcx.styles.iter().enumerate() {
+        for (i, style) in self.styles.iter().enumerate() {
             for _ in text[style.range.clone()].chars() {
-                lcx.info[char_index].1 = i as u16;
+                self.info[char_index].1 = i as u16;
                 char_index += 1;
             }
         }
+        self.apply_hyphenation(text);
+        self.resolve_paragraphs(text, &mut layout.data.paragraphs);
+        layout.data.has_bidi = !self.levels.is_empty();
         use super::layout::{Decoration, Style};
         fn conv_deco<B: Brush>(
             deco: &ResolvedDecoration<B>,
@@ -150,37 +329,289 @@ impl<'a, B: Brush, T: TextSource> RangedBuilder<'a, B, T> {
                 None
             }
         }
-        layout.data.styles.extend(lcx.styles.iter().map(|s| {
-            let s = &s.style;
-            Style {
+        layout
+            .data
+            .style_ranges
+            .extend(self.styles.iter().map(|s| s.range.clone()));
+        for (i, style) in self.styles.iter().enumerate() {
+            let s = &style.style;
+            // Styles frequently share the same tab stops.
+            let tab_stops = match self.styles[..i]
+                .iter()
+                .position(|prev| prev.style.tab_stops == s.tab_stops)
+            {
+                Some(index) => layout.data.styles[index].tab_stops.clone(),
+                None => {
+                    let start = layout.data.tab_stops.len();
+                    layout
+                        .data
+                        .tab_stops
+                        .extend_from_slice(self.rcx.tab_stops(s.tab_stops).unwrap_or(&[]));
+                    start..layout.data.tab_stops.len()
+                }
+            };
+            layout.data.styles.push(Style {
                 brush: s.brush.clone(),
                 underline: conv_deco(&s.underline, &s.brush),
                 strikethrough: conv_deco(&s.strikethrough, &s.brush),
+                preedit: if s.preedit {
+                    conv_deco(
+                        &ResolvedDecoration {
+                            enabled: true,
+                            ..s.underline.clone()
+                        },
+                        &s.brush,
+                    )
+                } else {
+                    None
+                },
                 line_height: s.line_height,
+                tab_size: s.tab_size,
+                tab_stops,
+            });
+        }
+        let damage = edit
+            .filter(|_| !is_empty)
+            .and_then(|(replaced, inserted_len)| {
+                layout.data.damage(&prev, text, replaced, inserted_len)
+            });
+        let range = match &damage {
+            Some(damage) => {
+                if damage.lines.is_none() {
+                    super::layout::line::greedy::unjustify(&mut prev);
+                }
+                layout.data.reuse_prefix(&prev, damage);
+                damage.text_range.start..damage.new_text_end
             }
-        }));
+            None => 0..text.len(),
+        };
+        let first_run = layout.data.runs.len();
+        let first_item = layout.data.items.len();
         {
+            self.shape_cache.set_generation(fcx.collection.generation());
             let query = fcx.collection.query(&mut fcx.source_cache);
             super::shape::shape_text(
-                &lcx.rcx,
+                &self.rcx,
                 query,
-                &lcx.styles,
-                &lcx.info,
-                lcx.bidi.levels(),
-                &mut lcx.scx,
+                &self.styles,
+                &self.inline_boxes,
+                &self.info,
+                &self.levels,
+                &mut self.scx,
+                &mut self.shape_cache,
                 text,
+                range,
                 layout,
             );
         }
-        layout.data.finish();
+        let runs = first_run..layout.data.runs.len();
+        layout
+            .data
+            .mark_hyphenation_points(runs.clone(), &self.hyphenation_points);
+        layout.data.finish(text, runs);
+        layout.data.measure_content(first_item);
+        if let Some(damage) = &damage {
+            layout.data.reuse_suffix(&prev, damage);
+        }
+        layout.data.finish_content_widths();
+        layout.data.resolve_tabs(text);
+        prev.clear();
+        self.prev_data = prev;
         if is_empty {
             layout.data.text_len = 0;
+            layout.data.paragraphs[0].text_range = 0..0;
             let run = &mut layout.data.runs[0];
             run.cluster_range.end = 0;
             run.text_range.end = 0;
             layout.data.clusters.clear();
         }
     }
+}
+
+/// Returns the key of the hyphenator for a language tag, which is its
+/// primary language subtag.
+fn hyphenation_key(language: &str) -> String {
+    Language::parse(language)
+        .map(|language| language.language().into())
+        .unwrap_or_else(|| language.to_ascii_lowercase())
+}
+
+impl<B: Brush> LayoutContext<B> {
+    /// Adjusts the line break opportunities in the text according to the
+    /// hyphenation mode of each style and records the positions at which a
+    /// hyphen is displayed when the line is broken.
+    fn apply_hyphenation(&mut self, text: &str) {
+        self.hyphenation_points.clear();
+        let mut word_start: Option<(usize, usize)> = None;
+        let mut has_shy = false;
+        let mut chars = text.char_indices().enumerate().peekable();
+        while let Some((char_index, (offset, ch))) = chars.next() {
+            let style = &self.styles[self.info[char_index].1 as usize].style;
+            if ch == '\u{AD}' {
+                // Soft hyphens prevent automatic hyphenation of the word.
+                has_shy = true;
+                let next_index = char_index + 1;
+                if let Some(next) = self.info.get_mut(next_index) {
+                    if next.0.boundary() == Boundary::Line {
+                        if style.hyphens == Hyphens::None {
+                            next.0 = CharInfo::new(next.0.properties(), Boundary::None);
+                        } else {
+                            self.hyphenation_points.push(offset + ch.len_utf8());
+                        }
+                    }
+                }
+                continue;
+            }
+            if !ch.is_alphabetic() {
+                word_start = None;
+                has_shy = false;
+                continue;
+            }
+            let (start_char, start) = *word_start.get_or_insert((char_index, offset));
+            let at_end = !chars
+                .peek()
+                .is_some_and(|(_, (_, next))| next.is_alphabetic() || *next == '\u{AD}');
+            if !at_end {
+                continue;
+            }
+            word_start = None;
+            if core::mem::take(&mut has_shy) {
+                continue;
+            }
+            let style = &self.styles[self.info[start_char].1 as usize].style;
+            if style.hyphens != Hyphens::Auto {
+                continue;
+            }
+            let Some(hyphenator) = style.locale.and_then(|locale| {
+                let key = hyphenation_key(locale.language());
+                self.hyphenators
+                    .iter()
+                    .find(|(language, _)| *language == key)
+                    .map(|(_, hyphenator)| hyphenator)
+            }) else {
+                continue;
+            };
+            let word = &text[start..offset + ch.len_utf8()];
+            self.word_breaks.clear();
+            hyphenator.hyphenate(word, &mut self.word_breaks);
+            let mut word_chars = word.char_indices().enumerate();
+            for &word_break in &self.word_breaks {
+                let Some((index, _)) = word_chars.find(|(_, (offset, _))| *offset == word_break)
+                else {
+                    break;
+                };
+                let info = &mut self.info[start_char + index].0;
+                if info.boundary() != Boundary::Mandatory {
+                    *info = CharInfo::new(info.properties(), Boundary::Line);
+                }
+                self.hyphenation_points.push(start + word_break);
+            }
+        }
+    }
+}
+
+impl<B: Brush> Default for LayoutContext<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Brush> Clone for LayoutContext<B> {
+    fn clone(&self) -> Self {
+        // None of the internal state is visible so just return a new instance
+        // with the same hyphenators and shape cache capacity.
+        let mut lcx = Self::new();
+        lcx.hyphenators.clone_from(&self.hyphenators);
+        #[cfg(feature = "std")]
+        lcx.shape_cache.set_capacity(self.shape_cache.capacity());
+        lcx
+    }
+}
+
+/// Builder for constructing a text layout with ranged attributes.
+pub struct RangedBuilder<'a, B: Brush, T: TextSource> {
+    text: T,
+    scale: f32,
+    lcx: &'a mut LayoutContext<B>,
+    fcx: &'a mut FontContext,
+}
+
+impl<'a, B: Brush, T: TextSource> RangedBuilder<'a, B, T> {
+    pub fn push_default(&mut self, property: &StyleProperty<B>) {
+        let resolved = self.lcx.rcx.resolve(self.fcx, property, self.scale);
+        self.lcx.rsb.push_default(resolved);
+    }
+
+    pub fn push(&mut self, property: &StyleProperty<B>, range: impl RangeBounds<usize>) {
+        let resolved = self.lcx.rcx.resolve(self.fcx, property, self.scale);
+        self.lcx.rsb.push(resolved, range);
+    }
+
+    /// Pushes a box of fixed size that will be laid out inline with the
+    /// text at the byte offset given by [`InlineBox::index`].
+    pub fn push_inline_box(&mut self, inline_box: InlineBox) {
+        self.lcx.inline_boxes.push(inline_box);
+    }
+
+    /// Sets the writing mode of the layout. The default is
+    /// [`WritingMode::HorizontalTb`].
+    ///
+    /// In vertical writing modes, all text is set upright using the
+    /// vertical alternates and metrics of the font.
+    pub fn set_writing_mode(&mut self, writing_mode: WritingMode) {
+        self.lcx.writing_mode = writing_mode;
+    }
+
+    /// Marks the specified range of text as being composed by an input
+    /// method, with an optional range for the cursor within the composed
+    /// text. Both ranges are byte offsets into the full text.
+    ///
+    /// The composed text is given a [`preedit`](crate::layout::Style::preedit)
+    /// decoration and the cursor can be located with
+    /// [`Cursor::from_preedit`](crate::layout::Cursor::from_preedit).
+    pub fn set_preedit(&mut self, range: Range<usize>, cursor: Option<Range<usize>>) {
+        self.lcx
+            .rsb
+            .push(ResolvedProperty::Preedit(true), range.clone());
+        self.lcx.preedit = Some((range, cursor));
+    }
+
+    #[cfg(feature = "std")]
+    pub fn build_into(&mut self, layout: &mut Layout<B>) {
+        self.lcx.rsb.finish(&mut self.lcx.styles);
+        self.lcx
+            .build_into_layout(self.fcx, self.scale, self.text.as_str(), layout, None);
+    }
+
+    /// Rebuilds a layout after an edit of its text, reusing the shaped runs
+    /// and lines of the paragraphs that are unaffected by the edit.
+    ///
+    /// The layout must have been built from the previous text, and
+    /// replacing the `replaced` byte range of that text with `inserted`
+    /// must produce the text of this builder. The text outside of the edit
+    /// must have the same styles as before.
+    ///
+    /// Lines are recomputed on the next call to [`Layout::break_all_lines`].
+    /// When the greedy strategy is used with the same parameters as before,
+    /// only the lines of the damaged paragraphs are broken again.
+    ///
+    /// Falls back to a full rebuild if the previous layout cannot be reused.
+    #[cfg(feature = "std")]
+    pub fn build_into_edited(
+        &mut self,
+        layout: &mut Layout<B>,
+        replaced: Range<usize>,
+        inserted: &str,
+    ) {
+        self.lcx.rsb.finish(&mut self.lcx.styles);
+        let text = self.text.as_str();
+        let edit = text
+            .get(replaced.start..replaced.start + inserted.len())
+            .is_some_and(|s| s == inserted)
+            .then_some((replaced, inserted.len()));
+        self.lcx
+            .build_into_layout(self.fcx, self.scale, text, layout, edit);
+    }
 
     #[cfg(feature = "std")]
     pub fn build(&mut self) -> Layout<B> {
@@ -190,6 +621,109 @@ impl<'a, B: Brush, T: TextSource> RangedBuilder<'a, B, T> {
     }
 }
 
+/// Builder for constructing a text layout from a tree of nested style
+/// spans.
+pub struct TreeBuilder<'a, B: Brush> {
+    scale: f32,
+    lcx: &'a mut LayoutContext<B>,
+    fcx: &'a mut FontContext,
+}
+
+impl<'a, B: Brush> TreeBuilder<'a, B> {
+    /// Pushes a property that applies to the root span.
+    ///
+    /// Spans that are already open and text that has already been pushed
+    /// are not affected.
+    pub fn push_default(&mut self, property: &StyleProperty<B>) {
+        let resolved = self.lcx.rcx.resolve(self.fcx, property, self.scale);
+        self.lcx.tsb.push_default(resolved);
+    }
+
+    /// Opens a new span that inherits the style of the current span,
+    /// modified by the specified properties.
+    pub fn push_style_span<'s>(
+        &mut self,
+        properties: impl IntoIterator<Item = &'s StyleProperty<'s, B>>,
+    ) where
+        B: 's,
+    {
+        let lcx = &mut *self.lcx;
+        let fcx = &mut *self.fcx;
+        let scale = self.scale;
+        lcx.tsb.push_style_span(
+            properties
+                .into_iter()
+                .map(|property| lcx.rcx.resolve(fcx, property, scale)),
+        );
+    }
+
+    /// Closes the current span.
+    pub fn pop_style_span(&mut self) {
+        self.lcx.tsb.pop_style_span();
+    }
+
+    /// Appends text with the style of the current span.
+    pub fn push_text(&mut self, text: &str) {
+        self.lcx.tsb.push_text(text);
+    }
+
+    /// Appends text with the style of the current span that is being
+    /// composed by an input method, with an optional range for the cursor
+    /// relative to the start of the composed text.
+    ///
+    /// Only the most recently pushed composed text is retained.
+    pub fn push_preedit_text(&mut self, text: &str, cursor: Option<Range<usize>>) {
+        let start = self.lcx.tsb.text_len();
+        self.lcx
+            .tsb
+            .push_style_span([ResolvedProperty::Preedit(true)]);
+        self.lcx.tsb.push_text(text);
+        self.lcx.tsb.pop_style_span();
+        let cursor = cursor.map(|cursor| start + cursor.start..start + cursor.end);
+        self.lcx.preedit = Some((start..start + text.len(), cursor));
+    }
+
+    /// Pushes a box of fixed size that will be laid out inline with the
+    /// text at the current position. The [`InlineBox::index`] field is
+    /// ignored.
+    pub fn push_inline_box(&mut self, mut inline_box: InlineBox) {
+        inline_box.index = self.lcx.tsb.text_len();
+        self.lcx.inline_boxes.push(inline_box);
+    }
+
+    /// Sets the writing mode of the layout. The default is
+    /// [`WritingMode::HorizontalTb`].
+    ///
+    /// In vertical writing modes, all text is set upright using the
+    /// vertical alternates and metrics of the font.
+    pub fn set_writing_mode(&mut self, writing_mode: WritingMode) {
+        self.lcx.writing_mode = writing_mode;
+    }
+
+    /// Builds the layout into the specified target and returns the
+    /// accumulated text.
+    ///
+    /// The builder can then be used to construct another layout, starting
+    /// from an empty tree with the same root style and writing mode.
+    #[cfg(feature = "std")]
+    pub fn build_into(&mut self, layout: &mut Layout<B>) -> String {
+        let lcx = &mut *self.lcx;
+        let text = lcx.tsb.finish(&mut lcx.styles);
+        lcx.analyze_text(&text);
+        lcx.build_into_layout(self.fcx, self.scale, &text, layout, None);
+        lcx.reset();
+        text
+    }
+
+    /// Builds the layout and returns it along with the accumulated text.
+    #[cfg(feature = "std")]
+    pub fn build(&mut self) -> (Layout<B>, String) {
+        let mut layout = Layout::default();
+        let text = self.build_into(&mut layout);
+        (layout, text)
+    }
+}
+
 #[doc(hidden)]
 pub trait TextSource {
     fn as_str(&self) -> &str;
@@ -200,3 +734,377 @@ impl<'a> TextSource for &'a str {
         self
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::layout::{Alignment, PositionedLayoutItem};
+    use crate::testing::{self, Brush, Content};
+
+    /// Rebuilds a layout of `text` after replacing `range` with `inserted`
+    /// and checks that it matches a full build of the edited text.
+    fn check_edit(text: &str, range: Range<usize>, inserted: &str, max_advance: Option<f32>) {
+        check_aligned_edit(text, range, inserted, max_advance, Alignment::Start);
+    }
+
+    fn check_aligned_edit(
+        text: &str,
+        range: Range<usize>,
+        inserted: &str,
+        max_advance: Option<f32>,
+        alignment: Alignment,
+    ) {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let mut layout = testing::build(&mut fcx, &mut lcx, text, &Content::default());
+        layout.break_all_lines(max_advance, alignment);
+        let mut edited = text.to_string();
+        edited.replace_range(range.clone(), inserted);
+        let mut builder = testing::ranged_builder(&mut fcx, &mut lcx, &edited, &Content::default());
+        builder.build_into_edited(&mut layout, range.clone(), inserted);
+        layout.break_all_lines(max_advance, alignment);
+        let mut expected = testing::build(&mut fcx, &mut lcx, &edited, &Content::default());
+        expected.break_all_lines(max_advance, alignment);
+        assert_eq!(
+            testing::dump(&layout),
+            testing::dump(&expected),
+            "replacing {range:?} of {text:?} with {inserted:?}"
+        );
+        assert_eq!(layout.min_content_width(), expected.min_content_width());
+        assert_eq!(layout.max_content_width(), expected.max_content_width());
+    }
+
+    const TEXT: &str = "one two three\nfour five six\nseven eight\n\nnine";
+
+    #[test]
+    fn edit_within_paragraph() {
+        for max_advance in [None, Some(60.), Some(25.)] {
+            check_edit(TEXT, 4..7, "2", max_advance);
+            check_edit(TEXT, 4..7, "two two two", max_advance);
+            check_edit(TEXT, 18..19, "", max_advance);
+            check_edit(TEXT, 0..0, "zero ", max_advance);
+            check_edit(TEXT, 45..45, " ten", max_advance);
+        }
+    }
+
+    #[test]
+    fn edit_paragraphs() {
+        for max_advance in [None, Some(60.), Some(25.)] {
+            // Split a paragraph.
+            check_edit(TEXT, 7..8, "\n", max_advance);
+            // Join paragraphs.
+            check_edit(TEXT, 13..14, "", max_advance);
+            check_edit(TEXT, 40..41, "", max_advance);
+            // Replace several paragraphs.
+            check_edit(TEXT, 10..30, "a\nb\nc", max_advance);
+            check_edit(TEXT, 0..45, "all", max_advance);
+            // Insert an empty paragraph.
+            check_edit(TEXT, 28..28, "\n", max_advance);
+        }
+    }
+
+    #[test]
+    fn edit_with_changed_paragraph_style() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let mut layout = testing::build(&mut fcx, &mut lcx, "ab\ncd\nef", &Content::default());
+        layout.break_all_lines(None, Alignment::Start);
+        // Indent the last paragraph along with an edit of the first.
+        let text = "xab\ncd\nef";
+        let content = Content {
+            spans: &[(StyleProperty::TextIndent(20.), 7..9)],
+            ..Content::default()
+        };
+        let mut builder = testing::ranged_builder(&mut fcx, &mut lcx, text, &content);
+        builder.build_into_edited(&mut layout, 0..0, "x");
+        layout.break_all_lines(None, Alignment::Start);
+        let mut expected = testing::build(&mut fcx, &mut lcx, text, &content);
+        expected.break_all_lines(None, Alignment::Start);
+        assert_eq!(testing::dump(&layout), testing::dump(&expected));
+    }
+
+    #[test]
+    fn edit_separators() {
+        // A line feed inserted after a carriage return joins it into a
+        // single separator.
+        check_edit("one\rtwo", 4..4, "\n", Some(60.));
+        check_edit("one\r\ntwo", 4..5, "", Some(60.));
+        check_edit("one\r\ntwo", 3..4, "", Some(60.));
+    }
+
+    #[test]
+    fn edit_rtl() {
+        let text = "abc\nשלום עולם\ndef";
+        check_edit(text, 4..6, "", Some(60.));
+        check_edit(text, 13..13, " שלום", Some(60.));
+        check_edit(text, 3..4, " ", Some(60.));
+    }
+
+    #[test]
+    fn edit_before_emergency_break() {
+        // The lines of the following paragraph do not depend on an emergency
+        // break before its separator.
+        check_aligned_edit(
+            "1 2 3 ש\r1 2 3 שלום 1 2 3 ש\n\u{2029}bb ",
+            25..33,
+            "1 2 3 ",
+            Some(25.),
+            Alignment::End,
+        );
+        check_aligned_edit(
+            "\tשש\t日本1 2 3 \u{2029}x\u{ad}y日本",
+            1..13,
+            "\r\n\r\n",
+            Some(25.),
+            Alignment::Middle,
+        );
+    }
+
+    #[test]
+    fn edit_to_empty() {
+        check_edit(TEXT, 0..TEXT.len(), "", Some(60.));
+        check_edit("", 0..0, "text", Some(60.));
+    }
+
+    #[test]
+    fn consecutive_edits() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let mut text = TEXT.to_string();
+        let mut layout = testing::build(&mut fcx, &mut lcx, &text, &Content::default());
+        layout.break_all_lines(Some(60.), Alignment::Start);
+        let edits = [
+            (4..7, "2"),
+            (5..5, " and more"),
+            (0..3, ""),
+            (20..21, "\n"),
+            (2..2, "x"),
+            (30..40, ""),
+        ];
+        for (range, inserted) in edits {
+            text.replace_range(range.clone(), inserted);
+            let mut builder =
+                testing::ranged_builder(&mut fcx, &mut lcx, &text, &Content::default());
+            builder.build_into_edited(&mut layout, range, inserted);
+            layout.break_all_lines(Some(60.), Alignment::Start);
+            let mut expected = testing::build(&mut fcx, &mut lcx, &text, &Content::default());
+            expected.break_all_lines(Some(60.), Alignment::Start);
+            assert_eq!(testing::dump(&layout), testing::dump(&expected), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn edit_after_collection_change() {
+        fn font_ids(layout: &Layout<Brush>) -> Vec<u64> {
+            layout
+                .lines()
+                .flat_map(|line| line.runs())
+                .map(|run| run.font().data.id())
+                .collect()
+        }
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let mut layout = testing::build(&mut fcx, &mut lcx, "ab\ncd", &Content::default());
+        layout.break_all_lines(None, Alignment::Start);
+        // Replace the font data of the family, so that runs that were shaped
+        // before refer to a font that is no longer in the collection.
+        let family = fcx.collection.family_id(testing::FAMILY).unwrap();
+        assert!(fcx.collection.unregister_family(family));
+        fcx.collection.register_fonts(testing::FONT.to_vec());
+        let mut builder =
+            testing::ranged_builder(&mut fcx, &mut lcx, "ab\ncde", &Content::default());
+        builder.build_into_edited(&mut layout, 5..5, "e");
+        layout.break_all_lines(None, Alignment::Start);
+        let mut expected = testing::build(&mut fcx, &mut lcx, "ab\ncde", &Content::default());
+        expected.break_all_lines(None, Alignment::Start);
+        assert_eq!(font_ids(&layout), font_ids(&expected));
+        assert_eq!(testing::dump(&layout), testing::dump(&expected));
+    }
+
+    const RED: Brush = [255, 0, 0, 255];
+
+    /// Returns the number of glyphs and the brush of the preedit decoration
+    /// of each glyph run.
+    fn preedit_runs(layout: &Layout<Brush>) -> Vec<(usize, Option<Brush>)> {
+        let mut runs = vec![];
+        for line in layout.lines() {
+            for item in line.items() {
+                if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                    let preedit = glyph_run.style().preedit.as_ref();
+                    runs.push((
+                        glyph_run.positioned_glyphs().count(),
+                        preedit.map(|preedit| preedit.brush),
+                    ));
+                }
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn preedit() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        // The preedit decoration uses the brush of the underline.
+        let content = Content {
+            spans: &[(StyleProperty::UnderlineBrush(Some(RED)), 3..6)],
+            ..Content::default()
+        };
+        let mut builder = testing::ranged_builder(&mut fcx, &mut lcx, "abcdef", &content);
+        builder.set_preedit(2..5, Some(3..4));
+        let mut layout = builder.build();
+        layout.break_all_lines(None, Alignment::Start);
+        let black = Brush::default();
+        assert_eq!(
+            preedit_runs(&layout),
+            [(2, None), (1, Some(black)), (2, Some(RED)), (1, None)]
+        );
+        assert_eq!(layout.preedit_range(), Some(2..5));
+        assert_eq!(layout.data.preedit_cursor, Some(3..4));
+        // The preedit is not retained by the next layout.
+        let layout = testing::build(&mut fcx, &mut lcx, "abcdef", &Content::default());
+        assert_eq!(layout.preedit_range(), None);
+    }
+
+    #[test]
+    fn push_preedit_text() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let mut builder = testing::tree_builder(&mut fcx, &mut lcx);
+        builder.push_text("ab");
+        // The cursor is relative to the composed text.
+        builder.push_preedit_text("cde", Some(1..1));
+        builder.push_text("f");
+        let (mut layout, text) = builder.build();
+        layout.break_all_lines(None, Alignment::Start);
+        assert_eq!(text, "abcdef");
+        assert_eq!(
+            preedit_runs(&layout),
+            [(2, None), (3, Some(Brush::default())), (1, None)]
+        );
+        assert_eq!(layout.preedit_range(), Some(2..5));
+        assert_eq!(layout.data.preedit_cursor, Some(3..3));
+    }
+
+    #[test]
+    fn paragraphs() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let text = "ab\n\u{5D0}\u{5D1}\r\ncd\u{2029}ef";
+        // Paragraph properties are taken from the first character.
+        let content = Content {
+            spans: &[
+                (StyleProperty::TextAlign(Some(Alignment::Middle)), 0..1),
+                (StyleProperty::TextAlign(Some(Alignment::End)), 10..11),
+                (StyleProperty::TextAlign(Some(Alignment::End)), 14..16),
+            ],
+            ..Content::default()
+        };
+        let mut layout = testing::build(&mut fcx, &mut lcx, text, &content);
+        layout.break_all_lines(Some(50.), Alignment::Start);
+        // A carriage return followed by a line feed is a single separator.
+        let paragraphs: Vec<_> = layout
+            .data
+            .paragraphs
+            .iter()
+            .map(|paragraph| (paragraph.text_range.clone(), paragraph.base_level))
+            .collect();
+        assert_eq!(paragraphs, [(0..3, 0), (3..9, 1), (9..14, 0), (14..16, 0)]);
+        let lines: Vec<_> = layout
+            .lines()
+            .map(|line| (line.is_rtl(), line.metrics().offset))
+            .collect();
+        assert_eq!(
+            lines,
+            [(false, 15.), (true, 30.), (false, 0.), (false, 30.)]
+        );
+    }
+
+    /// Records the words it is asked to hyphenate and allows a break after
+    /// every character.
+    #[derive(Clone, Default)]
+    struct RecordingHyphenator(Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl Hyphenator for RecordingHyphenator {
+        fn hyphenate(&self, word: &str, breaks: &mut Vec<usize>) {
+            self.0.lock().unwrap().push(word.to_string());
+            breaks.extend(word.char_indices().skip(1).map(|(offset, _)| offset));
+        }
+    }
+
+    fn hyphenate(text: &str, max_advance: f32) -> (Vec<String>, Vec<&str>) {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let hyphenator = RecordingHyphenator::default();
+        lcx.set_hyphenator("en", hyphenator.clone());
+        let style = [
+            StyleProperty::Locale(Some("en")),
+            StyleProperty::Hyphens(Hyphens::Auto),
+        ];
+        let mut layout = testing::build(&mut fcx, &mut lcx, text, &Content::styled(&style));
+        layout.break_all_lines(Some(max_advance), Alignment::Start);
+        let words = hyphenator.0.lock().unwrap().clone();
+        (words, testing::line_texts(&layout, text))
+    }
+
+    #[test]
+    fn auto_hyphenation() {
+        let (words, lines) = hyphenate("abcdef gh", 45.);
+        assert_eq!(words, ["abcdef", "gh"]);
+        assert_eq!(lines, ["abc", "def ", "gh"]);
+    }
+
+    #[test]
+    fn soft_hyphen_prevents_auto_hyphenation() {
+        let (words, lines) = hyphenate("ab\u{AD}cdef gh", 45.);
+        assert_eq!(words, ["gh"]);
+        assert_eq!(lines, ["ab\u{AD}", "cdef ", "gh"]);
+        let (words, _) = hyphenate("\u{AD}abcd abcd\u{AD}", 45.);
+        assert!(words.is_empty());
+    }
+
+    #[test]
+    fn shape_cache() {
+        fn build(fcx: &mut FontContext, lcx: &mut LayoutContext<Brush>) -> Layout<Brush> {
+            let mut layout = testing::build(fcx, lcx, "one two\nthree", &Content::default());
+            layout.break_all_lines(None, Alignment::Start);
+            layout
+        }
+        fn font_ids(layout: &Layout<Brush>) -> Vec<u64> {
+            layout.runs().map(|run| run.font().data.id()).collect()
+        }
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::<Brush>::new();
+        let expected = testing::dump(&build(&mut fcx, &mut lcx));
+        lcx.set_shape_cache_capacity(1 << 20);
+        assert_eq!(testing::dump(&build(&mut fcx, &mut lcx)), expected);
+        assert_eq!(testing::dump(&build(&mut fcx, &mut lcx)), expected);
+        // Entries are evicted while the layout is built if the cache is too
+        // small to hold all of them.
+        lcx.set_shape_cache_capacity(1);
+        assert_eq!(testing::dump(&build(&mut fcx, &mut lcx)), expected);
+        assert_eq!(testing::dump(&build(&mut fcx, &mut lcx)), expected);
+        lcx.set_shape_cache_capacity(1 << 20);
+        build(&mut fcx, &mut lcx);
+        lcx.prune_shape_cache(0);
+        assert_eq!(testing::dump(&build(&mut fcx, &mut lcx)), expected);
+        lcx.clear_shape_cache();
+        assert_eq!(testing::dump(&build(&mut fcx, &mut lcx)), expected);
+        // Adding an italic font to the family changes the font of italic
+        // text, so that text is shaped again.
+        let italic = [StyleProperty::FontStyle(FontStyle::Italic)];
+        testing::build(&mut fcx, &mut lcx, "one", &Content::styled(&italic));
+        let mut descriptor = testing::descriptor(testing::FAMILY);
+        descriptor.style = Some(fontique::Style::Italic);
+        fcx.collection
+            .register_fonts_with_descriptor(testing::FONT.to_vec(), &descriptor);
+        let layout = testing::build(&mut fcx, &mut lcx, "one", &Content::styled(&italic));
+        let fresh = testing::build(
+            &mut fcx,
+            &mut LayoutContext::new(),
+            "one",
+            &Content::styled(&italic),
+        );
+        assert_eq!(font_ids(&layout), font_ids(&fresh));
+    }
+}
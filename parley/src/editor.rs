@@ -0,0 +1,721 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Plain text editing.
+
+use core::ops::Range;
+
+use peniko::kurbo::Rect;
+
+use crate::context::LayoutContext;
+use crate::font::FontContext;
+use crate::layout::{Affinity, Alignment, BreakStrategy, Cursor, Layout, Selection};
+use crate::style::{Brush, StyleProperty};
+
+/// Editor for a single block of plain text.
+///
+/// The editor owns the text along with its layout, a selection and an
+/// optional composition range. Operations that modify the text rebuild the
+/// layout immediately and so require the font and layout contexts, while
+/// cursor movement only depends on the current layout.
+///
+/// Text positions are byte offsets into the text and always lie on cluster
+/// boundaries after any movement or editing operation. Movement uses the
+/// layout as it was last built, so after changing the text positions are
+/// only guaranteed to lie on character boundaries until the layout is
+/// updated.
+#[derive(Clone)]
+pub struct PlainEditor<B: Brush> {
+    text: String,
+    layout: Layout<B>,
+    default_style: Vec<StyleProperty<'static, B>>,
+    scale: f32,
+    width: Option<f32>,
+    alignment: Alignment,
+    strategy: BreakStrategy,
+    selection: Selection,
+    /// Affinity of the focus of the selection.
+    affinity: Affinity,
+    compose: Option<Range<usize>>,
+    /// Range of the input method cursor within the text.
+    compose_cursor: Option<Range<usize>>,
+    /// Offset along the baseline that is maintained across consecutive
+    /// vertical movements.
+    preferred_offset: Option<f32>,
+    /// Edits of the text since the layout was last built, combined into the
+    /// replaced range of the previous text and the length of its
+    /// replacement, if the layout can be rebuilt incrementally.
+    edit: Option<(Range<usize>, usize)>,
+    /// Whether the layout must be built from the full text, because the text
+    /// was replaced or the style or scale changed.
+    rebuild: bool,
+    /// Whether the lines must be broken again, because the width, alignment
+    /// or line breaking strategy changed.
+    lines_dirty: bool,
+}
+
+impl<B: Brush> PlainEditor<B> {
+    /// Creates a new empty editor with the specified display scale.
+    pub fn new(scale: f32) -> Self {
+        Self {
+            text: String::new(),
+            layout: Layout::default(),
+            default_style: Vec::new(),
+            scale,
+            width: None,
+            alignment: Alignment::default(),
+            strategy: BreakStrategy::default(),
+            selection: Selection::default(),
+            affinity: Affinity::default(),
+            compose: None,
+            compose_cursor: None,
+            preferred_offset: None,
+            edit: None,
+            rebuild: true,
+            lines_dirty: true,
+        }
+    }
+
+    /// Returns the current text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the layout of the current text.
+    ///
+    /// This is stale after changing the text or any of the layout parameters
+    /// until [`update_layout`](Self::update_layout) is called.
+    pub fn layout(&self) -> &Layout<B> {
+        &self.layout
+    }
+
+    /// Returns the current selection.
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    /// Returns the range of text that is being composed by an input method,
+    /// if any.
+    pub fn compose(&self) -> Option<Range<usize>> {
+        self.compose.clone()
+    }
+
+    /// Replaces the text and places a collapsed selection at its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.text.push_str(text);
+        self.selection = Selection::collapsed(text.len());
+        self.affinity = Affinity::Downstream;
+        self.compose = None;
+        self.compose_cursor = None;
+        self.preferred_offset = None;
+        self.edit = None;
+        self.rebuild = true;
+    }
+
+    /// Sets the properties that apply to the full text.
+    pub fn set_default_style(
+        &mut self,
+        style: impl IntoIterator<Item = StyleProperty<'static, B>>,
+    ) {
+        self.default_style.clear();
+        self.default_style.extend(style);
+        self.edit = None;
+        self.rebuild = true;
+    }
+
+    /// Sets the display scale.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+        self.edit = None;
+        self.rebuild = true;
+    }
+
+    /// Sets the maximum advance of each line, or `None` for unbounded lines.
+    pub fn set_width(&mut self, width: Option<f32>) {
+        self.width = width;
+        self.lines_dirty = true;
+    }
+
+    /// Sets the alignment of lines.
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment;
+        self.lines_dirty = true;
+    }
+
+    /// Sets the line breaking strategy.
+    pub fn set_break_strategy(&mut self, strategy: BreakStrategy) {
+        self.strategy = strategy;
+        self.lines_dirty = true;
+    }
+
+    /// Rebuilds the layout if the text or any of the layout parameters have
+    /// changed since it was last built.
+    ///
+    /// After edits of the text, only the affected paragraphs are shaped and
+    /// broken into lines again. If only the width, alignment or line
+    /// breaking strategy changed, the lines are broken again without
+    /// shaping the text.
+    pub fn update_layout(&mut self, fcx: &mut FontContext, lcx: &mut LayoutContext<B>) {
+        if self.rebuild || self.edit.is_some() {
+            let mut builder = lcx.ranged_builder(fcx, &self.text, self.scale);
+            for property in &self.default_style {
+                builder.push_default(property);
+            }
+            if let Some(compose) = self.compose.clone() {
+                builder.set_preedit(compose, self.compose_cursor.clone());
+            }
+            match self.edit.take() {
+                Some((range, len)) if !self.rebuild => {
+                    let inserted = &self.text[range.start..range.start + len];
+                    builder.build_into_edited(&mut self.layout, range, inserted);
+                }
+                _ => builder.build_into(&mut self.layout),
+            }
+            self.rebuild = false;
+        } else if !self.lines_dirty {
+            return;
+        }
+        self.layout
+            .break_all_lines_with_strategy(self.width, self.alignment, self.strategy);
+        self.lines_dirty = false;
+    }
+
+    /// Returns a cursor at the focus of the selection.
+    pub fn cursor(&self) -> Cursor {
+        Cursor::from_index(&self.layout, self.selection.focus, self.affinity)
+    }
+
+    /// Returns a caret of the specified size at the focus of the selection.
+    pub fn cursor_geometry(&self, size: f32) -> Option<Rect> {
+        self.cursor().geometry(&self.layout, size)
+    }
+
+    /// Returns the rectangles that cover the selected text.
+    pub fn selection_geometry(&self) -> Vec<Rect> {
+        self.selection.geometry(&self.layout)
+    }
+
+    /// Returns the area that the candidate window of an input method should
+    /// avoid, which covers the composed text if there is any and otherwise
+    /// the selection or caret.
+    pub fn ime_cursor_area(&self) -> Option<Rect> {
+        if let Some(area) = Cursor::preedit_area(&self.layout) {
+            return Some(area);
+        }
+        let mut area: Option<Rect> = None;
+        self.selection.geometry_with(&self.layout, |rect| {
+            area = Some(area.map_or(rect, |area| area.union(rect)));
+        });
+        area.or_else(|| self.cursor_geometry(1.))
+    }
+
+    /// Sets the text that is being composed by an input method, replacing
+    /// any previously composed text or, if there is none, the selection.
+    ///
+    /// The optional cursor range is relative to the start of the composed
+    /// text. Setting empty text is equivalent to
+    /// [`clear_compose`](Self::clear_compose).
+    pub fn set_compose(
+        &mut self,
+        fcx: &mut FontContext,
+        lcx: &mut LayoutContext<B>,
+        text: &str,
+        cursor: Option<Range<usize>>,
+    ) {
+        if text.is_empty() {
+            self.clear_compose(fcx, lcx);
+            return;
+        }
+        let range = self
+            .compose
+            .take()
+            .unwrap_or_else(|| self.selection.text_range());
+        let start = range.start;
+        self.edit_text(range, text);
+        let cursor = cursor.map(|cursor| start + cursor.start..start + cursor.end);
+        let focus = cursor
+            .as_ref()
+            .map_or(start + text.len(), |cursor| cursor.start);
+        self.compose = Some(start..start + text.len());
+        self.compose_cursor = cursor;
+        self.selection = Selection::collapsed(focus);
+        self.affinity = Affinity::Downstream;
+        self.preferred_offset = None;
+        self.update_layout(fcx, lcx);
+    }
+
+    /// Removes the text that is being composed by an input method, if any.
+    pub fn clear_compose(&mut self, fcx: &mut FontContext, lcx: &mut LayoutContext<B>) {
+        self.remove_compose_text();
+        self.update_layout(fcx, lcx);
+    }
+
+    fn remove_compose_text(&mut self) {
+        let Some(range) = self.compose.take() else {
+            return;
+        };
+        self.compose_cursor = None;
+        self.selection = Selection::collapsed(range.start);
+        self.affinity = Affinity::Downstream;
+        self.edit_text(range, "");
+    }
+
+    /// Replaces a range of the text, recording the edit so that the layout
+    /// can be rebuilt incrementally.
+    fn edit_text(&mut self, range: Range<usize>, text: &str) {
+        let new_len = self.text.len() - range.len() + text.len();
+        self.edit = match self.edit.take() {
+            // Combine with the previous edit, mapping the range back to the
+            // text of the layout.
+            Some((prev, len)) => {
+                let old_len = self.text.len() - len + prev.len();
+                let start = prev.start.min(range.start);
+                // Offsets following the previous replacement are shifted by
+                // the difference in its length.
+                let end = prev.end + range.end.saturating_sub(prev.start + len);
+                Some((start..end, new_len + (end - start) - old_len))
+            }
+            None if !self.rebuild => Some((range.clone(), text.len())),
+            None => None,
+        };
+        self.text.replace_range(range, text);
+    }
+
+    /// Replaces the selected text with the specified text, leaving a
+    /// collapsed selection after the inserted text.
+    ///
+    /// Editing operations first remove any text that is being composed by an
+    /// input method, so committed text from an input method can be inserted
+    /// with this method.
+    pub fn insert_or_replace_selection(
+        &mut self,
+        fcx: &mut FontContext,
+        lcx: &mut LayoutContext<B>,
+        text: &str,
+    ) {
+        self.remove_compose_text();
+        self.replace_range(fcx, lcx, self.selection.text_range(), text);
+    }
+
+    /// Deletes the selected text or, if the selection is collapsed, the
+    /// grapheme cluster following it.
+    pub fn delete(&mut self, fcx: &mut FontContext, lcx: &mut LayoutContext<B>) {
+        self.remove_compose_text();
+        self.update_layout(fcx, lcx);
+        let range = if self.selection.is_collapsed() {
+            let start = self.selection.focus;
+            start
+                ..self
+                    .cursor()
+                    .next_logical(&self.layout)
+                    .insert_point
+                    .max(start)
+        } else {
+            self.selection.text_range()
+        };
+        self.replace_range(fcx, lcx, range, "");
+    }
+
+    /// Deletes the selected text or, if the selection is collapsed, the
+    /// grapheme cluster preceding it.
+    pub fn backdelete(&mut self, fcx: &mut FontContext, lcx: &mut LayoutContext<B>) {
+        self.remove_compose_text();
+        self.update_layout(fcx, lcx);
+        let range = if self.selection.is_collapsed() {
+            let end = self.selection.focus;
+            self.cursor()
+                .previous_logical(&self.layout)
+                .insert_point
+                .min(end)..end
+        } else {
+            self.selection.text_range()
+        };
+        self.replace_range(fcx, lcx, range, "");
+    }
+
+    /// Deletes the selected text or, if the selection is collapsed, the text
+    /// up to the end of the current or next word.
+    pub fn delete_word(&mut self, fcx: &mut FontContext, lcx: &mut LayoutContext<B>) {
+        self.remove_compose_text();
+        self.update_layout(fcx, lcx);
+        let range = if self.selection.is_collapsed() {
+            let start = self.selection.focus;
+            start
+                ..self
+                    .cursor()
+                    .next_word(&self.layout)
+                    .insert_point
+                    .max(start)
+        } else {
+            self.selection.text_range()
+        };
+        self.replace_range(fcx, lcx, range, "");
+    }
+
+    /// Deletes the selected text or, if the selection is collapsed, the text
+    /// back to the start of the current or previous word.
+    pub fn backdelete_word(&mut self, fcx: &mut FontContext, lcx: &mut LayoutContext<B>) {
+        self.remove_compose_text();
+        self.update_layout(fcx, lcx);
+        let range = if self.selection.is_collapsed() {
+            let end = self.selection.focus;
+            self.cursor()
+                .previous_word(&self.layout)
+                .insert_point
+                .min(end)..end
+        } else {
+            self.selection.text_range()
+        };
+        self.replace_range(fcx, lcx, range, "");
+    }
+
+    fn replace_range(
+        &mut self,
+        fcx: &mut FontContext,
+        lcx: &mut LayoutContext<B>,
+        range: Range<usize>,
+        text: &str,
+    ) {
+        let end = range.start + text.len();
+        if !range.is_empty() || !text.is_empty() {
+            self.edit_text(range, text);
+        }
+        self.selection = Selection::collapsed(end);
+        self.affinity = Affinity::Downstream;
+        self.preferred_offset = None;
+        self.update_layout(fcx, lcx);
+    }
+
+    /// Selects the full text.
+    pub fn select_all(&mut self) {
+        self.set_selection(Selection::new(0, self.text.len()), Affinity::Upstream);
+    }
+
+    /// Collapses the selection to its focus.
+    pub fn collapse_selection(&mut self) {
+        let focus = self.selection.focus;
+        self.set_selection(Selection::collapsed(focus), self.affinity);
+    }
+
+    /// Selects the word containing the specified text position.
+    pub fn select_word_at(&mut self, index: usize) {
+        let selection = Selection::word_at(&self.layout, index.min(self.text.len()));
+        self.set_selection(selection, Affinity::Upstream);
+    }
+
+    /// Selects the word at the specified point.
+    pub fn select_word_at_point(&mut self, x: f32, y: f32) {
+        let index = Cursor::from_point(&self.layout, x, y).insert_point;
+        self.select_word_at(index);
+    }
+
+    /// Moves the focus of the selection to the specified point, collapsing
+    /// the selection unless `extend` is true.
+    pub fn move_to_point(&mut self, x: f32, y: f32, extend: bool) {
+        self.move_to(Cursor::from_point(&self.layout, x, y), extend);
+    }
+
+    /// Moves the focus one cluster to the left in visual order.
+    pub fn move_left(&mut self, extend: bool) {
+        self.move_to(self.cursor().previous_visual(&self.layout), extend);
+    }
+
+    /// Moves the focus one cluster to the right in visual order.
+    pub fn move_right(&mut self, extend: bool) {
+        self.move_to(self.cursor().next_visual(&self.layout), extend);
+    }
+
+    /// Moves the focus to the start of the current or previous word.
+    pub fn move_word_backward(&mut self, extend: bool) {
+        self.move_to(self.cursor().previous_word(&self.layout), extend);
+    }
+
+    /// Moves the focus to the end of the current or next word.
+    pub fn move_word_forward(&mut self, extend: bool) {
+        self.move_to(self.cursor().next_word(&self.layout), extend);
+    }
+
+    /// Moves the focus to the start of the current line.
+    pub fn move_to_line_start(&mut self, extend: bool) {
+        self.move_to(self.cursor().line_start(&self.layout), extend);
+    }
+
+    /// Moves the focus to the end of the current line.
+    pub fn move_to_line_end(&mut self, extend: bool) {
+        self.move_to(self.cursor().line_end(&self.layout), extend);
+    }
+
+    /// Moves the focus to the start of the text.
+    pub fn move_to_text_start(&mut self, extend: bool) {
+        self.move_to(
+            Cursor::from_index(&self.layout, 0, Affinity::Downstream),
+            extend,
+        );
+    }
+
+    /// Moves the focus to the end of the text.
+    pub fn move_to_text_end(&mut self, extend: bool) {
+        let end = self.text.len();
+        self.move_to(
+            Cursor::from_index(&self.layout, end, Affinity::Downstream),
+            extend,
+        );
+    }
+
+    /// Moves the focus to the previous line, preserving its offset along the
+    /// baseline across consecutive vertical movements.
+    pub fn move_up(&mut self, extend: bool) {
+        self.move_lines(-1, extend);
+    }
+
+    /// Moves the focus to the next line, preserving its offset along the
+    /// baseline across consecutive vertical movements.
+    pub fn move_down(&mut self, extend: bool) {
+        self.move_lines(1, extend);
+    }
+
+    fn move_lines(&mut self, delta: isize, extend: bool) {
+        let mut preferred_offset = self.preferred_offset;
+        let cursor = self
+            .cursor()
+            .move_lines(&self.layout, delta, &mut preferred_offset);
+        self.move_to(cursor, extend);
+        self.preferred_offset = preferred_offset;
+    }
+
+    fn move_to(&mut self, cursor: Cursor, extend: bool) {
+        let focus = self.char_boundary(cursor.insert_point);
+        let selection = if extend {
+            Selection::new(self.selection.anchor, focus)
+        } else {
+            Selection::collapsed(focus)
+        };
+        self.set_selection(selection, cursor.affinity());
+    }
+
+    fn set_selection(&mut self, selection: Selection, affinity: Affinity) {
+        self.selection = Selection::new(
+            self.char_boundary(selection.anchor),
+            self.char_boundary(selection.focus),
+        );
+        self.affinity = affinity;
+        self.preferred_offset = None;
+    }
+
+    /// Returns the nearest character boundary of the text at or before the
+    /// specified position, which may come from a stale layout.
+    fn char_boundary(&self, index: usize) -> usize {
+        let mut index = index.min(self.text.len());
+        while !self.text.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Brush, Content};
+
+    const TEXT: &str = "one two three\nfour five six\nseven eight";
+
+    fn editor(fcx: &mut FontContext, lcx: &mut LayoutContext<Brush>) -> PlainEditor<Brush> {
+        let mut editor = PlainEditor::new(1.);
+        editor.set_default_style(testing::default_style());
+        editor.set_width(Some(60.));
+        editor.set_text(TEXT);
+        editor.update_layout(fcx, lcx);
+        editor
+    }
+
+    /// Asserts that the layout of the editor matches a full rebuild.
+    fn assert_matches_full_build(
+        editor: &PlainEditor<Brush>,
+        fcx: &mut FontContext,
+        lcx: &mut LayoutContext<Brush>,
+    ) {
+        let mut builder = testing::ranged_builder(fcx, lcx, editor.text(), &Content::default());
+        if let Some(compose) = editor.compose() {
+            builder.set_preedit(compose, editor.compose_cursor.clone());
+        }
+        let mut layout = Layout::default();
+        builder.build_into(&mut layout);
+        layout.break_all_lines_with_strategy(editor.width, editor.alignment, editor.strategy);
+        assert_eq!(
+            testing::dump(editor.layout()),
+            testing::dump(&layout),
+            "text {:?}",
+            editor.text()
+        );
+    }
+
+    #[test]
+    fn merged_edits() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        // Pairs of edits, each applied to the text produced by the previous.
+        let cases: &[[(Range<usize>, &str); 2]] = &[
+            // Second edit before the first.
+            [(10..13, "3"), (0..3, "1")],
+            [(10..13, "three three"), (4..7, "")],
+            // Second edit after the first.
+            [(0..3, "1"), (6..11, "3")],
+            [(0..3, "one one"), (18..22, "4")],
+            [(4..7, ""), (20..25, "5\n5")],
+            // Second edit adjacent to the first.
+            [(4..7, "2"), (5..5, "2")],
+            [(4..7, "2"), (3..4, "")],
+            // Overlapping edits.
+            [(4..7, "TWO"), (2..6, "x")],
+            [(4..7, "TWO"), (5..10, "")],
+            [(4..7, ""), (0..20, "start")],
+            // Second edit within the first.
+            [(4..7, "a longer two"), (6..10, "x")],
+            // Edits spanning paragraphs.
+            [(10..20, "\n"), (0..12, "")],
+            [(13..14, ""), (30..35, "\n\n")],
+        ];
+        for [(range1, text1), (range2, text2)] in cases {
+            let mut editor = editor(&mut fcx, &mut lcx);
+            editor.edit_text(range1.clone(), text1);
+            editor.edit_text(range2.clone(), text2);
+            let mut expected = TEXT.to_string();
+            expected.replace_range(range1.clone(), text1);
+            expected.replace_range(range2.clone(), text2);
+            assert_eq!(editor.text(), expected);
+            // Applying the merged edit to the original text must produce
+            // the edited text.
+            let (range, len) = editor.edit.clone().unwrap();
+            let mut merged = TEXT.to_string();
+            merged.replace_range(range.clone(), &expected[range.start..range.start + len]);
+            assert_eq!(merged, expected, "edits {range1:?} and {range2:?}");
+            editor.update_layout(&mut fcx, &mut lcx);
+            assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        }
+    }
+
+    #[test]
+    fn edits_with_layout_changes() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut editor = editor(&mut fcx, &mut lcx);
+        // Lines are broken again without rebuilding the layout.
+        editor.set_width(Some(90.));
+        editor.update_layout(&mut fcx, &mut lcx);
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        // Changes of the lines do not prevent an incremental rebuild after
+        // edits, which are combined until the layout is updated.
+        editor.set_alignment(Alignment::Middle);
+        editor.edit_text(0..3, "1");
+        editor.set_width(Some(40.));
+        editor.edit_text(5..5, "x");
+        assert_eq!(editor.edit, Some((0..7, 6)));
+        editor.update_layout(&mut fcx, &mut lcx);
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        // Edits following a change of the style are not recorded.
+        editor.set_scale(1.);
+        editor.edit_text(0..1, "one");
+        assert_eq!(editor.edit, None);
+        editor.update_layout(&mut fcx, &mut lcx);
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+    }
+
+    #[test]
+    fn consecutive_edits() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut editor = editor(&mut fcx, &mut lcx);
+        editor.move_to_text_start(false);
+        for text in ["a", "b", " ", "c\n", "d"] {
+            editor.insert_or_replace_selection(&mut fcx, &mut lcx, text);
+            assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        }
+        editor.move_to_text_end(false);
+        for _ in 0..8 {
+            editor.backdelete(&mut fcx, &mut lcx);
+            assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        }
+        editor.move_to_text_start(false);
+        for _ in 0..4 {
+            editor.move_right(false);
+        }
+        editor.delete_word(&mut fcx, &mut lcx);
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        editor.delete(&mut fcx, &mut lcx);
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        // Composition replaces its previous text before it is committed.
+        editor.set_compose(&mut fcx, &mut lcx, "x", None);
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        editor.set_compose(&mut fcx, &mut lcx, "xyz\nw", Some(1..2));
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        editor.insert_or_replace_selection(&mut fcx, &mut lcx, "committed");
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+        editor.select_all();
+        editor.insert_or_replace_selection(&mut fcx, &mut lcx, "");
+        assert_eq!(editor.text(), "");
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+    }
+
+    #[test]
+    fn move_with_stale_layout() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut editor = editor(&mut fcx, &mut lcx);
+        // Positions found with the layout of the previous text must be
+        // snapped to character boundaries of the new text.
+        editor.set_text("a\u{C4}");
+        editor.move_left(false);
+        assert_eq!(editor.selection(), Selection::collapsed(1));
+        editor.move_right(true);
+        assert_eq!(editor.selection(), Selection::new(1, 1));
+        editor.move_to_point(25., 5., false);
+        assert_eq!(editor.selection(), Selection::collapsed(1));
+        editor.move_to_point(55., 25., true);
+        assert_eq!(editor.selection(), Selection::new(1, 3));
+        editor.move_to_point(25., 5., false);
+        editor.insert_or_replace_selection(&mut fcx, &mut lcx, "x");
+        assert_eq!(editor.text(), "ax\u{C4}");
+        assert_matches_full_build(&editor, &mut fcx, &mut lcx);
+    }
+
+    #[test]
+    fn compose() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut editor = PlainEditor::new(1.);
+        editor.set_default_style(testing::default_style());
+        editor.set_text("ab");
+        editor.update_layout(&mut fcx, &mut lcx);
+        let area = |editor: &PlainEditor<Brush>| {
+            let rect = editor.ime_cursor_area().unwrap();
+            (rect.x0, rect.x1)
+        };
+        assert_eq!(area(&editor), (20., 21.));
+        editor.set_compose(&mut fcx, &mut lcx, "xyz", Some(1..2));
+        assert_eq!(editor.text(), "abxyz");
+        assert_eq!(editor.compose(), Some(2..5));
+        assert_eq!(editor.selection(), Selection::collapsed(3));
+        assert_eq!(area(&editor), (30., 40.));
+        // Composed text is replaced rather than inserted at the cursor.
+        editor.set_compose(&mut fcx, &mut lcx, "w", None);
+        assert_eq!(editor.text(), "abw");
+        assert_eq!(editor.selection(), Selection::collapsed(3));
+        assert_eq!(area(&editor), (20., 30.));
+        editor.clear_compose(&mut fcx, &mut lcx);
+        assert_eq!(editor.text(), "ab");
+        assert_eq!(editor.compose(), None);
+        assert_eq!(editor.selection(), Selection::collapsed(2));
+        // Committing replaces the composed text.
+        editor.move_to_text_start(false);
+        editor.move_right(true);
+        editor.set_compose(&mut fcx, &mut lcx, "xy", None);
+        assert_eq!(editor.text(), "xyb");
+        assert_eq!(area(&editor), (0., 20.));
+        editor.insert_or_replace_selection(&mut fcx, &mut lcx, "z");
+        assert_eq!(editor.text(), "zb");
+        assert_eq!(editor.compose(), None);
+        assert_eq!(editor.selection(), Selection::collapsed(1));
+    }
+}
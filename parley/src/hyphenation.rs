@@ -0,0 +1,198 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Dictionary based hyphenation.
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+
+use alloc::collections::BTreeMap;
+
+/// Source of hyphenation points for words in a particular language.
+pub trait Hyphenator: Send + Sync {
+    /// Appends the byte offsets within `word` at which it may be broken with
+    /// a hyphen. Offsets must be in ascending order and lie on character
+    /// boundaries.
+    fn hyphenate(&self, word: &str, breaks: &mut Vec<usize>);
+}
+
+/// Hyphenator using the Liang patterns found in TeX hyphenation
+/// dictionaries.
+#[derive(Clone, Default, Debug)]
+pub struct HyphenationPatterns {
+    patterns: BTreeMap<String, Vec<u8>>,
+    exceptions: BTreeMap<String, Vec<usize>>,
+    max_pattern_len: usize,
+    left_min: usize,
+    right_min: usize,
+}
+
+impl HyphenationPatterns {
+    /// Creates a new hyphenator from the source text of a pattern list and
+    /// an optional list of exceptions.
+    ///
+    /// Patterns are separated by whitespace in the usual TeX form, such as
+    /// `.hy3ph` or `4te.`. Exceptions are words with the permitted
+    /// hyphenation points marked with `-`, such as `as-so-ciate`. In both
+    /// cases, text following a `%` on a line is ignored.
+    pub fn new(patterns: &str, exceptions: &str) -> Self {
+        let mut result = Self {
+            left_min: 2,
+            right_min: 3,
+            ..Default::default()
+        };
+        for pattern in tokens(patterns) {
+            let mut letters = String::new();
+            let mut values = vec![0];
+            for ch in pattern.chars() {
+                if let Some(value) = ch.to_digit(10) {
+                    *values.last_mut().unwrap() = value as u8;
+                } else {
+                    letters.push(ch);
+                    values.push(0);
+                }
+            }
+            result.max_pattern_len = result.max_pattern_len.max(values.len() - 1);
+            result.patterns.insert(letters, values);
+        }
+        for exception in tokens(exceptions) {
+            let mut word = String::new();
+            let mut breaks = vec![];
+            for ch in exception.chars() {
+                if ch == '-' {
+                    breaks.push(word.chars().count());
+                } else {
+                    word.push(ch);
+                }
+            }
+            result.exceptions.insert(word, breaks);
+        }
+        result
+    }
+
+    /// Sets the minimum number of characters that must precede and follow a
+    /// hyphenation point. The defaults are 2 and 3 respectively.
+    pub fn with_limits(mut self, left_min: usize, right_min: usize) -> Self {
+        self.left_min = left_min.max(1);
+        self.right_min = right_min.max(1);
+        self
+    }
+}
+
+impl Hyphenator for HyphenationPatterns {
+    fn hyphenate(&self, word: &str, breaks: &mut Vec<usize>) {
+        let chars: Vec<char> = word
+            .chars()
+            .map(|ch| ch.to_lowercase().next().unwrap_or(ch))
+            .collect();
+        let len = chars.len();
+        if len < self.left_min + self.right_min {
+            return;
+        }
+        // Character indices that may be preceded by a hyphen.
+        let mut points = vec![];
+        let lower: String = chars.iter().collect();
+        if let Some(exception) = self.exceptions.get(&lower) {
+            points.extend_from_slice(exception);
+        } else {
+            let mut padded = Vec::with_capacity(len + 2);
+            padded.push('.');
+            padded.extend_from_slice(&chars);
+            padded.push('.');
+            let mut values = vec![0u8; padded.len() + 1];
+            let mut key = String::new();
+            for start in 0..padded.len() {
+                key.clear();
+                let end = padded.len().min(start + self.max_pattern_len);
+                for (i, &ch) in padded[start..end].iter().enumerate() {
+                    key.push(ch);
+                    if let Some(pattern) = self.patterns.get(&key) {
+                        for (value, &pattern_value) in
+                            values[start..start + i + 2].iter_mut().zip(pattern)
+                        {
+                            *value = (*value).max(pattern_value);
+                        }
+                    }
+                }
+            }
+            // The value preceding character `i` of the word is at `i + 1`
+            // due to the leading padding.
+            points.extend((1..len).filter(|&i| values[i + 1] & 1 != 0));
+        }
+        let offsets: Vec<usize> = word.char_indices().map(|(offset, _)| offset).collect();
+        breaks.extend(
+            points
+                .into_iter()
+                .filter(|&i| i >= self.left_min && len - i >= self.right_min)
+                .filter_map(|i| offsets.get(i).copied()),
+        );
+    }
+}
+
+fn tokens(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .lines()
+        .flat_map(|line| line.split('%').next().unwrap_or("").split_whitespace())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    /// Patterns from Liang's thesis that hyphenate "hyphenation", and one
+    /// with a character that is encoded in two bytes.
+    const PATTERNS: &str = "
+        hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n
+        % Patterns in comments are ignored: 1ph 1at
+        é1l";
+
+    fn hyphenate(hyphenator: &HyphenationPatterns, word: &str) -> Vec<usize> {
+        let mut breaks = vec![];
+        hyphenator.hyphenate(word, &mut breaks);
+        breaks
+    }
+
+    #[test]
+    fn patterns() {
+        let hyphenator = HyphenationPatterns::new(PATTERNS, "");
+        assert_eq!(hyphenate(&hyphenator, "hyphenation"), [2, 6]);
+        // Words are lowercased before matching.
+        assert_eq!(hyphenate(&hyphenator, "HyPhenation"), [2, 6]);
+        // Without the comment, the patterns allow more breaks.
+        let hyphenator = HyphenationPatterns::new("hy3ph 1na 1ph 1at", "");
+        assert_eq!(hyphenate(&hyphenator, "hyphenation"), [2, 5, 6]);
+    }
+
+    #[test]
+    fn multibyte_offsets() {
+        let hyphenator = HyphenationPatterns::new(PATTERNS, "");
+        // Breaks are byte offsets, and "é" is two bytes long.
+        assert_eq!(hyphenate(&hyphenator, "xélla"), [3]);
+        assert_eq!(hyphenate(&hyphenator, "XÉLLA"), [3]);
+    }
+
+    #[test]
+    fn exceptions() {
+        let hyphenator = HyphenationPatterns::new(PATTERNS, "hyphena-tion % hyp-hen");
+        assert_eq!(hyphenate(&hyphenator, "hyphenation"), [7]);
+        assert_eq!(hyphenate(&hyphenator, "Hyphenation"), [7]);
+        // Words without exceptions still use the patterns, and exceptions
+        // in comments are ignored.
+        assert_eq!(hyphenate(&hyphenator, "hyphen"), [2]);
+    }
+
+    #[test]
+    fn limits() {
+        let hyphenator = HyphenationPatterns::new(PATTERNS, "");
+        assert_eq!(
+            hyphenate(&hyphenator.clone().with_limits(3, 3), "hyphenation"),
+            [6]
+        );
+        assert_eq!(
+            hyphenate(&hyphenator.clone().with_limits(1, 6), "hyphenation"),
+            [2]
+        );
+        // Words that are too short for both limits are never broken.
+        assert!(hyphenate(&hyphenator.with_limits(6, 6), "hyphenation").is_empty());
+    }
+}
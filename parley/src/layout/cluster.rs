@@ -44,22 +44,56 @@ impl<'a, B: Brush> Cluster<'a, B> {
         self.data.info.whitespace().is_space_or_nbsp()
     }
 
+    /// Returns true if the cluster is a newline (CR, LF or CRLF).
+    pub fn is_newline(&self) -> bool {
+        self.data.info.whitespace() == Whitespace::Newline
+    }
+
     /// Returns an iterator over the glyphs in the cluster.
     pub fn glyphs(&self) -> impl Iterator<Item = Glyph> + 'a + Clone {
-        if self.data.glyph_len == 0xFF {
+        // A kashida inserted by justification logically follows the cluster
+        // and is displayed as a sequence of tatweel glyphs that share its
+        // width.
+        let kashida = match self.run.line_data {
+            Some(line_data) if self.data.has_kashida() => line_data.kashida,
+            _ => 0.,
+        };
+        let tatweel_advance = self.run.data.tatweel_advance;
+        let tatweel_count = if kashida > 0. && tatweel_advance > 0. {
+            (kashida / tatweel_advance).ceil().max(1.) as usize
+        } else {
+            0
+        };
+        let tatweel = Glyph {
+            id: self.run.data.tatweel_id,
+            style_index: self.data.style_index,
+            x: 0.,
+            y: 0.,
+            advance: kashida / tatweel_count.max(1) as f32,
+        };
+        let (before, after) = if self.run.is_rtl() {
+            (tatweel_count, 0)
+        } else {
+            (0, tatweel_count)
+        };
+        let glyphs = if self.data.glyph_len == 0xFF {
             GlyphIter::Single(Some(Glyph {
                 id: self.data.glyph_offset,
                 style_index: self.data.style_index,
                 x: 0.,
                 y: 0.,
-                advance: self.data.advance,
+                advance: self.data.advance - kashida,
             }))
         } else {
             let start = self.run.data.glyph_start + self.data.glyph_offset as usize;
             GlyphIter::Slice(
                 self.run.layout.glyphs[start..start + self.data.glyph_len as usize].iter(),
             )
-        }
+        };
+        core::iter::repeat(tatweel)
+            .take(before)
+            .chain(glyphs)
+            .chain(core::iter::repeat(tatweel).take(after))
     }
 
     pub(crate) fn info(&self) -> ClusterInfo {
@@ -4,8 +4,17 @@
 //! Hit testing.
 
 use super::*;
+use crate::util::nearly_eq;
+use peniko::kurbo::Rect;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 
 /// Represents a position within a layout.
+///
+/// Offsets and advances are measured along the axes of the line as described
+/// for [`LineMetrics`], so in vertical writing modes the advance of a cluster
+/// is its vertical advance.
 #[derive(Copy, Clone, Default, Debug)]
 pub struct Cursor {
     /// Path to the target cluster.
@@ -30,7 +39,19 @@ pub struct Cursor {
 
 impl Cursor {
     /// Creates a new cursor from the specified layout and point.
-    pub fn from_point<B: Brush>(layout: &Layout<B>, mut x: f32, y: f32) -> Self {
+    pub fn from_point<B: Brush>(layout: &Layout<B>, x: f32, y: f32) -> Self {
+        // Convert the point to offsets along the inline and block axes.
+        let (x, y) = match layout.data.writing_mode {
+            WritingMode::HorizontalTb => (x, y),
+            WritingMode::VerticalLr => (y, x),
+            WritingMode::VerticalRl => (y, layout.data.height - x),
+        };
+        Self::from_line_point(layout, x, y)
+    }
+
+    /// Creates a new cursor from an offset along the baseline and an offset
+    /// along the block axis.
+    fn from_line_point<B: Brush>(layout: &Layout<B>, mut x: f32, y: f32) -> Self {
         let mut result = Self {
             is_inside: x >= 0. && y >= 0.,
             ..Default::default()
@@ -38,7 +59,7 @@ impl Cursor {
         let last_line = layout.data.lines.len().saturating_sub(1);
         for (line_index, line) in layout.lines().enumerate() {
             let line_metrics = line.metrics();
-            if y > line_metrics.baseline + line_metrics.descent + line_metrics.leading * 0.5 {
+            if y > line_metrics.max_coord {
                 if line_index != last_line {
                     continue;
                 }
@@ -50,8 +71,29 @@ impl Cursor {
             result.baseline = line_metrics.baseline;
             result.path.line_index = line_index;
             let mut last_edge = line_metrics.offset;
-            for (run_index, run) in line.runs().enumerate() {
+            let mut run_index = 0;
+            for item in line.item_data() {
+                if item.kind == LayoutItemKind::InlineBox {
+                    // Both edges of an inline box map to the same text
+                    // position, so snap to the start of the next cluster.
+                    last_edge += item.advance;
+                    x = x.max(last_edge);
+                    continue;
+                }
+                let run = Run::new(&layout.data, &layout.data.runs[item.index], Some(item));
                 result.path.run_index = run_index;
+                run_index += 1;
+                if item.is_ellipsis {
+                    // The ellipsis replaces truncated text, so snap to the
+                    // start of the next cluster as with inline boxes.
+                    last_edge += item.advance;
+                    x = x.max(last_edge);
+                    continue;
+                }
+                if run.is_rtl() && run.is_hyphenated() {
+                    // The hyphen precedes the clusters of a right-to-left run.
+                    last_edge += run.data().hyphen_advance;
+                }
                 let cluster_range = run.data().cluster_range.clone();
                 for (cluster_index, cluster) in run.visual_clusters().enumerate() {
                     let range = cluster.text_range();
@@ -63,23 +105,30 @@ impl Cursor {
                     } else {
                         cluster_index
                     };
+                    // Text positions at the left and right edges of the
+                    // cluster.
+                    let (left, right) = if result.is_rtl {
+                        (range.end, range.start)
+                    } else {
+                        (range.start, range.end)
+                    };
                     if x >= last_edge {
                         let advance = cluster.advance();
                         let next_edge = last_edge + advance;
                         result.offset = next_edge;
-                        result.insert_point = range.end;
+                        result.insert_point = right;
                         if x >= next_edge {
                             last_edge = next_edge;
                             continue;
                         }
                         result.advance = advance;
                         if x <= (last_edge + next_edge) * 0.5 {
-                            result.insert_point = range.start;
+                            result.insert_point = left;
                             result.offset = last_edge;
                         }
                     } else {
                         result.is_inside = false;
-                        result.insert_point = range.start;
+                        result.insert_point = left;
                         result.offset = line_metrics.offset;
                     }
                     return result;
@@ -115,13 +164,24 @@ impl Cursor {
             }
             let mut last_edge = line_metrics.offset;
             result.offset = last_edge;
-            for (run_index, run) in line.runs().enumerate() {
+            let mut run_index = 0;
+            for item in line.item_data() {
+                if item.kind == LayoutItemKind::InlineBox {
+                    last_edge += item.advance;
+                    result.offset = last_edge;
+                    continue;
+                }
+                let run = Run::new(&layout.data, &layout.data.runs[item.index], Some(item));
                 result.path.run_index = run_index;
+                run_index += 1;
                 if !run.text_range().contains(&position) {
                     last_edge += run.advance();
                     result.offset = last_edge;
                     continue;
                 }
+                if run.is_rtl() && run.is_hyphenated() {
+                    last_edge += run.data().hyphen_advance;
+                }
                 let cluster_range = run.data().cluster_range.clone();
                 for (cluster_index, cluster) in run.visual_clusters().enumerate() {
                     let range = cluster.text_range();
@@ -136,7 +196,9 @@ impl Cursor {
                     };
                     let advance = cluster.advance();
                     if range.contains(&position) {
-                        if !is_leading || !result.is_inside {
+                        // The leading edge of a right-to-left cluster is on
+                        // its right side.
+                        if (is_leading && result.is_inside) == result.is_rtl {
                             result.offset += advance;
                         }
                         result.insert_point = if is_leading { range.start } else { range.end };
@@ -165,6 +227,350 @@ impl Cursor {
     pub fn is_trailing(&self) -> bool {
         self.text_end == self.insert_point
     }
+
+    /// Creates a new cursor for the specified layout and text position,
+    /// using the affinity to choose between the locations that the position
+    /// maps to.
+    pub fn from_index<B: Brush>(layout: &Layout<B>, index: usize, affinity: Affinity) -> Self {
+        let text_len = layout.data.text_len;
+        let follows_newline = index != 0
+            && cluster_at(layout, index - 1)
+                .is_some_and(|(_, cluster)| cluster.info.whitespace() == Whitespace::Newline);
+        if index < text_len && (affinity == Affinity::Downstream || follows_newline) {
+            Self::from_position(layout, index, true)
+        } else if index == 0 {
+            Self::from_position(layout, 0, true)
+        } else {
+            Self::from_position(layout, index.min(text_len) - 1, false)
+        }
+    }
+
+    /// Creates a new cursor at the start of the cursor range of the text
+    /// being composed by an input method.
+    ///
+    /// Returns `None` if the layout has no composed text or the input method
+    /// has hidden its cursor.
+    pub fn from_preedit<B: Brush>(layout: &Layout<B>) -> Option<Self> {
+        let cursor = layout.data.preedit_cursor.as_ref()?;
+        Some(Self::from_index(layout, cursor.start, Affinity::Downstream))
+    }
+
+    /// Returns the bounding box of the text being composed by an input
+    /// method, or `None` if there is no composed text.
+    ///
+    /// When the input method has a visible cursor with a non-empty range,
+    /// only that range is covered. This is suitable for positioning the
+    /// candidate window of the input method.
+    pub fn preedit_area<B: Brush>(layout: &Layout<B>) -> Option<Rect> {
+        let preedit = layout.data.preedit.clone()?;
+        let range = match layout.data.preedit_cursor.clone() {
+            Some(cursor) if !cursor.is_empty() => cursor,
+            _ => preedit,
+        };
+        let mut area: Option<Rect> = None;
+        Selection::new(range.start, range.end).geometry_with(layout, |rect| {
+            area = Some(area.map_or(rect, |area| area.union(rect)));
+        });
+        area.or_else(|| {
+            // Empty composed text is represented by a caret.
+            Self::from_index(layout, range.start, Affinity::Downstream).geometry(layout, 1.)
+        })
+    }
+
+    /// Returns a rectangle of the specified size along the baseline that
+    /// represents the cursor as a caret spanning the height of its line, or
+    /// `None` if the layout has no lines.
+    pub fn geometry<B: Brush>(&self, layout: &Layout<B>, size: f32) -> Option<Rect> {
+        let metrics = *layout.get(self.path.line_index)?.metrics();
+        let (top, bottom) = (metrics.min_coord, metrics.max_coord);
+        let mut result = None;
+        let mut rect = SelectionRect::new(&layout.data, top, bottom);
+        let mut f = |r| result = Some(r);
+        rect.push(self.offset, self.offset + size, true, &mut f);
+        rect.flush(&mut f);
+        result
+    }
+
+    /// Returns the affinity of the cursor.
+    pub fn affinity(&self) -> Affinity {
+        if self.is_leading() {
+            Affinity::Downstream
+        } else {
+            Affinity::Upstream
+        }
+    }
+
+    /// Returns a cursor at the start of the next grapheme cluster in logical
+    /// order.
+    pub fn next_logical<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        let index = match cluster_at(layout, self.insert_point) {
+            Some((range, _)) => range.end,
+            None => layout.data.text_len,
+        };
+        Self::from_index(layout, index, Affinity::Downstream)
+    }
+
+    /// Returns a cursor at the start of the previous grapheme cluster in
+    /// logical order.
+    pub fn previous_logical<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        let index = match self.insert_point.checked_sub(1) {
+            Some(index) => cluster_at(layout, index)
+                .map(|(range, _)| range.start)
+                .unwrap_or(index),
+            None => 0,
+        };
+        Self::from_index(layout, index, Affinity::Downstream)
+    }
+
+    /// Returns a cursor one cluster to the right of this cursor in visual
+    /// order, moving to an adjacent line at the edge of the current line.
+    pub fn next_visual<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        self.move_visual(layout, true)
+    }
+
+    /// Returns a cursor one cluster to the left of this cursor in visual
+    /// order, moving to an adjacent line at the edge of the current line.
+    pub fn previous_visual<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        self.move_visual(layout, false)
+    }
+
+    /// Returns a cursor at the end of the current or next word in logical
+    /// order.
+    pub fn next_word<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        let mut index = layout.data.text_len;
+        let mut in_word = false;
+        for (range, cluster) in logical_clusters(layout) {
+            if range.start < self.insert_point {
+                continue;
+            }
+            if in_word && range.start > self.insert_point && cluster.is_word_boundary() {
+                index = range.start;
+                break;
+            }
+            in_word |= !cluster.info.is_whitespace();
+        }
+        Self::from_index(layout, index, Affinity::Upstream)
+    }
+
+    /// Returns a cursor at the start of the current or previous word in
+    /// logical order.
+    pub fn previous_word<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        let mut index = 0;
+        let mut in_word = false;
+        let clusters: Vec<_> = logical_clusters(layout)
+            .take_while(|(range, _)| range.start < self.insert_point)
+            .collect();
+        for (range, cluster) in clusters.into_iter().rev() {
+            in_word |= !cluster.info.is_whitespace();
+            if in_word && cluster.is_word_boundary() {
+                index = range.start;
+                break;
+            }
+        }
+        Self::from_index(layout, index, Affinity::Downstream)
+    }
+
+    /// Returns a cursor at the logical start of the current line.
+    pub fn line_start<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        match layout.get(self.path.line_index) {
+            Some(line) => Self::from_index(layout, line.text_range().start, Affinity::Downstream),
+            None => *self,
+        }
+    }
+
+    /// Returns a cursor at the logical end of the current line, preceding
+    /// any trailing newline.
+    pub fn line_end<B: Brush>(&self, layout: &Layout<B>) -> Self {
+        let Some(line) = layout.get(self.path.line_index) else {
+            return *self;
+        };
+        let mut index = line.text_range().end;
+        if let Some((range, cluster)) = index.checked_sub(1).and_then(|i| cluster_at(layout, i)) {
+            if cluster.info.whitespace() == Whitespace::Newline {
+                index = range.start;
+            }
+        }
+        Self::from_index(layout, index, Affinity::Upstream)
+    }
+
+    /// Returns a cursor on the next line at the position nearest to the
+    /// preferred offset along the baseline.
+    ///
+    /// If `preferred_offset` is `None`, it is set to the offset of this
+    /// cursor. Callers should retain the value across consecutive vertical
+    /// movements and reset it to `None` when the cursor is moved in any
+    /// other way, so that moving through shorter lines does not lose the
+    /// original position.
+    pub fn next_line<B: Brush>(
+        &self,
+        layout: &Layout<B>,
+        preferred_offset: &mut Option<f32>,
+    ) -> Self {
+        self.move_lines(layout, 1, preferred_offset)
+    }
+
+    /// Returns a cursor on the previous line at the position nearest to the
+    /// preferred offset along the baseline.
+    ///
+    /// See [`next_line`](Self::next_line) for the treatment of
+    /// `preferred_offset`.
+    pub fn previous_line<B: Brush>(
+        &self,
+        layout: &Layout<B>,
+        preferred_offset: &mut Option<f32>,
+    ) -> Self {
+        self.move_lines(layout, -1, preferred_offset)
+    }
+
+    /// Returns a cursor moved by the specified number of lines, at the
+    /// position nearest to the preferred offset along the baseline.
+    ///
+    /// Moving beyond the first or last line places the cursor at the start
+    /// or end of the text, respectively.
+    pub fn move_lines<B: Brush>(
+        &self,
+        layout: &Layout<B>,
+        delta: isize,
+        preferred_offset: &mut Option<f32>,
+    ) -> Self {
+        let offset = *preferred_offset.get_or_insert(self.offset);
+        let target = self.path.line_index as isize + delta;
+        if target < 0 {
+            return Self::from_index(layout, 0, Affinity::Downstream);
+        }
+        let Some(line) = layout.get(target as usize) else {
+            return Self::from_index(layout, layout.data.text_len, Affinity::Downstream);
+        };
+        let mut result = Self::from_line_point(layout, offset, line.metrics().baseline);
+        if result.path.line_index == target as usize
+            && result.is_trailing()
+            && line.text_range().end == result.insert_point
+            && cluster_at(layout, result.text_start)
+                .is_some_and(|(_, cluster)| cluster.info.whitespace() == Whitespace::Newline)
+        {
+            // Never place the cursor after a newline.
+            result = Self::from_position(layout, result.text_start, true);
+        }
+        result
+    }
+
+    fn move_visual<B: Brush>(&self, layout: &Layout<B>, right: bool) -> Self {
+        let line_index = self.path.line_index;
+        let clusters = visual_line_clusters(layout, line_index);
+        let is_rtl_paragraph = layout.get(line_index).is_some_and(|line| line.is_rtl());
+        // Visual boundary between clusters at which the cursor is located.
+        let boundary = match clusters
+            .iter()
+            .position(|(range, _)| range.start == self.text_start && range.end == self.text_end)
+        {
+            Some(index) => {
+                let (_, is_rtl) = clusters[index];
+                if is_rtl != self.is_leading() {
+                    index
+                } else {
+                    index + 1
+                }
+            }
+            // The cursor is on a newline, which follows the content of the
+            // line in the paragraph direction.
+            None if is_rtl_paragraph => 0,
+            None => clusters.len(),
+        };
+        // Each cluster is described by the text position and leading edge
+        // state of its right and left edges.
+        let right_edge = |(range, is_rtl): &(Range<usize>, bool)| (range.start, *is_rtl);
+        let left_edge = |(range, is_rtl): &(Range<usize>, bool)| (range.start, !*is_rtl);
+        let target = if right && boundary < clusters.len() {
+            Some(right_edge(&clusters[boundary]))
+        } else if !right && boundary > 0 {
+            Some(left_edge(&clusters[boundary - 1]))
+        } else {
+            // Move to the adjacent line in the direction of travel.
+            let forward = right != is_rtl_paragraph;
+            let adjacent = if forward {
+                line_index.checked_add(1)
+            } else {
+                line_index.checked_sub(1)
+            };
+            let Some(adjacent) = adjacent.filter(|index| *index < layout.len()) else {
+                return *self;
+            };
+            let clusters = visual_line_clusters(layout, adjacent);
+            let edge = if right {
+                clusters.first().map(left_edge)
+            } else {
+                clusters.last().map(right_edge)
+            };
+            match edge {
+                Some(edge) => Some(edge),
+                None => {
+                    let line = layout.get(adjacent).unwrap();
+                    return Self::from_index(layout, line.text_range().start, Affinity::Downstream);
+                }
+            }
+        };
+        match target {
+            Some((position, is_leading)) => Self::from_position(layout, position, is_leading),
+            None => *self,
+        }
+    }
+}
+
+/// Side of a text position with which a cursor is associated when the
+/// position maps to more than one location, such as at a soft line break or
+/// a change in direction.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum Affinity {
+    /// The cursor is associated with the character following the position.
+    #[default]
+    Downstream,
+    /// The cursor is associated with the character preceding the position.
+    Upstream,
+}
+
+/// Returns an iterator over the text range and data of each cluster
+/// in the layout in logical order.
+fn logical_clusters<B: Brush>(
+    layout: &Layout<B>,
+) -> impl Iterator<Item = (Range<usize>, &ClusterData)> + '_ {
+    let data = &layout.data;
+    data.runs.iter().flat_map(move |run| {
+        data.clusters[run.cluster_range.clone()]
+            .iter()
+            .map(move |cluster| (cluster.text_range(run), cluster))
+    })
+}
+
+/// Returns the text range and cluster containing the specified position.
+fn cluster_at<B: Brush>(layout: &Layout<B>, index: usize) -> Option<(Range<usize>, &ClusterData)> {
+    let data = &layout.data;
+    let run = data
+        .runs
+        .iter()
+        .find(|run| run.text_range.contains(&index))?;
+    data.clusters[run.cluster_range.clone()]
+        .iter()
+        .map(|cluster| (cluster.text_range(run), cluster))
+        .find(|(range, _)| range.contains(&index))
+}
+
+/// Returns the text range and direction of each cluster on a line in visual
+/// order, excluding newlines.
+fn visual_line_clusters<B: Brush>(
+    layout: &Layout<B>,
+    line_index: usize,
+) -> Vec<(Range<usize>, bool)> {
+    let mut clusters = Vec::new();
+    if let Some(line) = layout.get(line_index) {
+        for run in line.runs() {
+            for cluster in run.visual_clusters() {
+                if !cluster.is_newline() {
+                    clusters.push((cluster.text_range(), run.is_rtl()));
+                }
+            }
+        }
+    }
+    clusters
 }
 
 /// Index based path to a cluster.
@@ -194,3 +600,487 @@ impl CursorPath {
         self.run(layout)?.get(self.cluster_index)
     }
 }
+
+/// Range of text defined by an anchor and a focus position.
+///
+/// The anchor is the fixed end of the selection and the focus is the end
+/// that moves as the selection is extended. Both are byte offsets into the
+/// text of a layout.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Selection {
+    /// Fixed end of the selection.
+    pub anchor: usize,
+    /// Moving end of the selection.
+    pub focus: usize,
+}
+
+impl Selection {
+    /// Creates a new selection from the specified anchor and focus.
+    pub fn new(anchor: usize, focus: usize) -> Self {
+        Self { anchor, focus }
+    }
+
+    /// Creates a collapsed selection at the specified position.
+    pub fn collapsed(position: usize) -> Self {
+        Self::new(position, position)
+    }
+
+    /// Returns true if the selection is empty.
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+
+    /// Returns the range of text covered by the selection.
+    pub fn text_range(&self) -> Range<usize> {
+        self.anchor.min(self.focus)..self.anchor.max(self.focus)
+    }
+
+    /// Creates a selection covering the word that contains the specified
+    /// text position. If the position is in a sequence of whitespace, the
+    /// whitespace is selected instead.
+    pub fn word_at<B: Brush>(layout: &Layout<B>, index: usize) -> Self {
+        let mut start = 0;
+        let mut end = layout.data.text_len;
+        let mut found = false;
+        for (range, cluster) in logical_clusters(layout) {
+            if found {
+                if cluster.is_word_boundary() {
+                    end = range.start;
+                    break;
+                }
+            } else if cluster.is_word_boundary() || range.start == 0 {
+                start = range.start;
+            }
+            found |= range.contains(&index);
+        }
+        Self::new(start, end)
+    }
+
+    /// Returns the rectangles that cover the selected text in the
+    /// specified layout.
+    pub fn geometry<B: Brush>(&self, layout: &Layout<B>) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        self.geometry_with(layout, |rect| rects.push(rect));
+        rects
+    }
+
+    /// Invokes `f` with each rectangle that covers the selected text in the
+    /// specified layout.
+    ///
+    /// Each line produces one rectangle for every visually contiguous
+    /// sequence of selected clusters, so a line with mixed directions may
+    /// produce several. Rectangles span the full height of the line. A
+    /// selected newline is extended to a quarter of the font size, roughly
+    /// the width of a space, so that selected line endings remain visible.
+    pub fn geometry_with<B: Brush>(&self, layout: &Layout<B>, mut f: impl FnMut(Rect)) {
+        let range = self.text_range();
+        if range.is_empty() {
+            return;
+        }
+        let data = &layout.data;
+        for line in layout.lines() {
+            let line_range = line.text_range();
+            if line_range.start >= range.end || line_range.end <= range.start {
+                continue;
+            }
+            let metrics = line.metrics();
+            let mut rect = SelectionRect::new(data, metrics.min_coord, metrics.max_coord);
+            let mut x = metrics.offset;
+            for item in line.item_data() {
+                if item.kind == LayoutItemKind::InlineBox {
+                    let is_selected = range.contains(&item.text_range.start);
+                    rect.push(x, x + item.advance, is_selected, &mut f);
+                    x += item.advance;
+                    continue;
+                }
+                if item.is_ellipsis {
+                    rect.push(x, x + item.advance, false, &mut f);
+                    x += item.advance;
+                    continue;
+                }
+                let run = Run::new(data, &data.runs[item.index], Some(item));
+                // The hyphen of a hyphenated run is selected along with the
+                // logically last cluster.
+                let hyphen_advance = if run.is_hyphenated() {
+                    run.data().hyphen_advance
+                } else {
+                    0.
+                };
+                let is_last_selected = item
+                    .cluster_range
+                    .end
+                    .checked_sub(1)
+                    .and_then(|index| data.clusters.get(index))
+                    .is_some_and(|cluster| range.contains(&cluster.text_range(run.data()).start));
+                if run.is_rtl() && hyphen_advance != 0. {
+                    rect.push(x, x + hyphen_advance, is_last_selected, &mut f);
+                    x += hyphen_advance;
+                }
+                for cluster in run.visual_clusters() {
+                    let is_selected = range.contains(&cluster.text_range().start);
+                    let advance = cluster.advance();
+                    let extent = if is_selected && cluster.is_newline() {
+                        advance.max(run.font_size() * 0.25)
+                    } else {
+                        advance
+                    };
+                    rect.push(x, x + extent, is_selected, &mut f);
+                    x += advance;
+                }
+                if !run.is_rtl() && hyphen_advance != 0. {
+                    rect.push(x, x + hyphen_advance, is_last_selected, &mut f);
+                    x += hyphen_advance;
+                }
+            }
+            rect.flush(&mut f);
+        }
+    }
+}
+
+/// Accumulates visually contiguous selected extents on a single line.
+struct SelectionRect<'a, B: Brush> {
+    layout: &'a LayoutData<B>,
+    top: f32,
+    bottom: f32,
+    current: Option<(f32, f32)>,
+}
+
+impl<'a, B: Brush> SelectionRect<'a, B> {
+    fn new(layout: &'a LayoutData<B>, top: f32, bottom: f32) -> Self {
+        Self {
+            layout,
+            top,
+            bottom,
+            current: None,
+        }
+    }
+
+    fn push(&mut self, start: f32, end: f32, is_selected: bool, f: &mut impl FnMut(Rect)) {
+        if !is_selected {
+            self.flush(f);
+            return;
+        }
+        match &mut self.current {
+            Some(current) if nearly_eq(current.1, start) => current.1 = current.1.max(end),
+            _ => {
+                self.flush(f);
+                self.current = Some((start, end));
+            }
+        }
+    }
+
+    fn flush(&mut self, f: &mut impl FnMut(Rect)) {
+        let Some((start, end)) = self.current.take() else {
+            return;
+        };
+        let (top, bottom) = (self.top as f64, self.bottom as f64);
+        let (start, end) = (start as f64, end as f64);
+        f(match self.layout.writing_mode {
+            WritingMode::HorizontalTb => Rect::new(start, top, end, bottom),
+            WritingMode::VerticalLr => Rect::new(top, start, bottom, end),
+            WritingMode::VerticalRl => {
+                let width = self.layout.height as f64;
+                Rect::new(width - bottom, start, width - top, end)
+            }
+        });
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::context::LayoutContext;
+    use crate::testing::{self, build_and_break, Brush, Content};
+
+    /// Hebrew letters alef, bet and gimel, displayed from right to left.
+    const RTL: &str = "\u{5D0}\u{5D1}\u{5D2}";
+
+    fn layout(text: &str) -> Layout<Brush> {
+        build_and_break(text, &Content::default(), None, Alignment::Start)
+    }
+
+    #[test]
+    fn point_in_rtl_run() {
+        let layout = layout(RTL);
+        let hit = |x| {
+            let cursor = Cursor::from_point(&layout, x, 5.);
+            (cursor.insert_point, cursor.offset, cursor.is_inside)
+        };
+        // The leftmost cluster is gimel, whose left edge is its end.
+        assert_eq!(hit(2.), (6, 0., true));
+        assert_eq!(hit(8.), (4, 10., true));
+        assert_eq!(hit(12.), (4, 10., true));
+        assert_eq!(hit(18.), (2, 20., true));
+        assert_eq!(hit(28.), (0, 30., true));
+        // Points beyond the line map to its visual edges.
+        assert_eq!(hit(-5.), (6, 0., false));
+        assert_eq!(hit(100.), (0, 30., false));
+        assert_eq!(Cursor::from_point(&layout, 100., 100.).insert_point, 0);
+    }
+
+    #[test]
+    fn position_in_rtl_run() {
+        let layout = layout(RTL);
+        let offset = |position, is_leading| {
+            let cursor = Cursor::from_position(&layout, position, is_leading);
+            assert!(cursor.is_rtl);
+            cursor.offset
+        };
+        // The leading edge of a right-to-left cluster is on its right side.
+        assert_eq!(offset(0, true), 30.);
+        assert_eq!(offset(0, false), 20.);
+        assert_eq!(offset(2, true), 20.);
+        assert_eq!(offset(4, true), 10.);
+        assert_eq!(offset(4, false), 0.);
+    }
+
+    #[test]
+    fn point_in_mixed_line() {
+        // "ab " followed by alef and bet, displayed as "ab " then bet, alef.
+        let text = "ab \u{5D0}\u{5D1}";
+        let layout = layout(text);
+        let hit = |x| Cursor::from_point(&layout, x, 5.).insert_point;
+        assert_eq!(hit(2.), 0);
+        assert_eq!(hit(18.), 2);
+        assert_eq!(hit(32.), 7);
+        assert_eq!(hit(38.), 5);
+        assert_eq!(hit(42.), 5);
+        assert_eq!(hit(48.), 3);
+        assert_eq!(Cursor::from_position(&layout, 3, true).offset, 50.);
+        assert_eq!(Cursor::from_position(&layout, 5, true).offset, 40.);
+    }
+
+    /// Returns the line, insert point and offset of each cursor reached by
+    /// repeatedly applying `step`, starting with `cursor`, until the cursor
+    /// no longer moves.
+    fn walk(
+        layout: &Layout<Brush>,
+        mut cursor: Cursor,
+        step: impl Fn(&Cursor, &Layout<Brush>) -> Cursor,
+    ) -> Vec<(usize, usize, f32)> {
+        let mut positions = vec![(cursor.path.line_index, cursor.insert_point, cursor.offset)];
+        loop {
+            let next = step(&cursor, layout);
+            let position = (next.path.line_index, next.insert_point, next.offset);
+            if position == *positions.last().unwrap() {
+                return positions;
+            }
+            positions.push(position);
+            cursor = next;
+        }
+    }
+
+    #[test]
+    fn visual_movement_in_mixed_line() {
+        // Displayed as "ab " followed by bet and alef.
+        let layout = layout("ab \u{5D0}\u{5D1}");
+        let start = Cursor::from_index(&layout, 0, Affinity::Downstream);
+        // The cursor moves one cluster at a time in the direction of
+        // travel, whatever the direction of the run.
+        assert_eq!(
+            walk(&layout, start, Cursor::next_visual),
+            [
+                (0, 0, 0.),
+                (0, 1, 10.),
+                (0, 2, 20.),
+                (0, 3, 30.),
+                (0, 5, 40.),
+                (0, 3, 50.)
+            ]
+        );
+        let end = Cursor::from_point(&layout, 100., 5.);
+        assert_eq!(
+            walk(&layout, end, Cursor::previous_visual),
+            [
+                (0, 3, 50.),
+                (0, 5, 40.),
+                (0, 7, 30.),
+                (0, 2, 20.),
+                (0, 1, 10.),
+                (0, 0, 0.)
+            ]
+        );
+    }
+
+    #[test]
+    fn visual_movement_across_rtl_lines() {
+        let text = "\u{5D0}\u{5D1} \u{5D2}\u{5D3}";
+        let layout = build_and_break(text, &Content::default(), Some(35.), Alignment::Start);
+        // Moving left from the right edge of a line continues at the right
+        // edge of the next line.
+        let start = Cursor::from_index(&layout, 0, Affinity::Downstream);
+        let left = [
+            (0, 0, 35.),
+            (0, 2, 25.),
+            (0, 4, 15.),
+            (0, 5, 5.),
+            (1, 5, 35.),
+            (1, 7, 25.),
+            (1, 9, 15.),
+        ];
+        assert_eq!(walk(&layout, start, Cursor::previous_visual), left);
+        let end = Cursor::from_index(&layout, text.len(), Affinity::Upstream);
+        let right: Vec<_> = left.into_iter().rev().collect();
+        assert_eq!(walk(&layout, end, Cursor::next_visual), right);
+    }
+
+    #[test]
+    fn word_movement_through_ligature() {
+        // "fi" at the start of "five" forms a ligature in the test font.
+        let layout = layout("one two three\nfour five");
+        let at = |index| Cursor::from_index(&layout, index, Affinity::Downstream);
+        let ends: Vec<_> = walk(&layout, at(0), Cursor::next_word)
+            .into_iter()
+            .map(|(_, index, _)| index)
+            .collect();
+        assert_eq!(ends, [0, 3, 7, 13, 18, 23]);
+        let starts: Vec<_> = walk(&layout, at(23), Cursor::previous_word)
+            .into_iter()
+            .map(|(_, index, _)| index)
+            .collect();
+        assert_eq!(starts, [23, 19, 14, 8, 4, 0]);
+    }
+
+    #[test]
+    fn line_end_before_newline() {
+        let layout = layout("ab\ncd");
+        let cursor = Cursor::from_index(&layout, 1, Affinity::Downstream);
+        assert_eq!(cursor.line_end(&layout).insert_point, 2);
+        assert_eq!(cursor.line_start(&layout).insert_point, 0);
+        let cursor = Cursor::from_index(&layout, 4, Affinity::Downstream);
+        assert_eq!(cursor.line_end(&layout).insert_point, 5);
+        assert_eq!(cursor.line_start(&layout).insert_point, 3);
+    }
+
+    #[test]
+    fn vertical_movement_through_shorter_line() {
+        let layout = layout("abcdef\nab\nabcdef");
+        let position =
+            |cursor: Cursor| (cursor.path.line_index, cursor.insert_point, cursor.offset);
+        let mut preferred_offset = None;
+        let cursor = Cursor::from_index(&layout, 5, Affinity::Downstream);
+        // The shorter line places the cursor before its newline, and the
+        // next line returns to the preferred offset.
+        let down = cursor.next_line(&layout, &mut preferred_offset);
+        assert_eq!(position(down), (1, 9, 20.));
+        assert_eq!(preferred_offset, Some(50.));
+        let down = down.next_line(&layout, &mut preferred_offset);
+        assert_eq!(position(down), (2, 15, 50.));
+        let up = down.previous_line(&layout, &mut preferred_offset);
+        assert_eq!(position(up), (1, 9, 20.));
+        let up = up.previous_line(&layout, &mut preferred_offset);
+        assert_eq!(position(up), (0, 5, 50.));
+        // Moving beyond the last line places the cursor at the end.
+        let end = down.next_line(&layout, &mut preferred_offset);
+        assert_eq!(end.insert_point, 16);
+    }
+
+    /// Returns the selection rectangles as `(x0, y0, x1, y1)`.
+    fn rects(layout: &Layout<Brush>, anchor: usize, focus: usize) -> Vec<(f64, f64, f64, f64)> {
+        Selection::new(anchor, focus)
+            .geometry(layout)
+            .into_iter()
+            .map(|rect| (rect.x0, rect.y0, rect.x1, rect.y1))
+            .collect()
+    }
+
+    #[test]
+    fn selection_geometry() {
+        let layout = layout("abcdef");
+        assert_eq!(rects(&layout, 1, 4), [(10., 0., 40., 10.)]);
+        // The anchor may follow the focus.
+        assert_eq!(rects(&layout, 4, 1), [(10., 0., 40., 10.)]);
+        assert!(rects(&layout, 2, 2).is_empty());
+    }
+
+    #[test]
+    fn selection_geometry_across_lines() {
+        let layout = build_and_break(
+            "ab cd\nef",
+            &Content::default(),
+            Some(30.),
+            Alignment::Start,
+        );
+        assert_eq!(
+            rects(&layout, 1, 7),
+            [
+                (10., 0., 30., 10.),
+                (0., 10., 22.5, 20.),
+                (0., 20., 10., 30.)
+            ]
+        );
+    }
+
+    #[test]
+    fn selection_geometry_bidi() {
+        // Displayed as "ab " followed by bet and alef.
+        let layout = layout("ab \u{5D0}\u{5D1}");
+        assert_eq!(
+            rects(&layout, 1, 5),
+            [(10., 0., 30., 10.), (40., 0., 50., 10.)]
+        );
+    }
+
+    #[test]
+    fn word_at() {
+        let layout = layout("ab cd ef");
+        assert_eq!(Selection::word_at(&layout, 4).text_range(), 3..5);
+        assert_eq!(Selection::word_at(&layout, 0).text_range(), 0..2);
+        // Words may begin with a ligature, such as "fi" in the test font.
+        let layout = self::layout("one five office");
+        for index in 4..8 {
+            assert_eq!(Selection::word_at(&layout, index).text_range(), 4..8);
+        }
+        assert_eq!(Selection::word_at(&layout, 10).text_range(), 9..15);
+    }
+
+    fn preedit_layout(
+        text: &str,
+        preedit: Range<usize>,
+        cursor: Option<Range<usize>>,
+    ) -> Layout<Brush> {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut builder = testing::ranged_builder(&mut fcx, &mut lcx, text, &Content::default());
+        builder.set_preedit(preedit, cursor);
+        let mut layout = Layout::default();
+        builder.build_into(&mut layout);
+        layout.break_all_lines(None, Alignment::Start);
+        layout
+    }
+
+    fn coords(rect: Option<Rect>) -> Option<(f64, f64, f64, f64)> {
+        rect.map(|rect| (rect.x0, rect.y0, rect.x1, rect.y1))
+    }
+
+    #[test]
+    fn preedit_area() {
+        // A non-empty cursor range is covered in place of the composed text.
+        let layout = preedit_layout("abcdef", 2..5, Some(3..4));
+        assert_eq!(
+            coords(Cursor::preedit_area(&layout)),
+            Some((30., 0., 40., 10.))
+        );
+        let cursor = Cursor::from_preedit(&layout).unwrap();
+        assert_eq!((cursor.insert_point, cursor.offset), (3, 30.));
+        let layout = preedit_layout("abcdef", 2..5, Some(3..3));
+        assert_eq!(
+            coords(Cursor::preedit_area(&layout)),
+            Some((20., 0., 50., 10.))
+        );
+        // A hidden cursor still covers the composed text.
+        let layout = preedit_layout("abcdef", 2..5, None);
+        assert_eq!(
+            coords(Cursor::preedit_area(&layout)),
+            Some((20., 0., 50., 10.))
+        );
+        assert!(Cursor::from_preedit(&layout).is_none());
+        // Empty composed text is represented by a caret.
+        let layout = preedit_layout("abcdef", 2..2, None);
+        assert_eq!(
+            coords(Cursor::preedit_area(&layout)),
+            Some((20., 0., 21., 10.))
+        );
+        assert_eq!(Cursor::preedit_area(&self::layout("abc")), None);
+    }
+}
@@ -1,17 +1,18 @@
 // Copyright 2021 the Parley Authors
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
-use crate::layout::{Alignment, Glyph, LineMetrics, RunMetrics, Style};
-use crate::style::Brush;
+use crate::layout::{Alignment, Glyph, InlineBox, LineMetrics, RunMetrics, Style, WritingMode};
+use crate::style::{Brush, LineHeight, TabAlignment, TabSize, TabStop, TextJustify};
 use crate::util::*;
 use crate::Font;
 use core::ops::Range;
 use swash::shape::Shaper;
 use swash::text::cluster::{Boundary, ClusterInfo};
+use swash::text::{Codepoint, JoiningType, Script};
 use swash::Synthesis;
 
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 
 #[derive(Copy, Clone)]
 pub struct ClusterData {
@@ -32,6 +33,18 @@ impl ClusterData {
     pub const LIGATURE_START: u16 = 1;
     pub const LIGATURE_COMPONENT: u16 = 2;
     pub const DIVERGENT_STYLES: u16 = 4;
+    /// A hyphen is displayed after the cluster if a line is broken
+    /// immediately following it.
+    pub const HYPHEN: u16 = 8;
+    /// The cluster is a tab character.
+    pub const TAB: u16 = 16;
+    /// The cluster belongs to a script that does not separate words with
+    /// spaces.
+    pub const UNSPACED: u16 = 32;
+    /// A kashida may be inserted between the cluster and the next one.
+    pub const KASHIDA_POINT: u16 = 64;
+    /// A kashida is inserted after the cluster by justification.
+    pub const KASHIDA: u16 = 128;
 
     pub fn is_ligature_start(self) -> bool {
         self.flags & Self::LIGATURE_START != 0
@@ -41,10 +54,37 @@ impl ClusterData {
         self.flags & Self::LIGATURE_COMPONENT != 0
     }
 
+    /// Returns true if a word begins at the cluster. Ligature components
+    /// share the information of the ligature start, so they never begin a
+    /// word.
+    pub fn is_word_boundary(self) -> bool {
+        !self.is_ligature_component() && self.info.is_boundary()
+    }
+
     pub fn has_divergent_styles(self) -> bool {
         self.flags & Self::DIVERGENT_STYLES != 0
     }
 
+    pub fn is_hyphenation_point(self) -> bool {
+        self.flags & Self::HYPHEN != 0
+    }
+
+    pub fn is_tab(self) -> bool {
+        self.flags & Self::TAB != 0
+    }
+
+    pub fn is_unspaced(self) -> bool {
+        self.flags & Self::UNSPACED != 0
+    }
+
+    pub fn is_kashida_point(self) -> bool {
+        self.flags & Self::KASHIDA_POINT != 0
+    }
+
+    pub fn has_kashida(self) -> bool {
+        self.flags & Self::KASHIDA != 0
+    }
+
     pub fn text_range(self, run: &RunData) -> Range<usize> {
         let start = run.text_range.start + self.text_offset as usize;
         start..start + self.text_len as usize
@@ -79,6 +119,32 @@ pub struct RunData {
     pub letter_spacing: f32,
     /// Total advance of the run.
     pub advance: f32,
+    /// Glyph identifier for the hyphen displayed at hyphenation points.
+    pub hyphen_id: u16,
+    /// Advance of the hyphen glyph.
+    pub hyphen_advance: f32,
+    /// Glyph identifier for the tatweel inserted by kashida justification.
+    pub tatweel_id: u16,
+    /// Advance of the tatweel glyph.
+    pub tatweel_advance: f32,
+}
+
+/// Kind of an item in the layout.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LayoutItemKind {
+    TextRun,
+    InlineBox,
+}
+
+/// A text run or inline box in logical order.
+#[derive(Copy, Clone)]
+pub struct LayoutItem {
+    /// Whether the item is a run or an inline box.
+    pub kind: LayoutItemKind,
+    /// Index of the run or inline box.
+    pub index: usize,
+    /// Bidi level for the item.
+    pub bidi_level: u8,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -95,22 +161,67 @@ impl Default for BreakReason {
     }
 }
 
+/// Tab character in the layout.
+#[derive(Clone, Debug)]
+pub struct TabData {
+    /// Index of the cluster of the tab.
+    pub cluster: usize,
+    /// Distance between the default tab stops.
+    pub interval: f32,
+    /// Range of the explicit tab stops in the layout.
+    pub stops: Range<usize>,
+    /// End of the range of clusters following the tab that are aligned to
+    /// its stop.
+    pub end: usize,
+    /// Range of the positions of decimal separators in the layout.
+    pub decimals: Range<usize>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ParagraphData {
+    /// Range of the source text, including the paragraph separator.
+    pub text_range: Range<usize>,
+    /// Base bidi level.
+    pub base_level: u8,
+    /// Alignment that overrides the alignment of the layout.
+    pub alignment: Option<Alignment>,
+    /// Alignment of the last line.
+    pub alignment_last: Option<Alignment>,
+    /// Justification method.
+    pub justify: TextJustify,
+    /// Indentation of the first line.
+    pub text_indent: f32,
+    /// Indentation of the remaining lines.
+    pub hanging_indent: f32,
+    /// Step to which the height of each line is rounded up.
+    pub line_height_step: f32,
+}
+
 #[derive(Clone, Default)]
 pub struct LineData {
     /// Range of the source text.
     pub text_range: Range<usize>,
-    /// Range of line runs.
+    /// Range of line items.
+    pub item_range: Range<usize>,
+    /// Range of the indices of the line items that are text runs in
+    /// [`LayoutData::line_runs`].
     pub run_range: Range<usize>,
     /// Metrics for the line.
     pub metrics: LineMetrics,
-    /// The cause of the line break.
-    pub break_reason: BreakReason,
     /// Alignment.
     pub alignment: Alignment,
-    /// Maximum advance for the line.
+    /// Justification method.
+    pub justify: TextJustify,
+    /// Advance added at each justification opportunity on the line.
+    pub justification: f32,
+    /// Base bidi level of the paragraph containing the line.
+    pub base_level: u8,
+    /// Maximum advance for the line, excluding the indentation.
     pub max_advance: f32,
-    /// Number of justified clusters on the line.
-    pub num_spaces: usize,
+    /// Indentation of the line from the start edge of the paragraph.
+    pub indent: f32,
+    /// Step to which the height of the line is rounded up.
+    pub line_height_step: f32,
 }
 
 impl LineData {
@@ -119,69 +230,171 @@ impl LineData {
     }
 }
 
-#[derive(Clone, Default)]
-pub struct LineRunData {
-    /// Index of the original run.
-    pub run_index: usize,
-    /// Bidi level for the run.
+#[derive(Clone)]
+pub struct LineItemData {
+    /// Whether the item is a run or an inline box.
+    pub kind: LayoutItemKind,
+    /// Index of the original run or inline box.
+    pub index: usize,
+    /// Bidi level for the item.
     pub bidi_level: u8,
-    /// True if the run is composed entirely of whitespace.
+    /// True if the item is composed entirely of whitespace.
     pub is_whitespace: bool,
-    /// True if the run ends in whitespace.
+    /// True if the item ends in whitespace.
     pub has_trailing_whitespace: bool,
     /// Range of the source text.
     pub text_range: Range<usize>,
     /// Range of clusters.
     pub cluster_range: Range<usize>,
-    /// Advance for the run.
+    /// Advance for the item.
     pub advance: f32,
+    /// True if a hyphen is displayed at the end of the item.
+    pub is_hyphenated: bool,
+    /// True if the item displays the ellipsis of a truncated line rather
+    /// than its clusters.
+    pub is_ellipsis: bool,
+    /// Width of each kashida inserted by justification.
+    pub kashida: f32,
 }
 
-impl LineRunData {
-    pub fn compute_line_height<B: Brush>(&self, layout: &LayoutData<B>) -> f32 {
-        let mut line_height = 0f32;
-        let glyph_start = layout.runs[self.run_index].glyph_start;
+impl Default for LineItemData {
+    fn default() -> Self {
+        Self {
+            kind: LayoutItemKind::TextRun,
+            index: 0,
+            bidi_level: 0,
+            is_whitespace: false,
+            has_trailing_whitespace: false,
+            text_range: 0..0,
+            cluster_range: 0..0,
+            advance: 0.,
+            is_hyphenated: false,
+            is_ellipsis: false,
+            kashida: 0.,
+        }
+    }
+}
+
+impl LineItemData {
+    pub fn is_text_run(&self) -> bool {
+        self.kind == LayoutItemKind::TextRun
+    }
+
+    /// Invokes the callback with the line height of each style in the item.
+    pub fn for_each_line_height<B: Brush>(
+        &self,
+        layout: &LayoutData<B>,
+        mut f: impl FnMut(LineHeight),
+    ) {
+        let glyph_start = layout.runs[self.index].glyph_start;
         for cluster in &layout.clusters[self.cluster_range.clone()] {
             if cluster.glyph_len != 0xFF && cluster.has_divergent_styles() {
                 let start = glyph_start + cluster.glyph_offset as usize;
                 let end = start + cluster.glyph_len as usize;
                 for glyph in &layout.glyphs[start..end] {
-                    line_height = line_height.max(layout.styles[glyph.style_index()].line_height);
+                    f(layout.styles[glyph.style_index()].line_height);
                 }
             } else {
-                line_height =
-                    line_height.max(layout.styles[cluster.style_index as usize].line_height);
+                f(layout.styles[cluster.style_index as usize].line_height);
             }
         }
-        line_height
     }
 }
 
+/// Region of a previous layout that is invalidated by an edit of its text.
+///
+/// The region always consists of whole paragraphs, which are shaped and
+/// broken into lines independently of the surrounding text.
+#[derive(Clone, Debug)]
+pub struct Damage {
+    /// Range of the damaged paragraphs in the previous text.
+    pub text_range: Range<usize>,
+    /// End of the damaged paragraphs in the edited text.
+    pub new_text_end: usize,
+    /// Range of the damaged runs in the previous layout.
+    pub runs: Range<usize>,
+    /// Range of the damaged lines in the previous layout, if the surrounding
+    /// lines can be retained.
+    pub lines: Option<Range<usize>>,
+}
+
+/// Lines that must be recomputed after an edit. The lines preceding and
+/// following the gap are retained from the previous layout.
+#[derive(Clone, Default, Debug)]
+pub struct LineGap {
+    /// Index of the first line in the gap.
+    pub line_index: usize,
+    /// Range of items in the gap.
+    pub items: Range<usize>,
+    /// Range of clusters in the gap.
+    pub clusters: Range<usize>,
+}
+
 #[derive(Clone)]
 pub struct LayoutData<B: Brush> {
     pub scale: f32,
+    /// Generation of the font collection that the layout was built with.
+    pub generation: u64,
     pub has_bidi: bool,
-    pub base_level: u8,
+    pub writing_mode: WritingMode,
     pub text_len: usize,
+    /// Advance of the longest line along the inline axis, excluding trailing
+    /// whitespace.
     pub width: f32,
     pub full_width: f32,
+    /// Total size of the lines along the block axis.
     pub height: f32,
     pub fonts: Vec<Font>,
     pub coords: Vec<i16>,
     pub styles: Vec<Style<B>>,
+    /// Ranges of the source text covered by each style.
+    pub style_ranges: Vec<Range<usize>>,
+    /// Paragraphs in logical order.
+    pub paragraphs: Vec<ParagraphData>,
     pub runs: Vec<RunData>,
+    pub inline_boxes: Vec<InlineBox>,
+    pub items: Vec<LayoutItem>,
     pub clusters: Vec<ClusterData>,
     pub glyphs: Vec<Glyph>,
     pub lines: Vec<LineData>,
-    pub line_runs: Vec<LineRunData>,
+    pub line_items: Vec<LineItemData>,
+    /// Indices of the line items that are text runs, grouped by line.
+    pub line_runs: Vec<usize>,
+    /// Glyphs for the ellipsis of a truncated layout.
+    pub ellipsis: Vec<Glyph>,
+    /// Range of text that is being composed by an input method.
+    pub preedit: Option<Range<usize>>,
+    /// Range of the cursor within the composed text, if visible.
+    pub preedit_cursor: Option<Range<usize>>,
+    /// Lines that were invalidated by an edit.
+    pub line_gap: Option<LineGap>,
+    /// Maximum advance and alignment used to break all lines with the greedy
+    /// strategy.
+    pub break_params: Option<(f32, Alignment)>,
+    /// Explicit tab stops of all styles.
+    pub tab_stops: Vec<TabStop>,
+    /// Tabs in logical order.
+    pub tabs: Vec<TabData>,
+    /// Decimal separators and the index of the cluster of their first
+    /// occurrence following each tab.
+    pub tab_decimals: Vec<(char, usize)>,
+    /// Min-content and max-content widths of each paragraph.
+    pub content_widths: Vec<(f32, f32)>,
+    /// Advance of the widest sequence of clusters without a line break
+    /// opportunity.
+    pub min_content_width: f32,
+    /// Advance of the longest line if lines are only broken at mandatory
+    /// breaks.
+    pub max_content_width: f32,
 }
 
 impl<B: Brush> Default for LayoutData<B> {
     fn default() -> Self {
         Self {
             scale: 1.,
+            generation: 0,
             has_bidi: false,
-            base_level: 0,
+            writing_mode: WritingMode::HorizontalTb,
             text_len: 0,
             width: 0.,
             full_width: 0.,
@@ -189,11 +402,27 @@ impl<B: Brush> Default for LayoutData<B> {
             fonts: Vec::new(),
             coords: Vec::new(),
             styles: Vec::new(),
+            style_ranges: Vec::new(),
+            paragraphs: Vec::new(),
             runs: Vec::new(),
+            inline_boxes: Vec::new(),
+            items: Vec::new(),
             clusters: Vec::new(),
             glyphs: Vec::new(),
             lines: Vec::new(),
+            line_items: Vec::new(),
             line_runs: Vec::new(),
+            ellipsis: Vec::new(),
+            preedit: None,
+            preedit_cursor: None,
+            line_gap: None,
+            break_params: None,
+            tab_stops: Vec::new(),
+            tabs: Vec::new(),
+            tab_decimals: Vec::new(),
+            content_widths: Vec::new(),
+            min_content_width: 0.,
+            max_content_width: 0.,
         }
     }
 }
@@ -201,8 +430,9 @@ impl<B: Brush> Default for LayoutData<B> {
 impl<B: Brush> LayoutData<B> {
     pub fn clear(&mut self) {
         self.scale = 1.;
+        self.generation = 0;
         self.has_bidi = false;
-        self.base_level = 0;
+        self.writing_mode = WritingMode::HorizontalTb;
         self.text_len = 0;
         self.width = 0.;
         self.full_width = 0.;
@@ -210,11 +440,373 @@ impl<B: Brush> LayoutData<B> {
         self.fonts.clear();
         self.coords.clear();
         self.styles.clear();
+        self.style_ranges.clear();
+        self.paragraphs.clear();
         self.runs.clear();
+        self.inline_boxes.clear();
+        self.items.clear();
         self.clusters.clear();
         self.glyphs.clear();
         self.lines.clear();
+        self.line_items.clear();
+        self.line_runs.clear();
+        self.ellipsis.clear();
+        self.preedit = None;
+        self.preedit_cursor = None;
+        self.line_gap = None;
+        self.break_params = None;
+        self.tab_stops.clear();
+        self.tabs.clear();
+        self.tab_decimals.clear();
+        self.content_widths.clear();
+        self.min_content_width = 0.;
+        self.max_content_width = 0.;
+    }
+
+    /// Returns the paragraph containing the specified text position, or the
+    /// last paragraph if the position is at the end of the text.
+    pub fn paragraph_at(&self, index: usize) -> Option<&ParagraphData> {
+        let i = self
+            .paragraphs
+            .partition_point(|paragraph| paragraph.text_range.end <= index);
+        self.paragraphs.get(i).or(self.paragraphs.last())
+    }
+
+    /// Returns the indentation of a line that starts at the specified text
+    /// position.
+    pub fn line_indent(&self, index: usize) -> f32 {
+        match self.paragraph_at(index) {
+            // A line at the end of the text follows a paragraph separator.
+            Some(paragraph)
+                if paragraph.text_range.start == index || paragraph.text_range.end == index =>
+            {
+                paragraph.text_indent
+            }
+            Some(paragraph) => paragraph.hanging_indent,
+            None => 0.,
+        }
+    }
+
+    /// Returns the text position of the start of the cluster at the
+    /// specified index, or the length of the text if there is no such
+    /// cluster.
+    pub fn cluster_text_start(&self, cluster_index: usize) -> usize {
+        let run_index = self
+            .runs
+            .partition_point(|run| run.cluster_range.end <= cluster_index);
+        match (self.runs.get(run_index), self.clusters.get(cluster_index)) {
+            (Some(run), Some(cluster)) => run.text_range.start + cluster.text_offset as usize,
+            _ => self.text_len,
+        }
+    }
+
+    /// Computes the region of the previous layout that is invalidated by
+    /// replacing the `replaced` range of its text with `inserted_len` bytes,
+    /// producing `text`.
+    ///
+    /// This layout must have been prepared for the edited text, with its
+    /// styles and paragraphs resolved. Returns `None` if no part of the
+    /// previous layout can be reused.
+    pub fn damage(
+        &self,
+        prev: &Self,
+        text: &str,
+        replaced: Range<usize>,
+        inserted_len: usize,
+    ) -> Option<Damage> {
+        if prev.runs.is_empty()
+            || prev.text_len == 0
+            || replaced.start > replaced.end
+            || replaced.end > prev.text_len
+            || prev.text_len - replaced.len() + inserted_len != self.text_len
+            || prev.scale != self.scale
+            || prev.generation != self.generation
+            || prev.writing_mode != self.writing_mode
+            || !prev.inline_boxes.is_empty()
+            || !self.inline_boxes.is_empty()
+        {
+            return None;
+        }
+        // A carriage return preceding the edit may combine with an inserted
+        // line feed into a single separator.
+        let before = if text.as_bytes().get(replaced.start.wrapping_sub(1)) == Some(&b'\r') {
+            replaced.start - 1
+        } else {
+            replaced.start
+        };
+        let first = prev
+            .paragraphs
+            .partition_point(|paragraph| paragraph.text_range.end <= before)
+            .min(prev.paragraphs.len().checked_sub(1)?);
+        let last = prev
+            .paragraphs
+            .partition_point(|paragraph| paragraph.text_range.end <= replaced.end)
+            .min(prev.paragraphs.len().checked_sub(1)?);
+        let text_range =
+            prev.paragraphs[first].text_range.start..prev.paragraphs[last].text_range.end;
+        let new_text_end = text_range.end - replaced.len() + inserted_len;
+        // The paragraphs surrounding the damaged region must be unchanged,
+        // apart from the offset of the following paragraphs.
+        let moved = |paragraph: &ParagraphData| ParagraphData {
+            text_range: paragraph.text_range.start + inserted_len - replaced.len()
+                ..paragraph.text_range.end + inserted_len - replaced.len(),
+            ..paragraph.clone()
+        };
+        let new_last = self
+            .paragraphs
+            .partition_point(|paragraph| paragraph.text_range.end < new_text_end);
+        if self.paragraphs.get(first)?.text_range.start != text_range.start
+            || self.paragraphs.get(new_last)?.text_range.end != new_text_end
+            || self.paragraphs.len() - new_last != prev.paragraphs.len() - last
+            || self.paragraphs[..first] != prev.paragraphs[..first]
+            || self.paragraphs[new_last + 1..]
+                .iter()
+                .zip(&prev.paragraphs[last + 1..])
+                .any(|(a, b)| *a != moved(b))
+        {
+            return None;
+        }
+        // Runs never span paragraphs, but make sure that the damaged runs
+        // cover exactly the damaged text.
+        let runs_start = prev
+            .runs
+            .partition_point(|run| run.text_range.start < text_range.start);
+        let runs_end = prev
+            .runs
+            .partition_point(|run| run.text_range.start < text_range.end);
+        if runs_start == runs_end
+            || prev.runs[runs_start].text_range.start != text_range.start
+            || prev.runs[runs_end - 1].text_range.end != text_range.end
+        {
+            return None;
+        }
+        let lines = prev.damaged_lines(&text_range);
+        Some(Damage {
+            text_range,
+            new_text_end,
+            runs: runs_start..runs_end,
+            lines,
+        })
+    }
+
+    /// Returns the range of lines that contain the specified text if all
+    /// lines of the layout were broken with the same parameters and the
+    /// range begins and ends at line boundaries.
+    fn damaged_lines(&self, text_range: &Range<usize>) -> Option<Range<usize>> {
+        if self.break_params.is_none()
+            || self.line_gap.is_some()
+            || !self.ellipsis.is_empty()
+            || self.lines.last()?.text_range.end != self.text_len
+        {
+            return None;
+        }
+        let start = self
+            .lines
+            .partition_point(|line| line.text_range.end <= text_range.start);
+        let end = self
+            .lines
+            .partition_point(|line| line.text_range.start < text_range.end);
+        if self.lines.get(start)?.text_range.start != text_range.start
+            || self
+                .lines
+                .get(end)
+                .is_some_and(|line| line.text_range.start != text_range.end)
+        {
+            return None;
+        }
+        Some(start..end)
+    }
+
+    /// Copies the runs of the previous layout that precede the damaged
+    /// region.
+    pub fn reuse_prefix(&mut self, prev: &Self, damage: &Damage) {
+        let style_map = self.map_styles(prev, |range| {
+            (range.start < damage.text_range.start).then_some(range.start)
+        });
+        self.copy_runs(
+            prev,
+            0..damage.runs.start,
+            |offset| offset,
+            |index| style_map[index as usize],
+        );
+        let paragraphs = prev
+            .paragraphs
+            .partition_point(|paragraph| paragraph.text_range.start < damage.text_range.start);
+        self.content_widths
+            .extend_from_slice(&prev.content_widths[..paragraphs]);
+    }
+
+    /// Copies the runs of the previous layout that follow the damaged region
+    /// and, if possible, the lines surrounding it. Must be called after the
+    /// damaged region has been shaped.
+    pub fn reuse_suffix(&mut self, prev: &Self, damage: &Damage) {
+        let shift = |offset: usize| offset - damage.text_range.end + damage.new_text_end;
+        let style_map = self.map_styles(prev, |range| {
+            (range.end > damage.text_range.end)
+                .then(|| shift(range.start.max(damage.text_range.end)))
+        });
+        let gap_items = damage.runs.start..self.runs.len();
+        let gap_clusters = prev.runs[damage.runs.start].cluster_range.start..self.clusters.len();
+        let first_run = self.runs.len();
+        self.copy_runs(prev, damage.runs.end..prev.runs.len(), shift, |index| {
+            style_map[index as usize]
+        });
+        let paragraphs = prev
+            .paragraphs
+            .partition_point(|paragraph| paragraph.text_range.start < damage.text_range.end);
+        self.content_widths
+            .extend_from_slice(&prev.content_widths[paragraphs..]);
+        let Some(lines) = damage.lines.clone() else {
+            return;
+        };
+        let prefix_items = lines
+            .start
+            .checked_sub(1)
+            .map(|index| prev.lines[index].item_range.end)
+            .unwrap_or(0);
+        self.lines.extend_from_slice(&prev.lines[..lines.start]);
+        self.line_items
+            .extend_from_slice(&prev.line_items[..prefix_items]);
+        if let Some(suffix) = prev.lines.get(lines.end) {
+            let old_items = suffix.item_range.start;
+            let new_items = self.line_items.len();
+            let old_clusters = prev.runs[damage.runs.end].cluster_range.start;
+            let new_clusters = gap_clusters.end;
+            self.lines
+                .extend(prev.lines[lines.end..].iter().map(|line| LineData {
+                    text_range: shift(line.text_range.start)..shift(line.text_range.end),
+                    item_range: line.item_range.start - old_items + new_items
+                        ..line.item_range.end - old_items + new_items,
+                    ..line.clone()
+                }));
+            self.line_items.extend(
+                prev.line_items[old_items..]
+                    .iter()
+                    .map(|item| LineItemData {
+                        index: item.index - damage.runs.end + first_run,
+                        text_range: shift(item.text_range.start)..shift(item.text_range.end),
+                        cluster_range: item.cluster_range.start - old_clusters + new_clusters
+                            ..item.cluster_range.end - old_clusters + new_clusters,
+                        ..item.clone()
+                    }),
+            );
+        }
+        self.index_line_runs();
+        self.line_gap = Some(LineGap {
+            line_index: lines.start,
+            items: gap_items,
+            clusters: gap_clusters,
+        });
+        self.break_params = prev.break_params;
+    }
+
+    /// Records the line items that are text runs for each line, so that the
+    /// runs of a line can be accessed by index.
+    pub fn index_line_runs(&mut self) {
         self.line_runs.clear();
+        for line in &mut self.lines {
+            let start = self.line_runs.len();
+            self.line_runs.extend(
+                line.item_range
+                    .clone()
+                    .filter(|&index| self.line_items[index].is_text_run()),
+            );
+            line.run_range = start..self.line_runs.len();
+        }
+    }
+
+    /// Maps the styles of the previous layout to the styles of this layout
+    /// using the position in the edited text selected by `position` for the
+    /// text range of each previous style.
+    fn map_styles(
+        &self,
+        prev: &Self,
+        position: impl Fn(&Range<usize>) -> Option<usize>,
+    ) -> Vec<u16> {
+        let last = self.style_ranges.len().saturating_sub(1);
+        prev.style_ranges
+            .iter()
+            .map(|range| {
+                position(range)
+                    .map(|offset| {
+                        self.style_ranges
+                            .partition_point(|range| range.end <= offset)
+                            .min(last) as u16
+                    })
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Appends copies of the specified runs of another layout, along with
+    /// their clusters and glyphs, shifting text offsets by `shift` and
+    /// replacing style indices with the result of `style_map`.
+    pub fn copy_runs(
+        &mut self,
+        prev: &Self,
+        runs: Range<usize>,
+        shift: impl Fn(usize) -> usize,
+        style_map: impl Fn(u16) -> u16,
+    ) {
+        for (index, run) in prev.runs[runs.clone()].iter().enumerate() {
+            let glyphs_end = prev
+                .runs
+                .get(runs.start + index + 1)
+                .map(|next| next.glyph_start)
+                .unwrap_or(prev.glyphs.len());
+            let font = &prev.fonts[run.font_index];
+            let font_index = self
+                .fonts
+                .iter()
+                .position(|f| f == font)
+                .unwrap_or_else(|| {
+                    self.fonts.push(font.clone());
+                    self.fonts.len() - 1
+                });
+            let coords_start = self.coords.len();
+            self.coords
+                .extend_from_slice(&prev.coords[run.coords_range.clone()]);
+            let cluster_start = self.clusters.len();
+            self.clusters.extend(
+                prev.clusters[run.cluster_range.clone()]
+                    .iter()
+                    .map(|cluster| ClusterData {
+                        style_index: style_map(cluster.style_index),
+                        ..*cluster
+                    }),
+            );
+            let glyph_start = self.glyphs.len();
+            self.glyphs.extend(
+                prev.glyphs[run.glyph_start..glyphs_end]
+                    .iter()
+                    .map(|glyph| Glyph {
+                        style_index: style_map(glyph.style_index),
+                        ..*glyph
+                    }),
+            );
+            self.items.push(LayoutItem {
+                kind: LayoutItemKind::TextRun,
+                index: self.runs.len(),
+                bidi_level: run.bidi_level,
+            });
+            self.runs.push(RunData {
+                font_index,
+                coords_range: coords_start..self.coords.len(),
+                text_range: shift(run.text_range.start)..shift(run.text_range.end),
+                cluster_range: cluster_start..self.clusters.len(),
+                glyph_start,
+                ..run.clone()
+            });
+        }
+    }
+
+    /// Appends an item for the inline box at the specified index.
+    pub fn push_inline_box(&mut self, index: usize, bidi_level: u8) {
+        self.items.push(LayoutItem {
+            kind: LayoutItemKind::InlineBox,
+            index,
+            bidi_level,
+        });
     }
 
     #[allow(unused_assignments)]
@@ -229,6 +821,16 @@ impl<B: Brush> LayoutData<B> {
         word_spacing: f32,
         letter_spacing: f32,
     ) {
+        // Vertical layouts replace the advances and offsets produced by the
+        // shaper with the vertical metrics of each glyph.
+        let vertical = self
+            .writing_mode
+            .is_vertical()
+            .then(|| (font.data.clone(), shaper.normalized_coords().to_vec()));
+        let vertical_metrics = vertical.as_ref().and_then(|(data, coords)| {
+            swash::FontRef::from_index(data.as_ref(), font.index as usize)
+                .map(|font| font.glyph_metrics(coords).scale(font_size))
+        });
         let font_index = self
             .fonts
             .iter()
@@ -246,6 +848,13 @@ impl<B: Brush> LayoutData<B> {
             self.coords.extend_from_slice(coords);
         }
         let coords_end = self.coords.len();
+        let (ascent, descent) = if vertical_metrics.is_some() {
+            // Vertical lines use a central baseline.
+            let half = (metrics.ascent + metrics.descent) * 0.5;
+            (half, half)
+        } else {
+            (metrics.ascent, metrics.descent)
+        };
         let mut run = RunData {
             font_index,
             font_size,
@@ -257,8 +866,8 @@ impl<B: Brush> LayoutData<B> {
             cluster_range,
             glyph_start: self.glyphs.len(),
             metrics: RunMetrics {
-                ascent: metrics.ascent,
-                descent: metrics.descent,
+                ascent,
+                descent,
                 leading: metrics.leading,
                 underline_offset: metrics.underline_offset,
                 underline_size: metrics.stroke_size,
@@ -268,6 +877,10 @@ impl<B: Brush> LayoutData<B> {
             word_spacing,
             letter_spacing,
             advance: 0.,
+            hyphen_id: 0,
+            hyphen_advance: 0.,
+            tatweel_id: 0,
+            tatweel_advance: 0.,
         };
         // Track these so that we can flush if they overflow a u16.
         let mut glyph_count = 0usize;
@@ -275,6 +888,11 @@ impl<B: Brush> LayoutData<B> {
         macro_rules! flush_run {
             () => {
                 if !run.cluster_range.is_empty() {
+                    self.items.push(LayoutItem {
+                        kind: LayoutItemKind::TextRun,
+                        index: self.runs.len(),
+                        bidi_level: run.bidi_level,
+                    });
                     self.runs.push(run.clone());
                     run.text_range = text_offset..text_offset;
                     run.cluster_range.start = run.cluster_range.end;
@@ -309,7 +927,14 @@ impl<B: Brush> LayoutData<B> {
             }
             let text_len = source_range.len();
             let glyph_len = cluster.glyphs.len();
-            let advance = cluster.advance();
+            let advance = match &vertical_metrics {
+                Some(vertical_metrics) => cluster
+                    .glyphs
+                    .iter()
+                    .map(|g| vertical_metrics.advance_height(g.id))
+                    .sum(),
+                None => cluster.advance(),
+            };
             run.advance += advance;
             let mut cluster_data = ClusterData {
                 info: cluster.info,
@@ -347,7 +972,7 @@ impl<B: Brush> LayoutData<B> {
             run.cluster_range.end += 1;
             run.text_range.end += text_len;
             text_offset += text_len;
-            if glyph_len == 1 && num_components == 1 {
+            if glyph_len == 1 && num_components == 1 && vertical_metrics.is_none() {
                 let g = &cluster.glyphs[0];
                 if nearly_zero(g.x) && nearly_zero(g.y) {
                     // Handle the case with a single glyph with zero'd offset.
@@ -369,12 +994,22 @@ impl<B: Brush> LayoutData<B> {
                 if cluster_data.style_index != style_index {
                     cluster_data.flags |= ClusterData::DIVERGENT_STYLES;
                 }
+                let (x, y, advance) = match &vertical_metrics {
+                    // Upright glyphs are centered on the baseline and
+                    // positioned below the top of the advance.
+                    Some(vertical_metrics) => (
+                        -vertical_metrics.advance_width(g.id) * 0.5,
+                        vertical_metrics.vertical_origin(g.id),
+                        vertical_metrics.advance_height(g.id),
+                    ),
+                    None => (g.x, g.y, g.advance),
+                };
                 Glyph {
                     id: g.id,
                     style_index,
-                    x: g.x,
-                    y: g.y,
-                    advance: g.advance,
+                    x,
+                    y,
+                    advance,
                 }
             }));
             glyph_count += glyph_len;
@@ -383,8 +1018,389 @@ impl<B: Brush> LayoutData<B> {
         flush_run!();
     }
 
-    pub fn finish(&mut self) {
-        for run in &self.runs {
+    /// Marks the clusters of the specified runs that end at the specified
+    /// text offsets as hyphenation points. The offsets must be sorted.
+    pub fn mark_hyphenation_points(&mut self, runs: Range<usize>, offsets: &[usize]) {
+        if offsets.is_empty() {
+            return;
+        }
+        for run in &mut self.runs[runs] {
+            let mut hyphen = None;
+            for cluster in &mut self.clusters[run.cluster_range.clone()] {
+                let end = cluster.text_range(run).end;
+                if offsets.binary_search(&end).is_err() {
+                    continue;
+                }
+                if hyphen.is_none() {
+                    let font = &self.fonts[run.font_index];
+                    let coords = &self.coords[run.coords_range.clone()];
+                    hyphen = Some(
+                        map_glyph(font, coords, run.font_size, '\u{2010}')
+                            .or_else(|| map_glyph(font, coords, run.font_size, '-')),
+                    );
+                }
+                if let Some(Some((id, advance))) = hyphen {
+                    run.hyphen_id = id;
+                    run.hyphen_advance = advance;
+                    cluster.flags |= ClusterData::HYPHEN;
+                }
+            }
+        }
+    }
+
+    /// Returns the advance of the inline box at the specified index along the
+    /// inline axis.
+    pub fn inline_box_advance(&self, index: usize) -> f32 {
+        let inline_box = &self.inline_boxes[index];
+        if self.writing_mode.is_vertical() {
+            inline_box.height
+        } else {
+            inline_box.width
+        }
+    }
+
+    /// Returns the advance of the hyphen displayed after the cluster at the
+    /// specified index.
+    pub fn hyphen_advance(&self, cluster_index: usize) -> f32 {
+        let index = self
+            .runs
+            .partition_point(|run| run.cluster_range.end <= cluster_index);
+        self.runs
+            .get(index)
+            .map(|run| run.hyphen_advance)
+            .unwrap_or_default()
+    }
+
+    /// Computes the glyphs for an ellipsis displayed with the font of the
+    /// specified run.
+    pub fn ellipsis_glyphs(&self, run_index: usize, style_index: u16) -> Vec<Glyph> {
+        let run = &self.runs[run_index];
+        let font = &self.fonts[run.font_index];
+        let coords = &self.coords[run.coords_range.clone()];
+        let glyph = |(id, advance)| Glyph {
+            id,
+            style_index,
+            x: 0.,
+            y: 0.,
+            advance,
+        };
+        if let Some(ellipsis) = map_glyph(font, coords, run.font_size, '\u{2026}') {
+            vec![glyph(ellipsis)]
+        } else if let Some(period) = map_glyph(font, coords, run.font_size, '.') {
+            vec![glyph(period); 3]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Measures the min-content and max-content widths of the paragraphs
+    /// containing the items starting at `first_item`. The items must cover
+    /// whole paragraphs and follow those that have already been measured.
+    pub fn measure_content(&mut self, first_item: usize) {
+        let mut paragraph_index = usize::MAX;
+        // Advance and trailing whitespace of the current line and of the
+        // current sequence of clusters without a break opportunity.
+        let mut line = (0., 0.);
+        let mut segment = (0., 0.);
+        let mut widths = (0f32, 0f32);
+        // Inline boxes allow a break on either side.
+        let mut after_box = false;
+        for item in &self.items[first_item..] {
+            let (position, advance) = match item.kind {
+                LayoutItemKind::TextRun => (self.runs[item.index].text_range.start, 0.),
+                LayoutItemKind::InlineBox => (
+                    self.inline_boxes[item.index].index,
+                    self.inline_box_advance(item.index),
+                ),
+            };
+            let index = self
+                .paragraphs
+                .partition_point(|paragraph| paragraph.text_range.end <= position)
+                .min(self.paragraphs.len().saturating_sub(1));
+            let hanging_indent = self
+                .paragraphs
+                .get(index)
+                .map_or(0., |paragraph| paragraph.hanging_indent);
+            if index != paragraph_index {
+                if paragraph_index != usize::MAX {
+                    widths.0 = widths.0.max(segment.0 - segment.1);
+                    widths.1 = widths.1.max(line.0 - line.1);
+                    set_content_widths(&mut self.content_widths, paragraph_index, widths);
+                }
+                paragraph_index = index;
+                // Both start with the indentation of the first line.
+                line = (self.line_indent(position), 0.);
+                segment = line;
+                widths = (0., 0.);
+                after_box = false;
+            }
+            if item.kind == LayoutItemKind::InlineBox {
+                widths.0 = widths.0.max(segment.0 - segment.1);
+                segment = (self.line_indent(position) + advance, 0.);
+                line = (line.0 + advance, 0.);
+                after_box = true;
+                continue;
+            }
+            let run = &self.runs[item.index];
+            for j in run.cluster_range.clone() {
+                let cluster = &self.clusters[j];
+                if !cluster.is_ligature_component() {
+                    match cluster.info.boundary() {
+                        Boundary::Mandatory => {
+                            widths.0 = widths.0.max(segment.0 - segment.1);
+                            widths.1 = widths.1.max(line.0 - line.1);
+                            let indent = self.line_indent(cluster.text_range(run).start);
+                            line = (indent, 0.);
+                            segment = (indent, 0.);
+                        }
+                        Boundary::Line => {
+                            let mut width = segment.0 - segment.1;
+                            if j > 0 && self.clusters[j - 1].is_hyphenation_point() {
+                                width += self.hyphen_advance(j - 1);
+                            }
+                            widths.0 = widths.0.max(width);
+                            segment = (hanging_indent, 0.);
+                        }
+                        _ if after_box => {
+                            widths.0 = widths.0.max(segment.0 - segment.1);
+                            segment = (hanging_indent, 0.);
+                        }
+                        _ => {}
+                    }
+                    after_box = false;
+                }
+                // Only spaces hang at the end of a line.
+                let trailing = cluster.info.whitespace().is_space_or_nbsp();
+                for state in [&mut line, &mut segment] {
+                    state.0 += cluster.advance;
+                    state.1 = if trailing {
+                        state.1 + cluster.advance
+                    } else {
+                        0.
+                    };
+                }
+            }
+        }
+        if paragraph_index != usize::MAX {
+            widths.0 = widths.0.max(segment.0 - segment.1);
+            widths.1 = widths.1.max(line.0 - line.1);
+            set_content_widths(&mut self.content_widths, paragraph_index, widths);
+        }
+    }
+
+    /// Collects the tabs of the layout along with the clusters that are
+    /// aligned to their tab stops.
+    pub fn resolve_tabs(&mut self, text: &str) {
+        self.tabs.clear();
+        self.tab_decimals.clear();
+        if !text.contains('\t') {
+            return;
+        }
+        for run_index in 0..self.runs.len() {
+            for j in self.runs[run_index].cluster_range.clone() {
+                let cluster = &self.clusters[j];
+                if !cluster.is_tab() {
+                    continue;
+                }
+                let style = &self.styles[cluster.style_index as usize];
+                let stops = style.tab_stops.clone();
+                let interval = self.tab_interval(run_index, style.tab_size);
+                let end = self.clusters[j + 1..]
+                    .iter()
+                    .position(|cluster| {
+                        cluster.is_tab() || cluster.info.boundary() == Boundary::Mandatory
+                    })
+                    .map_or(self.clusters.len(), |offset| j + 1 + offset);
+                let decimals_start = self.tab_decimals.len();
+                for stop in &self.tab_stops[stops.clone()] {
+                    let TabAlignment::Decimal(separator) = stop.alignment else {
+                        continue;
+                    };
+                    if self.tab_decimals[decimals_start..]
+                        .iter()
+                        .any(|(ch, _)| *ch == separator)
+                    {
+                        continue;
+                    }
+                    let mut run = run_index;
+                    let position = (j + 1..end)
+                        .find(|&k| {
+                            while self.runs[run].cluster_range.end <= k {
+                                run += 1;
+                            }
+                            let offset = self.runs[run].text_range.start
+                                + self.clusters[k].text_offset as usize;
+                            text[offset..].starts_with(separator)
+                        })
+                        .unwrap_or(end);
+                    self.tab_decimals.push((separator, position));
+                }
+                self.tabs.push(TabData {
+                    cluster: j,
+                    interval,
+                    stops,
+                    end,
+                    decimals: decimals_start..self.tab_decimals.len(),
+                });
+            }
+        }
+    }
+
+    /// Returns the distance between the default tab stops for a tab in the
+    /// specified run.
+    fn tab_interval(&self, run_index: usize, tab_size: TabSize) -> f32 {
+        match tab_size {
+            TabSize::Absolute(size) => size,
+            TabSize::Spaces(count) => {
+                let run = &self.runs[run_index];
+                let font = &self.fonts[run.font_index];
+                let space = map_glyph(
+                    font,
+                    &self.coords[run.coords_range.clone()],
+                    run.font_size,
+                    ' ',
+                )
+                .map_or(run.font_size * 0.25, |(_, advance)| advance);
+                count * (space + run.letter_spacing + run.word_spacing)
+            }
+        }
+        .max(0.)
+    }
+
+    /// Sets the advance of the cluster at the specified index, adjusting the
+    /// advance of its last glyph accordingly.
+    pub fn set_cluster_advance(&mut self, cluster_index: usize, advance: f32) {
+        let run_index = self
+            .runs
+            .partition_point(|run| run.cluster_range.end <= cluster_index);
+        let glyph_start = self.runs[run_index].glyph_start;
+        let cluster = &mut self.clusters[cluster_index];
+        let delta = advance - cluster.advance;
+        cluster.advance = advance;
+        if cluster.glyph_len != 0xFF && cluster.glyph_len != 0 {
+            let last = glyph_start + cluster.glyph_offset as usize + cluster.glyph_len as usize - 1;
+            self.glyphs[last].advance += delta;
+        }
+    }
+
+    /// Computes the min-content and max-content widths of the layout from
+    /// those of its paragraphs.
+    pub fn finish_content_widths(&mut self) {
+        let (min, max) = self
+            .content_widths
+            .iter()
+            .fold((0f32, 0f32), |acc, widths| {
+                (acc.0.max(widths.0), acc.1.max(widths.1))
+            });
+        self.min_content_width = min;
+        self.max_content_width = max.max(min);
+    }
+
+    /// Applies word and letter spacing to the clusters of the specified runs
+    /// and advances each tab to the first default tab stop.
+    pub fn finish(&mut self, text: &str, runs: Range<usize>) {
+        if text.contains('\t') {
+            for run_index in runs.clone() {
+                for j in self.runs[run_index].cluster_range.clone() {
+                    let run = &self.runs[run_index];
+                    let cluster = self.clusters[j];
+                    if cluster.is_ligature_component()
+                        || !text[cluster.text_range(run)].starts_with('\t')
+                    {
+                        continue;
+                    }
+                    let tab_size = self.styles[cluster.style_index as usize].tab_size;
+                    let interval = self.tab_interval(run_index, tab_size);
+                    let font = &self.fonts[run.font_index];
+                    let space = map_glyph(
+                        font,
+                        &self.coords[run.coords_range.clone()],
+                        run.font_size,
+                        ' ',
+                    );
+                    let glyph_start = run.glyph_start;
+                    let cluster = &mut self.clusters[j];
+                    cluster.flags |= ClusterData::TAB;
+                    if let Some((id, _)) = space {
+                        // Fonts rarely map the tab character, so replace the
+                        // glyph emitted for it with a space glyph that spans
+                        // the tab.
+                        match cluster.glyph_len {
+                            0 | 0xFF => {
+                                cluster.glyph_len = 0xFF;
+                                cluster.glyph_offset = id;
+                            }
+                            _ => {
+                                let glyph =
+                                    &mut self.glyphs[glyph_start + cluster.glyph_offset as usize];
+                                glyph.id = id;
+                                glyph.advance = cluster.advance;
+                                cluster.glyph_len = 1;
+                            }
+                        }
+                    }
+                    self.set_cluster_advance(j, interval);
+                }
+            }
+        }
+        // Justification treats the clusters of scripts without spaces and the
+        // connections between joined Arabic letters specially.
+        for run_index in runs.clone() {
+            let run = &self.runs[run_index];
+            if text[run.text_range.clone()].is_ascii() {
+                continue;
+            }
+            let cluster_range = run.cluster_range.clone();
+            let mut tatweel = None;
+            for j in cluster_range.clone() {
+                let run = &self.runs[run_index];
+                let first_char =
+                    |cluster: &ClusterData| text[cluster.text_range(run)].chars().next();
+                let Some(ch) = first_char(&self.clusters[j]) else {
+                    continue;
+                };
+                let script = ch.script();
+                let mut flags = 0;
+                if matches!(
+                    script,
+                    Script::Han
+                        | Script::Hiragana
+                        | Script::Katakana
+                        | Script::Bopomofo
+                        | Script::Thai
+                        | Script::Lao
+                        | Script::Khmer
+                        | Script::Myanmar
+                ) || matches!(ch, '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FFEF}')
+                {
+                    flags |= ClusterData::UNSPACED;
+                }
+                if script == Script::Arabic
+                    && matches!(ch.joining_type(), JoiningType::D | JoiningType::L)
+                    && j + 1 < cluster_range.end
+                    && !self.clusters[j + 1].is_ligature_component()
+                    && first_char(&self.clusters[j + 1]).is_some_and(|next| {
+                        matches!(next.joining_type(), JoiningType::D | JoiningType::R)
+                    })
+                {
+                    let font = &self.fonts[run.font_index];
+                    let coords = &self.coords[run.coords_range.clone()];
+                    if tatweel
+                        .get_or_insert_with(|| map_glyph(font, coords, run.font_size, '\u{640}'))
+                        .is_some()
+                    {
+                        flags |= ClusterData::KASHIDA_POINT;
+                    }
+                }
+                self.clusters[j].flags |= flags;
+            }
+            if let Some(Some((id, advance))) = tatweel {
+                let run = &mut self.runs[run_index];
+                run.tatweel_id = id;
+                run.tatweel_advance = advance;
+            }
+        }
+        for run in &self.runs[runs] {
             let word = run.word_spacing;
             let letter = run.letter_spacing;
             if nearly_zero(word) && nearly_zero(letter) {
@@ -411,3 +1427,214 @@ impl<B: Brush> LayoutData<B> {
         }
     }
 }
+
+fn set_content_widths(
+    content_widths: &mut Vec<(f32, f32)>,
+    paragraph_index: usize,
+    widths: (f32, f32),
+) {
+    if content_widths.len() <= paragraph_index {
+        content_widths.resize(paragraph_index + 1, (0., 0.));
+    }
+    content_widths[paragraph_index] = widths;
+}
+
+/// Returns the identifier and advance of the glyph for the specified
+/// character in a font.
+fn map_glyph(font: &Font, coords: &[i16], font_size: f32, ch: char) -> Option<(u16, f32)> {
+    let font = swash::FontRef::from_index(font.data.as_ref(), font.index as usize)?;
+    let id = font.charmap().map(ch);
+    if id == 0 {
+        return None;
+    }
+    let advance = font
+        .glyph_metrics(coords)
+        .scale(font_size)
+        .advance_width(id);
+    Some((id, advance))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::layout::PositionedLayoutItem;
+    use crate::style::StyleProperty;
+    use crate::testing::{self, build_and_break, Brush, Content};
+
+    /// Returns the position and identifier of each glyph in the first line.
+    fn line_glyphs(text: &str, style: &[StyleProperty<Brush>]) -> Vec<(f32, u16)> {
+        let layout = build_and_break(text, &Content::styled(style), None, Alignment::Start);
+        let line = layout.get(0).unwrap();
+        let mut glyphs = vec![];
+        for item in line.items() {
+            if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                glyphs.extend(
+                    glyph_run
+                        .positioned_glyphs()
+                        .map(|glyph| (glyph.x, glyph.id)),
+                );
+            }
+        }
+        glyphs
+    }
+
+    fn positions(text: &str, stops: &[TabStop]) -> Vec<f32> {
+        line_glyphs(text, &[StyleProperty::TabStops(stops)])
+            .into_iter()
+            .map(|(x, _)| x)
+            .collect()
+    }
+
+    #[test]
+    fn default_tab_stops() {
+        // The default interval is eight spaces.
+        let glyphs = line_glyphs("ab\tc", &[]);
+        assert_eq!(
+            glyphs.iter().map(|g| g.0).collect::<Vec<_>>(),
+            [0., 10., 20., 80.]
+        );
+        // The tab is displayed with the glyph of a space.
+        let space = line_glyphs(" ", &[])[0].1;
+        assert_eq!(glyphs[2].1, space);
+        let style = [StyleProperty::TabSize(TabSize::Absolute(25.))];
+        let glyphs = line_glyphs("abc\td\te", &style);
+        assert_eq!(
+            glyphs.iter().map(|g| g.0).collect::<Vec<_>>(),
+            [0., 10., 20., 30., 50., 60., 75.]
+        );
+        // A tab at a stop advances to the next one.
+        let glyphs = line_glyphs("abcde\tf", &style);
+        assert_eq!(glyphs[6].0, 75.);
+    }
+
+    #[test]
+    fn tab_stop_alignment() {
+        let start = [TabStop::new(45., TabAlignment::Start)];
+        assert_eq!(positions("ab\tcd", &start), [0., 10., 20., 45., 55.]);
+        let center = [TabStop::new(60., TabAlignment::Center)];
+        assert_eq!(positions("a\tbcde", &center), [0., 10., 40., 50., 60., 70.]);
+        let end = [TabStop::new(70., TabAlignment::End)];
+        assert_eq!(positions("a\tbc", &end), [0., 10., 50., 60.]);
+        // The aligned text extends up to the next tab.
+        let stops = [
+            TabStop::new(50., TabAlignment::End),
+            TabStop::new(90., TabAlignment::Start),
+        ];
+        assert_eq!(positions("a\tbc\td", &stops), [0., 10., 30., 40., 50., 90.]);
+        // Stops before the current position are skipped.
+        assert_eq!(
+            positions("abcdef\tg", &start),
+            [0., 10., 20., 30., 40., 50., 60., 80.]
+        );
+    }
+
+    #[test]
+    fn decimal_tab_stop() {
+        let stops = [TabStop::new(60., TabAlignment::Decimal('.'))];
+        assert_eq!(positions("a\t12.5", &stops), [0., 10., 40., 50., 60., 70.]);
+        // Without a separator, the text ends at the stop.
+        assert_eq!(positions("a\t125", &stops), [0., 10., 30., 40., 50.]);
+    }
+
+    /// Returns the positions of the glyphs of each line of a vertical
+    /// layout.
+    fn vertical_glyphs(text: &str, writing_mode: WritingMode) -> Vec<Vec<(f32, f32)>> {
+        let content = Content {
+            writing_mode,
+            ..Content::default()
+        };
+        let layout = build_and_break(text, &content, Some(25.), Alignment::Start);
+        assert_eq!((layout.width(), layout.height()), (20., 20.));
+        layout
+            .lines()
+            .map(|line| {
+                line.items()
+                    .flat_map(|item| match item {
+                        PositionedLayoutItem::GlyphRun(glyph_run) => glyph_run
+                            .positioned_glyphs()
+                            .map(|glyph| (glyph.x, glyph.y))
+                            .collect(),
+                        PositionedLayoutItem::InlineBox(_) => vec![],
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn vertical_writing_modes() {
+        // Without vertical metrics in the font, glyphs advance by the height
+        // of the font and hang from its ascent.
+        assert_eq!(
+            vertical_glyphs("ab c", WritingMode::VerticalRl),
+            [vec![(10., 8.), (10., 18.), (10., 28.)], vec![(0., 8.)]]
+        );
+        assert_eq!(
+            vertical_glyphs("ab c", WritingMode::VerticalLr),
+            [vec![(0., 8.), (0., 18.), (0., 28.)], vec![(10., 8.)]]
+        );
+    }
+
+    /// Returns the min-content and max-content widths of the text with the
+    /// inline boxes.
+    fn content_widths(text: &str, boxes: &[InlineBox]) -> (f32, f32) {
+        let content = Content {
+            boxes,
+            ..Content::default()
+        };
+        let layout = testing::build_standalone(text, &content);
+        (layout.min_content_width(), layout.max_content_width())
+    }
+
+    #[test]
+    fn content_widths_of_text() {
+        assert_eq!(content_widths("", &[]), (0., 0.));
+        assert_eq!(content_widths("ab cde f", &[]), (30., 80.));
+        assert_eq!(content_widths("\u{5D0}\u{5D1} \u{5D2}", &[]), (20., 40.));
+        // Trailing whitespace is excluded and paragraphs are measured
+        // separately.
+        assert_eq!(content_widths("ab  \ncdef ", &[]), (40., 40.));
+        // A hyphen is inserted when breaking at a soft hyphen.
+        assert_eq!(content_widths("abc\u{AD}def", &[]), (40., 60.));
+    }
+
+    #[test]
+    fn content_widths_with_inline_box() {
+        let inline_box = InlineBox {
+            id: 0,
+            index: 2,
+            width: 25.,
+            height: 10.,
+            baseline: 8.,
+        };
+        // Inline boxes allow a break on either side.
+        assert_eq!(content_widths("abcd", &[inline_box]), (25., 65.));
+        let inline_box = InlineBox {
+            index: 3,
+            width: 45.,
+            ..inline_box
+        };
+        assert_eq!(content_widths("ab cd", &[inline_box]), (45., 95.));
+    }
+
+    #[test]
+    fn break_at_content_widths() {
+        for text in ["ab cde f", "abc\u{AD}def gh", "one two\nthree  four five\n"] {
+            let mut layout = build_and_break(text, &Content::default(), None, Alignment::Start);
+            let (min, max) = (layout.min_content_width(), layout.max_content_width());
+            let lines = layout.len();
+            layout.break_all_lines(Some(max), Alignment::Start);
+            assert_eq!(layout.len(), lines, "{text:?}");
+            assert_eq!(layout.width(), max, "{text:?}");
+            // No line overflows, so no word is broken.
+            layout.break_all_lines(Some(min), Alignment::Start);
+            for line in layout.lines() {
+                let metrics = line.metrics();
+                assert!(
+                    metrics.advance - metrics.trailing_whitespace <= min,
+                    "{text:?}"
+                );
+            }
+        }
+    }
+}
@@ -7,20 +7,20 @@
 use alloc::vec::Vec;
 
 use crate::layout::*;
-use crate::style::Brush;
+use crate::style::{Brush, LineHeight, TabAlignment, TextJustify};
 
 use core::ops::Range;
 
 #[derive(Default)]
 struct LineLayout {
     lines: Vec<LineData>,
-    runs: Vec<LineRunData>,
+    items: Vec<LineItemData>,
 }
 
 impl LineLayout {
     fn swap<B: Brush>(&mut self, layout: &mut LayoutData<B>) {
         core::mem::swap(&mut self.lines, &mut layout.lines);
-        core::mem::swap(&mut self.runs, &mut layout.line_runs);
+        core::mem::swap(&mut self.items, &mut layout.line_items);
     }
 }
 
@@ -31,6 +31,24 @@ pub struct BreakLines<'a, B: Brush> {
     state: BreakerState,
     prev_state: Option<BreakerState>,
     done: bool,
+    max_lines: Option<(usize, TextOverflow)>,
+    gap: Option<Gap>,
+    /// Advances of the tabs on the computed lines, by cluster index.
+    tab_advances: Vec<(usize, f32)>,
+}
+
+/// Lines retained around a gap that is being recomputed.
+struct Gap {
+    /// Number of lines preceding the gap.
+    lines: usize,
+    /// Number of line items preceding the gap.
+    items: usize,
+    /// Cluster at which the last line in the gap ends, or `usize::MAX` if
+    /// the gap extends to the end of the layout.
+    end: usize,
+    /// Lines following the gap, with item ranges relative to the start of
+    /// its items.
+    suffix: LineLayout,
 }
 
 impl<'a, B: Brush> BreakLines<'a, B> {
@@ -38,29 +56,173 @@ impl<'a, B: Brush> BreakLines<'a, B> {
         unjustify(layout);
         layout.width = 0.;
         layout.height = 0.;
+        layout.ellipsis.clear();
+        layout.line_gap = None;
+        layout.break_params = None;
+        // Tabs are moved to their stops as lines are computed.
+        for index in 0..layout.tabs.len() {
+            let tab = &layout.tabs[index];
+            layout.set_cluster_advance(tab.cluster, tab.interval);
+        }
         let mut lines = LineLayout::default();
         lines.swap(layout);
         lines.lines.clear();
-        lines.runs.clear();
+        lines.items.clear();
         Self {
             layout,
             lines,
             state: BreakerState::default(),
             prev_state: None,
             done: false,
+            max_lines: None,
+            gap: None,
+            tab_advances: Vec::new(),
+        }
+    }
+
+    /// Creates a line breaker that only computes the lines in the gap left
+    /// by an edit, retaining the lines that precede and follow it.
+    pub(crate) fn for_gap(layout: &'a mut LayoutData<B>, gap: LineGap) -> Self {
+        layout.width = 0.;
+        layout.height = 0.;
+        let mut lines = LineLayout::default();
+        lines.swap(layout);
+        let items = gap
+            .line_index
+            .checked_sub(1)
+            .map(|index| lines.lines[index].item_range.end)
+            .unwrap_or(0);
+        let mut suffix = LineLayout {
+            lines: lines.lines.split_off(gap.line_index),
+            items: lines.items.split_off(items),
+        };
+        for line in &mut suffix.lines {
+            line.item_range = line.item_range.start - items..line.item_range.end - items;
+        }
+        let end = if gap.clusters.end < layout.clusters.len() {
+            gap.clusters.end
+        } else {
+            usize::MAX
+        };
+        Self {
+            layout,
+            lines,
+            state: BreakerState {
+                items,
+                lines: gap.line_index,
+                i: gap.items.start,
+                j: gap.clusters.start,
+                line: LineState {
+                    items: gap.items.start..gap.items.start,
+                    clusters: gap.clusters.start..gap.clusters.start,
+                    // The gap begins after a paragraph separator.
+                    skip_mandatory_break: gap.clusters.start != 0,
+                    ..Default::default()
+                },
+                prev_boundary: None,
+            },
+            prev_state: None,
+            done: false,
+            max_lines: None,
+            gap: Some(Gap {
+                lines: gap.line_index,
+                items,
+                end,
+                suffix,
+            }),
+            tab_advances: Vec::new(),
         }
     }
 
+    /// Limits the number of lines in the layout. Lines beyond the maximum are
+    /// removed when the line breaker is finished and the treatment of the
+    /// last remaining line is determined by `overflow`.
+    pub fn set_max_lines(&mut self, max_lines: usize, overflow: TextOverflow) {
+        self.max_lines = Some((max_lines, overflow));
+    }
+
+    /// Returns true if more lines have been computed than the maximum set
+    /// by [`set_max_lines`](Self::set_max_lines).
+    fn exceeds_max_lines(&self) -> bool {
+        self.max_lines
+            .is_some_and(|(max_lines, _)| self.lines.lines.len() > max_lines)
+    }
+
     /// Computes the next line in the paragraph. Returns the advance and size
     /// (width and height for horizontal layouts) of the line.
     pub fn break_next(&mut self, max_advance: f32, alignment: Alignment) -> Option<(f32, f32)> {
+        self.break_next_at(0., max_advance, alignment)
+    }
+
+    /// Computes the next line in the paragraph within the space that starts
+    /// at `offset` along the inline axis and extends for `max_advance`.
+    /// Returns the advance and size of the line.
+    ///
+    /// Varying the space available to each line allows text to flow around
+    /// other content. The space is recorded in the
+    /// [`LineMetrics`](crate::layout::LineMetrics) of the line.
+    pub fn break_next_at(
+        &mut self,
+        offset: f32,
+        max_advance: f32,
+        alignment: Alignment,
+    ) -> Option<(f32, f32)> {
         if self.done {
             return None;
         }
+        if self
+            .gap
+            .as_ref()
+            .is_some_and(|gap| self.state.line.clusters.start >= gap.end)
+        {
+            // The remaining lines were retained.
+            self.done = true;
+            return None;
+        }
         self.prev_state = Some(self.state.clone());
-        let run_count = self.layout.runs.len();
-        while self.state.i < run_count {
-            let run_data = &self.layout.runs[self.state.i];
+        self.state.line.span_offset = offset;
+        self.state.line.span_width = max_advance;
+        // The indentation of the line reduces the space available to it.
+        self.state.line.indent = line_indent(self.layout, self.state.i, self.state.j);
+        let max_advance = max_advance - self.state.line.indent;
+        let item_count = self.layout.items.len();
+        while self.state.i < item_count {
+            let item = self.layout.items[self.state.i];
+            if item.kind == LayoutItemKind::InlineBox {
+                // Inline boxes are atomic, but allow a break on either side.
+                self.state.prev_boundary = None;
+                let next_x = self.state.line.x + self.layout.inline_box_advance(item.index);
+                if next_x > max_advance && self.state.line.x != 0. {
+                    // Move the box to the next line.
+                    if commit_line(
+                        self.layout,
+                        &mut self.lines,
+                        &mut self.state.line,
+                        max_advance,
+                        alignment,
+                        BreakReason::Regular,
+                        false,
+                    ) {
+                        self.state.items = self.lines.items.len();
+                        self.state.lines = self.lines.lines.len();
+                        self.state.line.x = 0.;
+                        let line = self.lines.lines.last().unwrap();
+                        return Some((line.metrics.advance, line.size()));
+                    }
+                }
+                self.state.line.skip_mandatory_break = false;
+                self.state.line.items.end = self.state.i + 1;
+                self.state.line.clusters.end = self.state.j;
+                self.state.line.x = next_x;
+                self.state.i += 1;
+                self.state.prev_boundary = Some(PrevBoundaryState {
+                    i: self.state.i,
+                    j: self.state.j,
+                    state: self.state.line.clone(),
+                });
+                continue;
+            }
+            let run_data = &self.layout.runs[item.index];
             let run = Run::new(self.layout, run_data, None);
             let cluster_start = run_data.cluster_range.start;
             let cluster_end = run_data.cluster_range.end;
@@ -74,7 +236,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                         if !self.state.line.skip_mandatory_break {
                             self.state.prev_boundary = None;
                             self.state.line.clusters.end = self.state.j;
-                            self.state.line.runs.end = self.state.i + 1;
+                            self.state.line.items.end = self.state.i + 1;
                             self.state.line.skip_mandatory_break = true;
                             if commit_line(
                                 self.layout,
@@ -85,7 +247,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                                 BreakReason::Explicit,
                                 false,
                             ) {
-                                self.state.runs = self.lines.runs.len();
+                                self.state.items = self.lines.items.len();
                                 self.state.lines = self.lines.lines.len();
                                 self.state.line.x = 0.;
                                 let line = self.lines.lines.last().unwrap();
@@ -95,19 +257,44 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                     }
                     Boundary::Line => {
                         if !is_ligature_continuation {
-                            self.state.prev_boundary = Some(PrevBoundaryState {
-                                i: self.state.i,
-                                j: self.state.j,
-                                state: self.state.line.clone(),
-                            });
+                            let mut state = self.state.line.clone();
+                            // A hyphen is never displayed at the start of a line,
+                            // which begins after a previous hyphenation point.
+                            let is_hyphenation_point = self.state.j > state.clusters.start
+                                && self.layout.clusters[self.state.j - 1].is_hyphenation_point();
+                            if is_hyphenation_point {
+                                // Breaking here displays a hyphen, so the break is only
+                                // usable if the hyphen fits.
+                                state.x += self.layout.hyphen_advance(self.state.j - 1);
+                                state.hyphenated = true;
+                            }
+                            if state.x <= max_advance || !is_hyphenation_point {
+                                self.state.prev_boundary = Some(PrevBoundaryState {
+                                    i: self.state.i,
+                                    j: self.state.j,
+                                    state,
+                                });
+                            }
                         }
                     }
                     _ => {}
                 }
                 self.state.line.skip_mandatory_break = false;
                 let mut advance = cluster.advance();
+                if self.layout.clusters[self.state.j].is_tab() {
+                    advance = tab_advance(self.layout, self.state.j, self.state.line.x);
+                    // Tabs are revisited after a line is rewrapped.
+                    while self
+                        .tab_advances
+                        .last()
+                        .is_some_and(|(index, _)| *index >= self.state.j)
+                    {
+                        self.tab_advances.pop();
+                    }
+                    self.tab_advances.push((self.state.j, advance));
+                }
                 if cluster.is_ligature_start() {
-                    while let Some(cluster) = run.get(self.state.j + 1) {
+                    while let Some(cluster) = run.get(self.state.j + 1 - cluster_start) {
                         if !cluster.is_ligature_continuation() {
                             break;
                         } else {
@@ -120,7 +307,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                 if next_x > max_advance {
                     if is_space {
                         // Hang overflowing whitespace
-                        self.state.line.runs.end = self.state.i + 1;
+                        self.state.line.items.end = self.state.i + 1;
                         self.state.line.clusters.end = self.state.j + 1;
                         self.state.line.x = next_x;
                         if commit_line(
@@ -132,7 +319,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                             BreakReason::Regular,
                             false,
                         ) {
-                            self.state.runs = self.lines.runs.len();
+                            self.state.items = self.lines.items.len();
                             self.state.lines = self.lines.lines.len();
                             self.state.line.x = 0.;
                             let line = self.lines.lines.last().unwrap();
@@ -143,7 +330,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                     } else if let Some(prev) = self.state.prev_boundary.take() {
                         if prev.state.x == 0. {
                             // This will cycle if we try to rewrap. Accept the overflowing fragment.
-                            self.state.line.runs.end = self.state.i + 1;
+                            self.state.line.items.end = self.state.i + 1;
                             self.state.line.clusters.end = self.state.j + 1;
                             self.state.line.x = next_x;
                             self.state.j += 1;
@@ -156,12 +343,11 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                                 BreakReason::Emergency,
                                 false,
                             ) {
-                                self.state.runs = self.lines.runs.len();
+                                self.state.items = self.lines.items.len();
                                 self.state.lines = self.lines.lines.len();
                                 self.state.line.x = 0.;
                                 let line = self.lines.lines.last().unwrap();
                                 self.state.prev_boundary = None;
-                                self.state.j += 1;
                                 return Some((line.metrics.advance, line.size()));
                             }
                         } else {
@@ -175,7 +361,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                                 BreakReason::Regular,
                                 false,
                             ) {
-                                self.state.runs = self.lines.runs.len();
+                                self.state.items = self.lines.items.len();
                                 self.state.lines = self.lines.lines.len();
                                 self.state.line.x = 0.;
                                 let line = self.lines.lines.last().unwrap();
@@ -189,7 +375,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                             // If we're at the start of the line, this particular
                             // cluster will never fit, so consume it and accept
                             // the overflow.
-                            self.state.line.runs.end = self.state.i + 1;
+                            self.state.line.items.end = self.state.i + 1;
                             self.state.line.clusters.end = self.state.j + 1;
                             self.state.line.x = next_x;
                             self.state.j += 1;
@@ -203,24 +389,20 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                             BreakReason::Emergency,
                             false,
                         ) {
-                            self.state.runs = self.lines.runs.len();
+                            self.state.items = self.lines.items.len();
                             self.state.lines = self.lines.lines.len();
                             self.state.line.x = 0.;
                             let line = self.lines.lines.last().unwrap();
                             self.state.prev_boundary = None;
-                            self.state.j += 1;
                             return Some((line.metrics.advance, line.size()));
                         }
                     }
                 } else {
                     // Commit the cluster to the line.
-                    self.state.line.runs.end = self.state.i + 1;
+                    self.state.line.items.end = self.state.i + 1;
                     self.state.line.clusters.end = self.state.j + 1;
                     self.state.line.x = next_x;
                     self.state.j += 1;
-                    if is_space {
-                        self.state.line.num_spaces += 1;
-                    }
                 }
             }
             self.state.i += 1;
@@ -234,7 +416,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
             BreakReason::None,
             true,
         ) {
-            self.state.runs = self.lines.runs.len();
+            self.state.items = self.lines.items.len();
             self.state.lines = self.lines.lines.len();
             self.state.line.x = 0.;
             let line = self.lines.lines.last().unwrap();
@@ -249,7 +431,7 @@ impl<'a, B: Brush> BreakLines<'a, B> {
         if let Some(state) = self.prev_state.take() {
             self.state = state;
             self.lines.lines.truncate(self.state.lines);
-            self.lines.runs.truncate(self.state.runs);
+            self.lines.items.truncate(self.state.items);
             self.done = false;
             true
         } else {
@@ -260,13 +442,82 @@ impl<'a, B: Brush> BreakLines<'a, B> {
     /// Breaks all remaining lines with the specified maximum advance. This
     /// consumes the line breaker.
     pub fn break_remaining(mut self, max_advance: f32, alignment: Alignment) {
-        while self.break_next(max_advance, alignment).is_some() {}
+        while !self.exceeds_max_lines() && self.break_next(max_advance, alignment).is_some() {}
+        self.finish();
+    }
+
+    /// Breaks all remaining lines, querying the space available to each line
+    /// from the offset of its top edge along the block axis. The function
+    /// returns the offset of the start of the space along the inline axis
+    /// and its width. This consumes the line breaker.
+    ///
+    /// The space is queried before the height of a line is known, so content
+    /// that begins partway down a line should be accounted for by the
+    /// function.
+    pub fn break_remaining_around(
+        mut self,
+        mut available: impl FnMut(f32) -> (f32, f32),
+        alignment: Alignment,
+    ) {
+        let mut y = self.lines.lines.iter().fold(0., |y, line| y + line.size());
+        while !self.exceeds_max_lines() {
+            let (offset, max_advance) = available(y);
+            let Some((_, size)) = self.break_next_at(offset, max_advance, alignment) else {
+                break;
+            };
+            y += size;
+        }
+        self.finish();
+    }
+
+    /// Breaks all remaining lines with the specified maximum advance,
+    /// choosing the break positions that minimize the total badness of the
+    /// lines in the paragraph. This consumes the line breaker.
+    pub fn break_remaining_optimal(mut self, max_advance: f32, alignment: Alignment) {
+        if !max_advance.is_finite() || max_advance == f32::MAX || self.layout.text_len == 0 {
+            // Without a constraint, the greedy result is optimal.
+            self.break_remaining(max_advance, alignment);
+            return;
+        }
+        if !self.done {
+            let lines =
+                super::optimal::break_optimal(self.layout, self.state.i, self.state.j, max_advance);
+            let count = lines.len();
+            for (index, (mut line, break_reason)) in lines.into_iter().enumerate() {
+                let max_advance = max_advance - line.indent;
+                commit_line(
+                    self.layout,
+                    &mut self.lines,
+                    &mut line,
+                    max_advance,
+                    alignment,
+                    break_reason,
+                    index + 1 == count,
+                );
+            }
+            self.done = true;
+        }
         self.finish();
     }
 
     /// Consumes the line breaker and finalizes all line computations.
     pub fn finish(mut self) {
-        for run in &mut self.lines.runs {
+        for &(index, advance) in &self.tab_advances {
+            self.layout.set_cluster_advance(index, advance);
+        }
+        if let Some((max_lines, overflow)) = self.max_lines {
+            if self.exceeds_max_lines() {
+                truncate(self.layout, &mut self.lines, max_lines, overflow);
+            }
+        }
+        let (first_line, first_item) = self
+            .gap
+            .as_ref()
+            .map_or((0, 0), |gap| (gap.lines, gap.items));
+        for run in &mut self.lines.items[first_item..] {
+            if !run.is_text_run() || run.is_ellipsis {
+                continue;
+            }
             run.is_whitespace = true;
             if run.bidi_level & 1 != 0 {
                 // RTL runs check for "trailing" whitespace at the front.
@@ -289,44 +540,69 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                 }
             }
         }
-        let mut y = 0.;
-        for line in &mut self.lines.lines {
-            let run_base = line.run_range.start;
-            let run_count = line.run_range.end - run_base;
-            line.metrics.ascent = 0.;
-            line.metrics.descent = 0.;
-            line.metrics.leading = 0.;
-            line.metrics.offset = 0.;
-            let mut have_metrics = false;
+        let mut y = first_line
+            .checked_sub(1)
+            .map_or(0., |index| self.lines.lines[index].metrics.max_coord);
+        for line in &mut self.lines.lines[first_line..] {
+            let item_count = line.item_range.len();
+            let above;
+            (
+                line.metrics.ascent,
+                line.metrics.descent,
+                line.metrics.leading,
+                above,
+            ) = line_metrics(
+                self.layout,
+                &self.lines.items[line.item_range.clone()],
+                line.line_height_step,
+            );
+            line.metrics.offset = line.metrics.span_offset;
+            let mut have_advance = false;
             let mut needs_reorder = false;
             line.text_range.start = usize::MAX;
-            // Compute metrics for the line, but ignore trailing whitespace.
-            for line_run in self.lines.runs[line.run_range.clone()].iter_mut().rev() {
-                line.text_range.end = line.text_range.end.max(line_run.text_range.end);
-                line.text_range.start = line.text_range.start.min(line_run.text_range.start);
-                if line_run.bidi_level != 0 {
+            // Compute advances for the line, but ignore trailing whitespace.
+            for line_item in self.lines.items[line.item_range.clone()].iter_mut().rev() {
+                line.text_range.end = line.text_range.end.max(line_item.text_range.end);
+                line.text_range.start = line.text_range.start.min(line_item.text_range.start);
+                if line_item.bidi_level != 0 {
                     needs_reorder = true;
                 }
-                if !have_metrics && line_run.is_whitespace {
+                if line_item.kind == LayoutItemKind::InlineBox {
+                    line_item.advance = self.layout.inline_box_advance(line_item.index);
+                    have_advance = true;
+                    continue;
+                }
+                let line_run = line_item;
+                if !have_advance && line_run.is_whitespace {
                     continue;
                 }
                 line_run.advance = self.layout.clusters[line_run.cluster_range.clone()]
                     .iter()
                     .map(|c| c.advance)
                     .sum();
-                let line_height = line_run.compute_line_height(self.layout);
-                let run = &self.layout.runs[line_run.run_index];
-                line.metrics.ascent = line.metrics.ascent.max(run.metrics.ascent * line_height);
-                line.metrics.descent = line.metrics.descent.max(run.metrics.descent * line_height);
-                line.metrics.leading = line.metrics.leading.max(run.metrics.leading * line_height);
-                have_metrics = true;
+                if line_run.is_hyphenated {
+                    line_run.advance += self.layout.runs[line_run.index].hyphen_advance;
+                }
+                if line_run.is_ellipsis {
+                    line_run.advance += self.layout.ellipsis.iter().map(|g| g.advance).sum::<f32>();
+                }
+                have_advance = true;
             }
-            if needs_reorder && run_count > 1 {
-                reorder_runs(&mut self.lines.runs[line.run_range.clone()]);
+            if needs_reorder && item_count > 1 {
+                reorder_items(&mut self.lines.items[line.item_range.clone()]);
             }
-            let trailing_whitespace = if !line.run_range.is_empty() {
-                let last_run = &self.lines.runs[line.run_range.end - 1];
-                if !last_run.cluster_range.is_empty() {
+            let is_rtl = line.base_level & 1 != 0;
+            // Trailing whitespace is at the visual start of a right-to-left
+            // line.
+            let trailing_whitespace = if !line.item_range.is_empty() {
+                let last_run = if is_rtl {
+                    &self.lines.items[line.item_range.start]
+                } else {
+                    &self.lines.items[line.item_range.end - 1]
+                };
+                // The logically last cluster of a run in the opposite
+                // direction is not at the edge of the line.
+                if !last_run.cluster_range.is_empty() && (last_run.bidi_level & 1 != 0) == is_rtl {
                     let cluster = &self.layout.clusters[last_run.cluster_range.end - 1];
                     if cluster.info.whitespace().is_space_or_nbsp() {
                         cluster.advance
@@ -340,74 +616,49 @@ impl<'a, B: Brush> BreakLines<'a, B> {
                 0.
             };
             line.metrics.trailing_whitespace = trailing_whitespace;
-            if line.alignment != Alignment::Start
-                && line.max_advance.is_finite()
-                && line.max_advance < f32::MAX
-            {
+            if line.max_advance.is_finite() && line.max_advance < f32::MAX {
                 let extra = line.max_advance - line.metrics.advance + trailing_whitespace;
                 if extra > 0. {
-                    let offset = if line.alignment == Alignment::Middle {
-                        extra * 0.5
-                    } else {
-                        extra
-                    };
                     if line.alignment == Alignment::Justified {
-                        if line.break_reason != BreakReason::None && line.num_spaces != 0 {
-                            let adjustment = extra / line.num_spaces as f32;
-                            let mut applied = 0;
-                            for line_run in &self.lines.runs[line.run_range.clone()] {
-                                if line_run.bidi_level & 1 != 0 {
-                                    for cluster in self.layout.clusters
-                                        [line_run.cluster_range.clone()]
-                                    .iter_mut()
-                                    .rev()
-                                    {
-                                        if applied == line.num_spaces {
-                                            break;
-                                        }
-                                        if cluster.info.whitespace().is_space_or_nbsp() {
-                                            cluster.advance += adjustment;
-                                            applied += 1;
-                                        }
-                                    }
-                                } else {
-                                    for cluster in self.layout.clusters
-                                        [line_run.cluster_range.clone()]
-                                    .iter_mut()
-                                    {
-                                        if applied == line.num_spaces {
-                                            break;
-                                        }
-                                        if cluster.info.whitespace().is_space_or_nbsp() {
-                                            cluster.advance += adjustment;
-                                            applied += 1;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        line.metrics.offset = offset;
+                        line.justification = justify(
+                            self.layout,
+                            &mut self.lines.items[line.item_range.clone()],
+                            line.justify,
+                            extra,
+                        );
+                    }
+                    if line.justification == 0. {
+                        line.metrics.offset += extra * line.alignment.left_fraction(is_rtl);
+                    }
+                    if is_rtl {
+                        // Trailing whitespace hangs off the visual start.
+                        line.metrics.offset -= trailing_whitespace;
                     }
                 }
             }
-            if !have_metrics {
-                // Line consisting entirely of whitespace?
-                if !line.run_range.is_empty() {
-                    let line_run = &self.lines.runs[line.run_range.start];
-                    let run = &self.layout.runs[line_run.run_index];
-                    line.metrics.ascent = run.metrics.ascent;
-                    line.metrics.descent = run.metrics.descent;
-                    line.metrics.leading = run.metrics.leading;
-                }
+            if !is_rtl {
+                // The indentation of a right-to-left line is excluded from
+                // its maximum advance at the right edge.
+                line.metrics.offset += line.indent;
             }
-            line.metrics.ascent = line.metrics.ascent.round();
-            line.metrics.descent = line.metrics.descent.round();
-            line.metrics.leading = (line.metrics.leading * 0.5).round() * 2.;
-            let above = (line.metrics.ascent + line.metrics.leading * 0.5).round();
-            let below = (line.metrics.descent + line.metrics.leading * 0.5).round();
+            line.metrics.min_coord = y;
             line.metrics.baseline = y + above;
-            y = line.metrics.baseline + below;
+            y += line.size();
+            line.metrics.max_coord = y;
+        }
+        if let Some(gap) = self.gap.take() {
+            // Move the retained lines below the recomputed lines.
+            let items = self.lines.items.len();
+            self.lines.items.extend(gap.suffix.items);
+            for mut line in gap.suffix.lines {
+                line.item_range = line.item_range.start + items..line.item_range.end + items;
+                let shift = y - line.metrics.min_coord;
+                line.metrics.min_coord += shift;
+                line.metrics.baseline += shift;
+                line.metrics.max_coord += shift;
+                y = line.metrics.max_coord;
+                self.lines.lines.push(line);
+            }
         }
     }
 }
@@ -418,66 +669,442 @@ impl<'a, B: Brush> Drop for BreakLines<'a, B> {
         let mut full_width = 0f32;
         let mut height = 0f32;
         for line in &self.lines.lines {
-            width = width.max(line.metrics.advance - line.metrics.trailing_whitespace);
-            full_width = full_width.max(line.metrics.advance);
+            let start = line.metrics.span_offset + line.indent;
+            width = width.max(start + line.metrics.advance - line.metrics.trailing_whitespace);
+            full_width = full_width.max(start + line.metrics.advance);
             height += line.metrics.size();
         }
         self.layout.width = width;
         self.layout.full_width = full_width;
         self.layout.height = height;
         self.lines.swap(self.layout);
+        self.layout.index_line_runs();
     }
 }
 
-/// Removes previous justification applied to clusters.
-fn unjustify<B: Brush>(layout: &mut LayoutData<B>) {
-    for line in &layout.lines {
-        if line.alignment == Alignment::Justified
-            && line.max_advance.is_finite()
-            && line.max_advance < f32::MAX
+/// Computes the rounded ascent, descent and leading of a line from its items
+/// in logical order, ignoring trailing whitespace, along with the extent of
+/// the line above its baseline. If `step` is nonzero, the leading is
+/// increased so that the height of the line is a multiple of it.
+fn line_metrics<B: Brush>(
+    layout: &LayoutData<B>,
+    items: &[LineItemData],
+    step: f32,
+) -> (f32, f32, f32, f32) {
+    // Metrics of content with a line height relative to the font metrics.
+    let (mut ascent, mut descent, mut leading) = (0f32, 0f32, 0f32);
+    // Metrics of the remaining content, which has half of its leading on
+    // either side of the text.
+    let (mut text_ascent, mut text_descent) = (0f32, 0f32);
+    let (mut above, mut below) = (f32::MIN, f32::MIN);
+    let (mut have_relative, mut have_half_leading) = (false, false);
+    let mut have_metrics = false;
+    for item in items.iter().rev() {
+        if item.kind == LayoutItemKind::InlineBox {
+            let inline_box = &layout.inline_boxes[item.index];
+            let (box_ascent, box_descent) = if layout.writing_mode.is_vertical() {
+                // Boxes are centered on the baseline of vertical lines.
+                (inline_box.width * 0.5, inline_box.width * 0.5)
+            } else {
+                (inline_box.baseline, inline_box.descent())
+            };
+            ascent = ascent.max(box_ascent);
+            descent = descent.max(box_descent);
+            have_relative = true;
+            have_metrics = true;
+            continue;
+        }
+        if !have_metrics
+            && !item.is_ellipsis
+            && layout.clusters[item.cluster_range.clone()]
+                .iter()
+                .all(|cluster| cluster.info.is_whitespace())
         {
-            let extra = line.max_advance - line.metrics.advance + line.metrics.trailing_whitespace;
-            if line.break_reason != BreakReason::None && line.num_spaces != 0 {
-                let adjustment = extra / line.num_spaces as f32;
-                let mut applied = 0;
-                for line_run in &layout.line_runs[line.run_range.clone()] {
-                    if line_run.bidi_level & 1 != 0 {
-                        for cluster in layout.clusters[line_run.cluster_range.clone()]
-                            .iter_mut()
-                            .rev()
-                        {
-                            if applied == line.num_spaces {
-                                break;
-                            }
-                            if cluster.info.whitespace().is_space_or_nbsp() {
-                                cluster.advance -= adjustment;
-                                applied += 1;
-                            }
-                        }
-                    } else {
-                        for cluster in layout.clusters[line_run.cluster_range.clone()].iter_mut() {
-                            if applied == line.num_spaces {
-                                break;
-                            }
-                            if cluster.info.whitespace().is_space_or_nbsp() {
-                                cluster.advance -= adjustment;
-                                applied += 1;
-                            }
-                        }
-                    }
+            continue;
+        }
+        let run = &layout.runs[item.index];
+        let metrics = &run.metrics;
+        item.for_each_line_height(layout, |line_height| {
+            let height = match line_height {
+                LineHeight::MetricsRelative(factor) => {
+                    ascent = ascent.max(metrics.ascent * factor);
+                    descent = descent.max(metrics.descent * factor);
+                    leading = leading.max(metrics.leading * factor);
+                    have_relative = true;
+                    return;
                 }
+                LineHeight::FontSizeRelative(factor) => run.font_size * factor,
+                LineHeight::Absolute(height) => height,
+            };
+            let half_leading = (height - metrics.ascent - metrics.descent) * 0.5;
+            text_ascent = text_ascent.max(metrics.ascent);
+            text_descent = text_descent.max(metrics.descent);
+            above = above.max(metrics.ascent + half_leading);
+            below = below.max(metrics.descent + half_leading);
+            have_half_leading = true;
+        });
+        have_metrics = true;
+    }
+    if !have_metrics {
+        // Line consisting entirely of whitespace?
+        if let Some(item) = items.iter().find(|item| item.is_text_run()) {
+            let run = &layout.runs[item.index];
+            ascent = run.metrics.ascent;
+            descent = run.metrics.descent;
+            leading = run.metrics.leading;
+            have_relative = true;
+        }
+    }
+    let half_leading = (leading * 0.5).round();
+    let mut extents = have_relative.then(|| {
+        (
+            ascent.round() + half_leading,
+            descent.round() + half_leading,
+        )
+    });
+    if have_half_leading {
+        // The height is rounded as a whole so that a fractional half-leading
+        // is not rounded up on both sides of the text.
+        let text_above = above.round();
+        let text_below = (above + below).round() - text_above;
+        extents = Some(extents.map_or((text_above, text_below), |(above, below)| {
+            (above.max(text_above), below.max(text_below))
+        }));
+    }
+    let (mut above, below) = extents.unwrap_or_default();
+    let ascent = ascent.max(text_ascent).round();
+    let descent = descent.max(text_descent).round();
+    let mut leading = above + below - ascent - descent;
+    if step > 0. {
+        let height = above + below;
+        let extra = (height / step).ceil() * step - height;
+        above += (extra * 0.5).round();
+        leading += extra;
+    }
+    (ascent, descent, leading, above)
+}
+
+/// Returns the indentation of a line that starts at the specified item and
+/// cluster.
+pub(super) fn line_indent<B: Brush>(
+    layout: &LayoutData<B>,
+    item_index: usize,
+    cluster_index: usize,
+) -> f32 {
+    let line_start = match layout.items.get(item_index) {
+        Some(item) if item.kind == LayoutItemKind::InlineBox => {
+            layout.inline_boxes[item.index].index
+        }
+        _ => layout.cluster_text_start(cluster_index),
+    };
+    layout.line_indent(line_start)
+}
+
+/// Returns the advance of the tab at the specified cluster index that moves
+/// the following text to the next tab stop after `x`.
+fn tab_advance<B: Brush>(layout: &LayoutData<B>, cluster_index: usize, x: f32) -> f32 {
+    let Ok(index) = layout
+        .tabs
+        .binary_search_by_key(&cluster_index, |tab| tab.cluster)
+    else {
+        return layout.clusters[cluster_index].advance;
+    };
+    let tab = &layout.tabs[index];
+    let stop = layout.tab_stops[tab.stops.clone()]
+        .iter()
+        .find(|stop| stop.position > x);
+    let Some(stop) = stop else {
+        if tab.interval <= 0. {
+            return 0.;
+        }
+        return ((x / tab.interval).floor() + 1.) * tab.interval - x;
+    };
+    let width = |end: usize| -> f32 {
+        layout.clusters[cluster_index + 1..end]
+            .iter()
+            .map(|cluster| cluster.advance)
+            .sum()
+    };
+    let offset = match stop.alignment {
+        TabAlignment::Start => 0.,
+        TabAlignment::Center => width(tab.end) * 0.5,
+        TabAlignment::End => width(tab.end),
+        TabAlignment::Decimal(separator) => width(
+            layout.tab_decimals[tab.decimals.clone()]
+                .iter()
+                .find(|(ch, _)| *ch == separator)
+                .map_or(tab.end, |(_, position)| *position),
+        ),
+    };
+    (stop.position - offset - x).max(0.)
+}
+
+/// Removes previous justification applied to clusters.
+pub(crate) fn unjustify<B: Brush>(layout: &mut LayoutData<B>) {
+    let mut opportunities = Vec::new();
+    for index in 0..layout.lines.len() {
+        let line = &layout.lines[index];
+        if line.justification == 0. {
+            continue;
+        }
+        let adjustment = -line.justification;
+        let is_kashida = justification_opportunities(
+            layout,
+            &layout.line_items[line.item_range.clone()],
+            line.justify,
+            &mut opportunities,
+        );
+        adjust_clusters(layout, &opportunities, is_kashida, adjustment);
+        layout.lines[index].justification = 0.;
+    }
+}
+
+/// Distributes the extra space on a line over its justification
+/// opportunities. Returns the advance added at each opportunity, or zero if
+/// the line has none.
+fn justify<B: Brush>(
+    layout: &mut LayoutData<B>,
+    items: &mut [LineItemData],
+    method: TextJustify,
+    extra: f32,
+) -> f32 {
+    let mut opportunities = Vec::new();
+    let is_kashida = justification_opportunities(layout, items, method, &mut opportunities);
+    if opportunities.is_empty() {
+        return 0.;
+    }
+    let adjustment = extra / opportunities.len() as f32;
+    adjust_clusters(layout, &opportunities, is_kashida, adjustment);
+    if is_kashida {
+        for item in items {
+            item.kashida = adjustment;
+        }
+    }
+    adjustment
+}
+
+/// Collects the clusters of a line that are followed by a justification
+/// opportunity. Returns true if the opportunities are kashida points.
+///
+/// Opportunities are never placed after the logically last cluster of the
+/// line that is not whitespace, so trailing whitespace is never expanded.
+fn justification_opportunities<B: Brush>(
+    layout: &LayoutData<B>,
+    items: &[LineItemData],
+    method: TextJustify,
+    opportunities: &mut Vec<usize>,
+) -> bool {
+    opportunities.clear();
+    // Lines always contain a contiguous range of clusters.
+    let (mut start, mut end) = (usize::MAX, 0);
+    for item in items.iter().filter(|item| item.is_text_run()) {
+        if !item.cluster_range.is_empty() {
+            start = start.min(item.cluster_range.start);
+            end = end.max(item.cluster_range.end);
+        }
+    }
+    let clusters = &layout.clusters;
+    while end > start && clusters[end - 1].info.is_whitespace() {
+        end -= 1;
+    }
+    if start >= end {
+        return false;
+    }
+    if method == TextJustify::Kashida {
+        opportunities.extend((start..end - 1).filter(|&i| clusters[i].is_kashida_point()));
+        if !opportunities.is_empty() {
+            return true;
+        }
+    }
+    for i in start..end {
+        let cluster = &clusters[i];
+        let is_character_gap = match method {
+            TextJustify::InterCharacter => true,
+            TextJustify::Auto => cluster.is_unspaced(),
+            _ => false,
+        };
+        if is_character_gap {
+            if i + 1 == end || clusters[i + 1].is_ligature_component() || cluster.is_tab() {
+                continue;
+            }
+            // The space following a ligature is added to the cluster that
+            // holds its glyphs.
+            let mut owner = i;
+            while owner > start && clusters[owner].is_ligature_component() {
+                owner -= 1;
+            }
+            if clusters[owner].glyph_len != 0 {
+                opportunities.push(owner);
+            }
+        } else if cluster.info.whitespace().is_space_or_nbsp() {
+            opportunities.push(i);
+        }
+    }
+    false
+}
+
+/// Adds the adjustment to the advances of the specified clusters. For
+/// kashidas, the adjustment is the width of the tatweel glyphs inserted
+/// after each cluster rather than space added to its glyphs.
+fn adjust_clusters<B: Brush>(
+    layout: &mut LayoutData<B>,
+    clusters: &[usize],
+    is_kashida: bool,
+    adjustment: f32,
+) {
+    for &index in clusters {
+        let advance = layout.clusters[index].advance + adjustment;
+        if is_kashida {
+            let cluster = &mut layout.clusters[index];
+            cluster.advance = advance;
+            if adjustment > 0. {
+                cluster.flags |= ClusterData::KASHIDA;
+            } else {
+                cluster.flags &= !ClusterData::KASHIDA;
+            }
+        } else {
+            layout.set_cluster_advance(index, advance);
+        }
+    }
+}
+
+/// Removes the lines following the first `max_lines` and, for
+/// [`TextOverflow::Ellipsis`], replaces the end of the last remaining line with
+/// an ellipsis.
+fn truncate<B: Brush>(
+    layout: &mut LayoutData<B>,
+    lines: &mut LineLayout,
+    max_lines: usize,
+    overflow: TextOverflow,
+) {
+    lines.lines.truncate(max_lines);
+    let Some(line) = lines.lines.last_mut() else {
+        lines.items.clear();
+        return;
+    };
+    lines.items.truncate(line.item_range.end);
+    if overflow != TextOverflow::Ellipsis {
+        return;
+    }
+    let items = &lines.items[line.item_range.clone()];
+    // The ellipsis uses the font of the logically last text on the line.
+    let Some((run_index, style_index)) = items
+        .iter()
+        .rev()
+        .find(|item| item.is_text_run())
+        .map(|item| {
+            let cluster = item.cluster_range.end.max(1) - 1;
+            (item.index, cluster)
+        })
+        .or_else(|| (!layout.runs.is_empty()).then_some((0, 0)))
+        .map(|(run_index, cluster)| {
+            let style_index = layout
+                .clusters
+                .get(cluster)
+                .map(|cluster| cluster.style_index)
+                .unwrap_or_default();
+            (run_index, style_index)
+        })
+    else {
+        return;
+    };
+    layout.ellipsis = layout.ellipsis_glyphs(run_index, style_index);
+    let ellipsis_advance: f32 = layout.ellipsis.iter().map(|g| g.advance).sum();
+    let available = if line.max_advance.is_finite() {
+        line.max_advance - ellipsis_advance
+    } else {
+        f32::MAX
+    };
+    // Find the longest prefix of the line, in logical order, that fits with
+    // the ellipsis and does not end with whitespace. The cut is recorded as
+    // the number of whole items retained along with the cluster end of a
+    // partially retained text run.
+    let mut cut = (0, None);
+    let mut cut_x = 0.;
+    let mut x = 0.;
+    'outer: for (item_index, item) in items.iter().enumerate() {
+        if item.kind == LayoutItemKind::InlineBox {
+            x += layout.inline_box_advance(item.index);
+            if x > available {
+                break;
+            }
+            cut = (item_index + 1, None);
+            cut_x = x;
+            continue;
+        }
+        for j in item.cluster_range.clone() {
+            let cluster = &layout.clusters[j];
+            x += cluster.advance;
+            let ends_ligature =
+                j + 1 == item.cluster_range.end || !layout.clusters[j + 1].is_ligature_component();
+            if !ends_ligature {
+                continue;
+            }
+            if x > available {
+                break 'outer;
+            }
+            if !cluster.info.is_whitespace() {
+                cut = (item_index, Some(j + 1));
+                cut_x = x;
             }
         }
     }
+    let (whole_items, cluster_end) = cut;
+    let mut end = line.item_range.start + whole_items;
+    let mut text_end = items.first().map(|item| item.text_range.start);
+    if let Some(cluster_end) = cluster_end {
+        let item = &mut lines.items[end];
+        let cluster = &layout.clusters[cluster_end - 1];
+        item.cluster_range.end = cluster_end;
+        item.text_range.end = cluster.text_range(&layout.runs[item.index]).end;
+        end += 1;
+    }
+    lines.items.truncate(end);
+    for item in &mut lines.items[line.item_range.start..] {
+        item.is_hyphenated = false;
+        text_end = text_end.max(Some(item.text_range.end));
+    }
+    let text_end = text_end.unwrap_or_default();
+    let cluster_end = cluster_end.unwrap_or_else(|| {
+        lines.items[line.item_range.start..]
+            .iter()
+            .map(|item| item.cluster_range.end)
+            .max()
+            .unwrap_or_default()
+    });
+    // The ellipsis is placed at the end of the line in the direction of the
+    // paragraph.
+    lines.items.push(LineItemData {
+        kind: LayoutItemKind::TextRun,
+        index: run_index,
+        bidi_level: line.base_level,
+        text_range: text_end..text_end,
+        cluster_range: cluster_end..cluster_end,
+        is_ellipsis: true,
+        ..Default::default()
+    });
+    line.item_range.end = lines.items.len();
+    line.metrics.advance = cut_x + ellipsis_advance;
+    if line.alignment == Alignment::Justified {
+        // The ellipsis ends the line, so the line is not stretched.
+        line.alignment = Alignment::Start;
+    }
 }
 
 #[derive(Clone, Default)]
-struct LineState {
-    x: f32,
-    runs: Range<usize>,
-    clusters: Range<usize>,
-    skip_mandatory_break: bool,
-    num_spaces: usize,
+pub(super) struct LineState {
+    pub(super) x: f32,
+    pub(super) items: Range<usize>,
+    pub(super) clusters: Range<usize>,
+    pub(super) skip_mandatory_break: bool,
+    /// True if the line ends at a hyphenation point.
+    pub(super) hyphenated: bool,
+    /// Indentation of the line from the start edge of the paragraph.
+    pub(super) indent: f32,
+    /// Offset of the start of the space available to the line.
+    pub(super) span_offset: f32,
+    /// Width of the space available to the line.
+    pub(super) span_width: f32,
 }
 
 #[derive(Clone, Default)]
@@ -489,7 +1116,7 @@ struct PrevBoundaryState {
 
 #[derive(Clone, Default)]
 struct BreakerState {
-    runs: usize,
+    items: usize,
     lines: usize,
     i: usize,
     j: usize,
@@ -508,20 +1135,27 @@ fn commit_line<B: Brush>(
 ) -> bool {
     let is_empty = layout.text_len == 0;
     state.clusters.end = state.clusters.end.min(layout.clusters.len());
-    if state.runs.end == 0 && is_last {
-        state.runs.end = 1;
+    if state.items.end == 0 && is_last {
+        state.items.end = 1;
     }
-    let last_run = state.runs.len() - 1;
-    let runs_start = lines.runs.len();
-    for (i, run_data) in layout.runs[state.runs.clone()].iter().enumerate() {
-        let run_index = state.runs.start + i;
-        let mut cluster_range = run_data.cluster_range.clone();
-        if i == 0 {
-            cluster_range.start = state.clusters.start;
-        }
-        if i == last_run {
-            cluster_range.end = state.clusters.end;
+    let items_start = lines.items.len();
+    for item in &layout.items[state.items.clone()] {
+        if item.kind == LayoutItemKind::InlineBox {
+            let index = layout.inline_boxes[item.index].index;
+            lines.items.push(LineItemData {
+                kind: LayoutItemKind::InlineBox,
+                index: item.index,
+                bidi_level: item.bidi_level,
+                text_range: index..index,
+                cluster_range: state.clusters.start..state.clusters.start,
+                ..Default::default()
+            });
+            continue;
         }
+        let run_data = &layout.runs[item.index];
+        let mut cluster_range = run_data.cluster_range.clone();
+        cluster_range.start = cluster_range.start.max(state.clusters.start);
+        cluster_range.end = cluster_range.end.min(state.clusters.end);
         if cluster_range.start > cluster_range.end
             || (!is_empty && cluster_range.start == cluster_range.end)
         {
@@ -539,43 +1173,80 @@ fn commit_line<B: Brush>(
                 .unwrap();
             first_cluster.text_range().start..last_cluster.text_range().end
         };
-        let line_run = LineRunData {
-            run_index,
+        let line_run = LineItemData {
+            kind: LayoutItemKind::TextRun,
+            index: item.index,
             bidi_level: run_data.bidi_level,
-            is_whitespace: false,
-            has_trailing_whitespace: false,
             cluster_range,
             text_range,
-            advance: 0.,
+            ..Default::default()
         };
-        lines.runs.push(line_run);
+        lines.items.push(line_run);
     }
-    let runs_end = lines.runs.len();
-    if runs_start == runs_end {
+    let items_end = lines.items.len();
+    if items_start == items_end {
         return false;
     }
-    let mut num_spaces = state.num_spaces;
-    if break_reason == BreakReason::Regular {
-        num_spaces = num_spaces.saturating_sub(1);
+    if state.hyphenated {
+        if let Some(item) = lines.items[items_start..items_end]
+            .iter_mut()
+            .rev()
+            .find(|item| item.is_text_run())
+        {
+            item.is_hyphenated = true;
+        }
+    }
+    // Lines never span paragraphs, so the paragraph is determined by the
+    // logically first item.
+    let paragraph = layout.paragraph_at(lines.items[items_start].text_range.start);
+    let mut alignment = paragraph
+        .and_then(|paragraph| paragraph.alignment)
+        .unwrap_or(alignment);
+    if matches!(break_reason, BreakReason::None | BreakReason::Explicit) {
+        // The line ends the paragraph or is followed by an explicit break.
+        alignment = paragraph
+            .and_then(|paragraph| paragraph.alignment_last)
+            .unwrap_or(match alignment {
+                Alignment::Justified => Alignment::Start,
+                alignment => alignment,
+            });
     }
     let mut line = LineData {
-        run_range: runs_start..runs_end,
+        item_range: items_start..items_end,
         max_advance,
+        indent: state.indent,
         alignment,
-        break_reason,
-        num_spaces,
+        justify: paragraph.map_or(TextJustify::Auto, |paragraph| paragraph.justify),
+        base_level: paragraph.map_or(0, |paragraph| paragraph.base_level),
+        line_height_step: paragraph.map_or(0., |paragraph| paragraph.line_height_step),
         ..Default::default()
     };
     line.metrics.advance = state.x;
+    line.metrics.span_offset = state.span_offset;
+    line.metrics.span_width = state.span_width;
+    (
+        line.metrics.ascent,
+        line.metrics.descent,
+        line.metrics.leading,
+        _,
+    ) = line_metrics(
+        layout,
+        &lines.items[items_start..items_end],
+        line.line_height_step,
+    );
     lines.lines.push(line);
     state.clusters.start = state.clusters.end;
     state.clusters.end += 1;
-    state.runs.start = state.runs.end - 1;
-    state.num_spaces = 0;
+    // A text run may continue on the next line, but an inline box never will.
+    state.items.start = match layout.items.get(state.items.end - 1) {
+        Some(item) if item.kind == LayoutItemKind::InlineBox => state.items.end,
+        _ => state.items.end - 1,
+    };
+    state.hyphenated = false;
     true
 }
 
-fn reorder_runs(runs: &mut [LineRunData]) {
+fn reorder_items(runs: &mut [LineItemData]) {
     let mut max_level = 0;
     let mut lowest_odd_level = 255;
     let len = runs.len();
@@ -609,3 +1280,714 @@ fn reorder_runs(runs: &mut [LineRunData]) {
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::context::LayoutContext;
+    use crate::layout::{
+        Alignment, BreakStrategy, Cursor, InlineBox, Layout, PositionedLayoutItem, TextOverflow,
+    };
+    use crate::style::{Direction, Hyphens, LineHeight, StyleProperty, TextJustify};
+    use crate::testing::{self, build_and_break, line_texts, Brush, Content};
+
+    /// Returns the items of each line as text ranges of glyph runs with
+    /// their offsets, or identifiers of inline boxes with their positions.
+    fn line_items(layout: &Layout<Brush>) -> Vec<Vec<String>> {
+        layout
+            .lines()
+            .map(|line| {
+                line.items()
+                    .map(|item| match item {
+                        PositionedLayoutItem::GlyphRun(glyph_run) => {
+                            format!("{:?} {}", glyph_run.run().text_range(), glyph_run.offset())
+                        }
+                        PositionedLayoutItem::InlineBox(inline_box) => {
+                            format!("box {} {} {}", inline_box.id, inline_box.x, inline_box.y)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inline_boxes() {
+        let boxes = [InlineBox::new(1, 3, 15., 20.)];
+        let layout = build_and_break(
+            "ab cd",
+            &Content {
+                boxes: &boxes,
+                ..Content::default()
+            },
+            None,
+            Alignment::Start,
+        );
+        assert_eq!(line_items(&layout), [["0..3 0", "box 1 30 0", "3..5 45"]]);
+        // The box sits on the baseline and extends the ascent of the line.
+        let line = layout.get(0).unwrap();
+        let metrics = line.metrics();
+        assert_eq!(
+            (metrics.ascent, metrics.descent, metrics.advance),
+            (20., 2., 65.)
+        );
+        // Boxes are not counted as runs.
+        assert_eq!(line.len(), 2);
+        assert_eq!(line.get(1).unwrap().text_range(), 3..5);
+        assert!(line.get(2).is_none());
+        let layout = build_and_break("ab cd", &Content::default(), None, Alignment::Start);
+        let line = layout.get(0).unwrap();
+        assert_eq!(line.len(), 1);
+        assert_eq!(line.get(0).unwrap().text_range(), 0..5);
+        assert!(line.get(1).is_none());
+        // Boxes at the start and end of the text, one of them extending
+        // below the baseline.
+        let boxes = [
+            InlineBox::new(1, 0, 15., 20.),
+            InlineBox::new(2, 5, 5., 5.),
+            InlineBox {
+                baseline: 2.,
+                ..InlineBox::new(3, 5, 5., 5.)
+            },
+        ];
+        let layout = build_and_break(
+            "ab cd",
+            &Content {
+                boxes: &boxes,
+                ..Content::default()
+            },
+            None,
+            Alignment::Start,
+        );
+        assert_eq!(
+            line_items(&layout),
+            [["box 1 0 0", "0..5 15", "box 2 65 15", "box 3 70 18"]]
+        );
+        assert_eq!(layout.get(0).unwrap().metrics().descent, 3.);
+    }
+
+    #[test]
+    fn break_before_inline_box() {
+        let boxes = [InlineBox::new(1, 3, 15., 20.)];
+        let layout = build_and_break(
+            "ab cd",
+            &Content {
+                boxes: &boxes,
+                ..Content::default()
+            },
+            Some(40.),
+            Alignment::Start,
+        );
+        assert_eq!(
+            line_items(&layout),
+            [vec!["0..3 0"], vec!["box 1 0 10", "3..5 15"]]
+        );
+        assert_eq!(layout.get(1).unwrap().metrics().baseline, 30.);
+        // A box that does not fit with the following text is placed on its
+        // own line.
+        let layout = build_and_break(
+            "ab cd",
+            &Content {
+                boxes: &boxes,
+                ..Content::default()
+            },
+            Some(20.),
+            Alignment::Start,
+        );
+        assert_eq!(
+            line_items(&layout),
+            [["0..3 0"], ["box 1 0 10"], ["3..5 0"]]
+        );
+    }
+
+    #[test]
+    fn ligature_in_later_run() {
+        // The second paragraph starts a new run, so the ligature
+        // continuations must be looked up relative to the run rather than
+        // the layout. "fiffi" is 5 + 5 + 8 + 8 + 8 = 34 units wide.
+        let text = "ab\nfiffi";
+        let layout = build_and_break(text, &Content::default(), Some(35.), Alignment::Start);
+        assert_eq!(line_texts(&layout, text), ["ab", "fiffi"]);
+    }
+
+    #[test]
+    fn soft_hyphen_then_overflow() {
+        // The line following a soft hyphen starts at its break, which must
+        // not be taken again with a hyphen.
+        let text = "ab\u{AD}cdef gh";
+        let style = [StyleProperty::Hyphens(Hyphens::Manual)];
+        let layout = build_and_break(text, &Content::styled(&style), Some(35.), Alignment::Start);
+        let lines = line_texts(&layout, text);
+        assert_eq!(lines[0], "ab\u{AD}");
+        assert_eq!(lines.concat(), text);
+    }
+
+    #[test]
+    fn emergency_break() {
+        // The cluster following an emergency break begins the next line and
+        // contributes to its advance.
+        let text = "abcde\u{2029}fg ";
+        let layout = build_and_break(text, &Content::default(), Some(25.), Alignment::Start);
+        let lines: Vec<_> = layout
+            .lines()
+            .map(|line| (&text[line.text_range()], line.metrics().advance))
+            .collect();
+        assert_eq!(
+            lines,
+            [("ab", 20.), ("cd", 20.), ("e\u{2029}", 20.), ("fg ", 30.)]
+        );
+    }
+
+    /// Breaks the text into at most `max_lines` lines and returns the text
+    /// and advance of each line, with an ellipsis appended where one is
+    /// displayed.
+    fn truncated(
+        text: &str,
+        max_advance: f32,
+        max_lines: usize,
+        overflow: TextOverflow,
+    ) -> Vec<(String, f32)> {
+        let mut layout = testing::build_standalone(text, &Content::default());
+        let mut breaker = layout.break_lines();
+        breaker.set_max_lines(max_lines, overflow);
+        breaker.break_remaining(max_advance, Alignment::Start);
+        layout
+            .lines()
+            .map(|line| {
+                let mut line_text = String::new();
+                for item in line.items() {
+                    if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                        let run = glyph_run.run();
+                        if run.is_ellipsis() {
+                            assert_eq!(glyph_run.positioned_glyphs().count(), 1);
+                            line_text.push('\u{2026}');
+                        } else {
+                            line_text.push_str(&text[run.text_range()]);
+                        }
+                    }
+                }
+                (line_text, line.metrics().advance)
+            })
+            .collect()
+    }
+
+    fn lines(lines: &[(&str, f32)]) -> Vec<(String, f32)> {
+        lines
+            .iter()
+            .map(|(text, advance)| (text.to_string(), *advance))
+            .collect()
+    }
+
+    #[test]
+    fn max_lines_clip() {
+        let text = "ab cd ef gh";
+        assert_eq!(
+            truncated(text, 30., 2, TextOverflow::Clip),
+            lines(&[("ab ", 30.), ("cd ", 30.)])
+        );
+        assert!(truncated(text, 30., 0, TextOverflow::Clip).is_empty());
+    }
+
+    #[test]
+    fn max_lines_ellipsis() {
+        let text = "ab cd ef gh";
+        assert_eq!(
+            truncated(text, 30., 2, TextOverflow::Ellipsis),
+            lines(&[("ab ", 30.), ("cd\u{2026}", 30.)])
+        );
+        // Clusters are removed from the end of the line until the ellipsis
+        // fits.
+        assert_eq!(
+            truncated(text, 25., 2, TextOverflow::Ellipsis),
+            lines(&[("ab ", 30.), ("c\u{2026}", 20.)])
+        );
+        assert_eq!(
+            truncated("ab\ncd\nef", 1000., 2, TextOverflow::Ellipsis),
+            lines(&[("ab\n", 20.), ("cd\u{2026}", 30.)])
+        );
+        assert!(truncated(text, 30., 0, TextOverflow::Ellipsis).is_empty());
+    }
+
+    #[test]
+    fn max_lines_not_exceeded() {
+        assert_eq!(
+            truncated("ab cd", 30., 2, TextOverflow::Ellipsis),
+            lines(&[("ab ", 30.), ("cd", 20.)])
+        );
+        assert_eq!(
+            truncated("abcdef", 1000., 1, TextOverflow::Ellipsis),
+            lines(&[("abcdef", 60.)])
+        );
+    }
+
+    #[test]
+    fn ellipsis_in_rtl_paragraph() {
+        // The ellipsis follows the text in the direction of the paragraph,
+        // so it is displayed on the left.
+        let text = "\u{5D0}\u{5D1} \u{5D2}\u{5D3}";
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut layout = testing::build(&mut fcx, &mut lcx, text, &Content::default());
+        let mut breaker = layout.break_lines();
+        breaker.set_max_lines(1, TextOverflow::Ellipsis);
+        breaker.break_remaining(30., Alignment::Start);
+        let runs: Vec<_> = layout
+            .get(0)
+            .unwrap()
+            .items()
+            .filter_map(|item| match item {
+                PositionedLayoutItem::GlyphRun(glyph_run) => Some((
+                    glyph_run.run().is_ellipsis(),
+                    glyph_run.run().text_range(),
+                    glyph_run.offset(),
+                )),
+                PositionedLayoutItem::InlineBox(_) => None,
+            })
+            .collect();
+        assert_eq!(runs, [(true, 4..4, 0.), (false, 0..4, 10.)]);
+    }
+
+    /// Returns whether each line is right-to-left and its offset.
+    fn aligned(text: &str, direction: Direction, alignment: Alignment) -> Vec<(bool, f32)> {
+        let layout = build_and_break(
+            text,
+            &Content::styled(&[StyleProperty::Direction(direction)]),
+            Some(60.),
+            alignment,
+        );
+        layout
+            .lines()
+            .map(|line| (line.is_rtl(), line.metrics().offset))
+            .collect()
+    }
+
+    /// Returns the offset of each glyph on the first line.
+    fn glyph_offsets(layout: &Layout<Brush>) -> Vec<f32> {
+        let mut offsets = vec![];
+        for item in layout.get(0).unwrap().items() {
+            if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                offsets.extend(glyph_run.positioned_glyphs().map(|glyph| glyph.x));
+            }
+        }
+        offsets
+    }
+
+    #[test]
+    fn direction_relative_alignment() {
+        let ltr = "abc";
+        let rtl = "\u{5D0}\u{5D1}\u{5D2}";
+        for (alignment, ltr_offset, rtl_offset) in [
+            (Alignment::Start, 0., 30.),
+            (Alignment::Middle, 15., 15.),
+            (Alignment::End, 30., 0.),
+            (Alignment::Justified, 0., 30.),
+            (Alignment::Left, 0., 0.),
+            (Alignment::Right, 30., 30.),
+        ] {
+            // The direction is taken from the first strong character by
+            // default.
+            assert_eq!(
+                aligned(ltr, Direction::Auto, alignment),
+                [(false, ltr_offset)]
+            );
+            assert_eq!(
+                aligned(rtl, Direction::Auto, alignment),
+                [(true, rtl_offset)]
+            );
+            assert_eq!(
+                aligned(rtl, Direction::LeftToRight, alignment),
+                [(false, ltr_offset)]
+            );
+            assert_eq!(
+                aligned(ltr, Direction::RightToLeft, alignment),
+                [(true, rtl_offset)]
+            );
+        }
+    }
+
+    #[test]
+    fn justified_rtl() {
+        let text = "\u{5D0}\u{5D1} \u{5D2}\u{5D3} \u{5D4}\u{5D5}";
+        let layout = build_and_break(text, &Content::default(), Some(70.), Alignment::Justified);
+        // The trailing space hangs off the left edge.
+        assert_eq!(glyph_offsets(&layout), [-10., 0., 10., 20., 50., 60.]);
+        // Trailing whitespace of a left-to-right run in a right-to-left
+        // paragraph is not at the edge of the line.
+        let layout = build_and_break(
+            "ab cd ef",
+            &Content::styled(&[StyleProperty::Direction(Direction::RightToLeft)]),
+            Some(70.),
+            Alignment::Justified,
+        );
+        assert_eq!(glyph_offsets(&layout), [0., 10., 20., 40., 50., 60.]);
+    }
+
+    /// Returns the text and offset of each line of an indented layout.
+    fn indented(
+        text: &str,
+        style: &[StyleProperty<Brush>],
+        alignment: Alignment,
+        strategy: BreakStrategy,
+    ) -> Vec<(String, f32)> {
+        let mut layout = testing::build_standalone(text, &Content::styled(style));
+        layout.break_all_lines_with_strategy(Some(50.), alignment, strategy);
+        layout
+            .lines()
+            .map(|line| (text[line.text_range()].to_string(), line.metrics().offset))
+            .collect()
+    }
+
+    #[test]
+    fn text_indent() {
+        let style = [
+            StyleProperty::TextIndent(20.),
+            StyleProperty::HangingIndent(10.),
+        ];
+        let expected = lines(&[("ab ", 20.), ("cd ", 10.), ("ef ", 10.), ("gh", 10.)]);
+        for strategy in [BreakStrategy::Greedy, BreakStrategy::Optimal] {
+            assert_eq!(
+                indented("ab cd ef gh", &style, Alignment::Start, strategy),
+                expected
+            );
+        }
+        // Each paragraph starts with the indentation of the first line.
+        assert_eq!(
+            indented("ab\ncd ef", &style, Alignment::Start, BreakStrategy::Greedy),
+            lines(&[("ab\n", 20.), ("cd ", 20.), ("ef", 10.)])
+        );
+        // The indentation is at the start edge, so it only reduces the space
+        // available to lines aligned to the end.
+        assert_eq!(
+            indented("ab cd", &style, Alignment::End, BreakStrategy::Greedy),
+            lines(&[("ab ", 30.), ("cd", 30.)])
+        );
+        assert_eq!(
+            indented(
+                "ab cd ef gh",
+                &[StyleProperty::TextIndent(-10.)],
+                Alignment::Start,
+                BreakStrategy::Greedy
+            ),
+            lines(&[("ab cd ", -10.), ("ef gh", 0.)])
+        );
+        let layout = build_and_break(
+            "ab cd ef gh",
+            &Content::styled(&style),
+            Some(50.),
+            Alignment::Start,
+        );
+        assert_eq!(layout.width(), 40.);
+        assert_eq!(layout.min_content_width(), 40.);
+        assert_eq!(layout.max_content_width(), 130.);
+        // Hit testing accounts for the indentation.
+        assert_eq!(Cursor::from_point(&layout, 15., 5.).insert_point, 0);
+        assert_eq!(Cursor::from_point(&layout, 38., 5.).insert_point, 2);
+    }
+
+    #[test]
+    fn text_indent_rtl() {
+        let style = [
+            StyleProperty::TextIndent(20.),
+            StyleProperty::HangingIndent(10.),
+        ];
+        let text = "\u{5D0}\u{5D1} \u{5D2}\u{5D3} \u{5D4}\u{5D5}";
+        let offsets: Vec<_> = indented(text, &style, Alignment::Start, BreakStrategy::Greedy)
+            .into_iter()
+            .map(|(_, offset)| offset)
+            .collect();
+        assert_eq!(offsets, [0., 10., 20.]);
+        // The first line ends 20 units from the right edge and its trailing
+        // space hangs off its visual start.
+        let layout = build_and_break(text, &Content::styled(&style), Some(50.), Alignment::Start);
+        assert_eq!(glyph_offsets(&layout), [0., 10., 20.]);
+    }
+
+    #[test]
+    fn break_around() {
+        let text = "ab cd ef gh ij kl";
+        for (alignment, offsets) in [
+            (Alignment::Start, [30., 30., 0., 0.]),
+            (Alignment::End, [50., 50., 20., 20.]),
+        ] {
+            let mut fcx = testing::font_context();
+            let mut lcx = LayoutContext::new();
+            let mut layout = testing::build(&mut fcx, &mut lcx, text, &Content::default());
+            let mut queries = vec![];
+            // Content occupies the left 30 units of the first two lines.
+            layout.break_lines().break_remaining_around(
+                |y| {
+                    queries.push(y);
+                    if y < 20. {
+                        (30., 40.)
+                    } else {
+                        (0., 70.)
+                    }
+                },
+                alignment,
+            );
+            assert_eq!(queries, [0., 10., 20., 30., 40.]);
+            assert_eq!(line_texts(&layout, text), ["ab ", "cd ", "ef gh ", "ij kl"]);
+            let lines: Vec<_> = layout
+                .lines()
+                .map(|line| {
+                    let metrics = line.metrics();
+                    (metrics.offset, metrics.span_offset, metrics.span_width)
+                })
+                .collect();
+            assert_eq!(
+                lines,
+                [
+                    (offsets[0], 30., 40.),
+                    (offsets[1], 30., 40.),
+                    (offsets[2], 0., 70.),
+                    (offsets[3], 0., 70.),
+                ]
+            );
+            assert_eq!(layout.width(), 50.);
+        }
+    }
+
+    #[test]
+    fn break_next_at() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let style = [StyleProperty::FontSize(20.)];
+        let mut layout = testing::build(&mut fcx, &mut lcx, "ab cd ef", &Content::styled(&style));
+        let mut breaker = layout.break_lines();
+        // The size of each line is known as soon as it is computed.
+        assert_eq!(
+            breaker.break_next_at(10., 70., Alignment::Start),
+            Some((60., 20.))
+        );
+        assert_eq!(breaker.break_next(70., Alignment::Start), Some((60., 20.)));
+        assert_eq!(breaker.break_next(70., Alignment::Start), Some((40., 20.)));
+        assert_eq!(breaker.break_next(70., Alignment::Start), None);
+        breaker.finish();
+        let offsets: Vec<_> = layout.lines().map(|line| line.metrics().offset).collect();
+        assert_eq!(offsets, [10., 0., 0.]);
+    }
+
+    /// Returns the identifier, offset and advance of each glyph on the first
+    /// line of a justified layout.
+    fn justified(text: &str, method: TextJustify, max_advance: f32) -> Vec<(u16, f32, f32)> {
+        let style = [StyleProperty::TextJustify(method)];
+        let layout = build_and_break(
+            text,
+            &Content::styled(&style),
+            Some(max_advance),
+            Alignment::Justified,
+        );
+        let mut glyphs = vec![];
+        for item in layout.get(0).unwrap().items() {
+            if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                glyphs.extend(
+                    glyph_run
+                        .positioned_glyphs()
+                        .map(|glyph| (glyph.id, glyph.x, glyph.advance)),
+                );
+            }
+        }
+        glyphs
+    }
+
+    fn offsets(glyphs: &[(u16, f32, f32)]) -> Vec<f32> {
+        glyphs.iter().map(|(_, x, _)| *x).collect()
+    }
+
+    #[test]
+    fn justify_inter_character() {
+        let glyphs = justified("abcd efg", TextJustify::InterCharacter, 70.);
+        assert_eq!(offsets(&glyphs), [0., 20., 40., 60., 70.]);
+        // Lines without justification opportunities are aligned to the
+        // start.
+        let glyphs = justified("abcd efg", TextJustify::Auto, 70.);
+        assert_eq!(offsets(&glyphs), [0., 10., 20., 30., 40.]);
+        // Both spaces and the gaps between unspaced characters are expanded
+        // by default.
+        let text = "ab \u{4E00}\u{4E01}\u{4E02}";
+        let glyphs = justified(text, TextJustify::Auto, 55.);
+        assert_eq!(offsets(&glyphs), [0., 10., 20., 32.5, 45.]);
+        let glyphs = justified(text, TextJustify::InterWord, 55.);
+        assert_eq!(offsets(&glyphs), [0., 10., 20., 35., 45.]);
+    }
+
+    #[test]
+    fn justify_kashida() {
+        const TATWEEL: u16 = 155;
+        let tatweels = |glyphs: &[(u16, f32, f32)]| -> Vec<(f32, f32)> {
+            glyphs
+                .iter()
+                .filter(|(id, ..)| *id == TATWEEL)
+                .map(|(_, x, advance)| (*x, *advance))
+                .collect()
+        };
+        // Three joined behs followed by a space, displayed from right to left
+        // with the trailing space hanging off the left edge.
+        let text = "\u{628}\u{628}\u{628} \u{628}\u{628}";
+        let glyphs = justified(text, TextJustify::Kashida, 50.);
+        assert_eq!(offsets(&glyphs), [-10., 0., 10., 20., 30., 40.]);
+        assert_eq!(tatweels(&glyphs), [(10., 10.), (30., 10.)]);
+        // A kashida is displayed with as many tatweels as needed to cover
+        // its width.
+        let glyphs = justified(text, TextJustify::Kashida, 55.);
+        assert_eq!(
+            tatweels(&glyphs),
+            [(10., 6.25), (16.25, 6.25), (32.5, 6.25), (38.75, 6.25)]
+        );
+        // Alef does not join to the following letter.
+        let glyphs = justified("\u{628}\u{627} \u{627}\u{628}", TextJustify::Kashida, 40.);
+        assert_eq!(tatweels(&glyphs), [(10., 10.), (20., 10.)]);
+        let glyphs = justified(text, TextJustify::Auto, 50.);
+        assert!(tatweels(&glyphs).is_empty());
+    }
+
+    #[test]
+    fn text_align_last() {
+        let text = "ab cd\nef gh ij";
+        for (last, offsets) in [
+            (None, [0., 0., 0.]),
+            (Some(Alignment::End), [20., 0., 50.]),
+            (Some(Alignment::Middle), [10., 0., 25.]),
+            (Some(Alignment::Justified), [0., 0., 0.]),
+        ] {
+            let style = [StyleProperty::TextAlignLast(last)];
+            let layout = build_and_break(
+                text,
+                &Content::styled(&style),
+                Some(70.),
+                Alignment::Justified,
+            );
+            assert_eq!(line_texts(&layout, text), ["ab cd", "ef gh ", "ij"]);
+            let line_offsets: Vec<_> = layout.lines().map(|line| line.metrics().offset).collect();
+            assert_eq!(line_offsets, offsets, "{last:?}");
+        }
+        // Lines before an explicit line break are the last lines of their
+        // paragraphs.
+        let style = [StyleProperty::TextAlignLast(Some(Alignment::Justified))];
+        let layout = build_and_break(
+            text,
+            &Content::styled(&style),
+            Some(70.),
+            Alignment::Justified,
+        );
+        assert_eq!(glyph_offsets(&layout), [0., 10., 20., 50., 60.]);
+        let layout = build_and_break(text, &Content::default(), Some(70.), Alignment::Justified);
+        assert_eq!(glyph_offsets(&layout), [0., 10., 20., 30., 40.]);
+    }
+
+    #[test]
+    fn break_justified_again() {
+        let text = "ab cd ef \u{628}\u{628}\u{628} \u{628}\u{628} \u{4E00}\u{4E01}\u{4E02}";
+        for method in [TextJustify::Auto, TextJustify::Kashida] {
+            let style = [StyleProperty::TextJustify(method)];
+            let mut layout = build_and_break(
+                text,
+                &Content::styled(&style),
+                Some(55.),
+                Alignment::Justified,
+            );
+            // Justification is removed before lines are broken again.
+            layout.break_all_lines(Some(75.), Alignment::Start);
+            let expected =
+                build_and_break(text, &Content::styled(&style), Some(75.), Alignment::Start);
+            assert_eq!(testing::dump(&layout), testing::dump(&expected));
+        }
+    }
+
+    /// Returns the ascent, descent, leading, baseline and block extent of
+    /// each line of a layout of two paragraphs.
+    fn line_heights(style: &[StyleProperty<Brush>]) -> Vec<[f32; 6]> {
+        let layout = build_and_break("ab\ncd", &Content::styled(style), None, Alignment::Start);
+        layout
+            .lines()
+            .map(|line| {
+                let metrics = line.metrics();
+                [
+                    metrics.ascent,
+                    metrics.descent,
+                    metrics.leading,
+                    metrics.baseline,
+                    metrics.min_coord,
+                    metrics.max_coord,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_height() {
+        let height = |line_height| line_heights(&[StyleProperty::LineHeight(line_height)]);
+        assert_eq!(
+            height(LineHeight::NORMAL),
+            [[8., 2., 0., 8., 0., 10.], [8., 2., 0., 18., 10., 20.]]
+        );
+        assert_eq!(
+            height(LineHeight::MetricsRelative(2.)),
+            [[16., 4., 0., 16., 0., 20.], [16., 4., 0., 36., 20., 40.]]
+        );
+        // The leading is split evenly above and below the text.
+        assert_eq!(
+            height(LineHeight::FontSizeRelative(2.)),
+            [[8., 2., 10., 13., 0., 20.], [8., 2., 10., 33., 20., 40.]]
+        );
+        assert_eq!(
+            height(LineHeight::Absolute(15.)),
+            [[8., 2., 5., 11., 0., 15.], [8., 2., 5., 26., 15., 30.]]
+        );
+        // Lines may be shorter than their text.
+        assert_eq!(
+            height(LineHeight::Absolute(5.)),
+            [[8., 2., -5., 6., 0., 5.], [8., 2., -5., 11., 5., 10.]]
+        );
+    }
+
+    #[test]
+    fn nearly_equal_line_heights() {
+        let line_height = LineHeight::MetricsRelative(0.5 + f32::EPSILON / 2.);
+        let layout = testing::build_standalone(
+            "ab",
+            &Content {
+                style: &[StyleProperty::LineHeight(0.5.into())],
+                spans: &[(StyleProperty::LineHeight(line_height), 1..2)],
+                ..Content::default()
+            },
+        );
+        assert_eq!(layout.styles().len(), 1);
+        assert_eq!(
+            layout.styles()[0].line_height,
+            LineHeight::MetricsRelative(0.5)
+        );
+    }
+
+    #[test]
+    fn mixed_line_heights() {
+        let line_height = StyleProperty::LineHeight(LineHeight::Absolute(20.));
+        let layout = build_and_break(
+            "ab",
+            &Content {
+                spans: &[(line_height, 1..2)],
+                ..Content::default()
+            },
+            None,
+            Alignment::Start,
+        );
+        let metrics = *layout.get(0).unwrap().metrics();
+        assert_eq!(metrics.baseline, 13.);
+        assert_eq!(metrics.size(), 20.);
+    }
+
+    #[test]
+    fn line_height_step() {
+        // The added space is split evenly above and below the line.
+        assert_eq!(
+            line_heights(&[StyleProperty::LineHeightStep(12.)]),
+            [[8., 2., 2., 9., 0., 12.], [8., 2., 2., 21., 12., 24.]]
+        );
+        assert_eq!(
+            line_heights(&[
+                StyleProperty::LineHeight(LineHeight::Absolute(15.)),
+                StyleProperty::LineHeightStep(12.),
+            ]),
+            [[8., 2., 14., 16., 0., 24.], [8., 2., 14., 40., 24., 48.]]
+        );
+    }
+}
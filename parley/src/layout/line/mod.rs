@@ -4,6 +4,7 @@
 use super::*;
 
 pub mod greedy;
+mod optimal;
 
 impl<'a, B: Brush> Line<'a, B> {
     /// Returns the metrics for the line.
@@ -16,6 +17,12 @@ impl<'a, B: Brush> Line<'a, B> {
         self.data.text_range.clone()
     }
 
+    /// Returns true if the paragraph containing the line has a right-to-left
+    /// base direction.
+    pub fn is_rtl(&self) -> bool {
+        self.data.base_level & 1 != 0
+    }
+
     /// Returns the number of runs in the line.
     pub fn len(&self) -> usize {
         self.data.run_range.len()
@@ -23,19 +30,16 @@ impl<'a, B: Brush> Line<'a, B> {
 
     /// Returns true if the line is empty.
     pub fn is_empty(&self) -> bool {
-        self.data.run_range.is_empty()
+        self.data.item_range.is_empty()
     }
 
     /// Returns the run at the specified index.
     pub fn get(&self, index: usize) -> Option<Run<'a, B>> {
-        let index = self.data.run_range.start + index;
-        if index >= self.data.run_range.end {
-            return None;
-        }
-        let line_data = self.layout.line_runs.get(index)?;
+        let item_index = *self.layout.line_runs[self.data.run_range.clone()].get(index)?;
+        let line_data = &self.layout.line_items[item_index];
         Some(Run {
             layout: self.layout,
-            data: self.layout.runs.get(line_data.run_index)?,
+            data: self.layout.runs.get(line_data.index)?,
             line_data: Some(line_data),
         })
     }
@@ -43,26 +47,46 @@ impl<'a, B: Brush> Line<'a, B> {
     /// Returns an iterator over the runs for the line.
     pub fn runs(&self) -> impl Iterator<Item = Run<'a, B>> + 'a + Clone {
         let copy = self.clone();
-        let line_runs = &copy.layout.line_runs[self.data.run_range.clone()];
-        line_runs.iter().map(move |line_data| Run {
-            layout: copy.layout,
-            data: &copy.layout.runs[line_data.run_index],
-            line_data: Some(line_data),
-        })
+        self.item_data()
+            .iter()
+            .filter(|item| item.is_text_run())
+            .map(move |line_data| Run {
+                layout: copy.layout,
+                data: &copy.layout.runs[line_data.index],
+                line_data: Some(line_data),
+            })
     }
 
     /// Returns an iterator over the glyph runs for the line.
     pub fn glyph_runs(&self) -> impl Iterator<Item = GlyphRun<'a, B>> + 'a + Clone {
-        GlyphRunIter {
+        self.items().filter_map(|item| match item {
+            PositionedLayoutItem::GlyphRun(glyph_run) => Some(glyph_run),
+            PositionedLayoutItem::InlineBox(_) => None,
+        })
+    }
+
+    /// Returns an iterator over the glyph runs and inline boxes for the
+    /// line in visual order.
+    pub fn items(&self) -> impl Iterator<Item = PositionedLayoutItem<'a, B>> + 'a + Clone {
+        LineItemIter {
             line: self.clone(),
-            run_index: 0,
+            item_index: 0,
             glyph_start: 0,
             offset: 0.,
         }
     }
+
+    pub(crate) fn item_data(&self) -> &'a [LineItemData] {
+        &self.layout.line_items[self.data.item_range.clone()]
+    }
 }
 
 /// Metrics information for a line.
+///
+/// In vertical writing modes, the baseline is the central baseline of the
+/// line, offsets along the baseline are measured downward from the top of the
+/// layout and offsets along the block axis are measured from the edge at which
+/// line stacking begins.
 #[derive(Copy, Clone, Default, Debug)]
 pub struct LineMetrics {
     /// Typographic ascent.
@@ -73,8 +97,19 @@ pub struct LineMetrics {
     pub leading: f32,
     /// Offset to the baseline.
     pub baseline: f32,
+    /// Offset to the top of the line, including the leading above the
+    /// text.
+    pub min_coord: f32,
+    /// Offset to the bottom of the line, including the leading below the
+    /// text.
+    pub max_coord: f32,
     /// Offset for alignment.
     pub offset: f32,
+    /// Offset of the start of the space that was available to the line.
+    pub span_offset: f32,
+    /// Width of the space that was available to the line, or `f32::MAX` if
+    /// it was unbounded.
+    pub span_width: f32,
     /// Full advance of the line.
     pub advance: f32,
     /// Advance of trailing whitespace.
@@ -111,7 +146,7 @@ impl<'a, B: Brush> GlyphRun<'a, B> {
         self.style
     }
 
-    /// Returns the offset to the baseline.
+    /// Returns the offset to the baseline along the block axis.
     pub fn baseline(&self) -> f32 {
         self.baseline
     }
@@ -129,48 +164,110 @@ impl<'a, B: Brush> GlyphRun<'a, B> {
     /// Returns an iterator over the glyphs in the run.
     pub fn glyphs(&'a self) -> impl Iterator<Item = Glyph> + 'a + Clone {
         self.run
-            .visual_clusters()
-            .flat_map(|cluster| cluster.glyphs())
+            .visual_glyphs()
             .skip(self.glyph_start)
             .take(self.glyph_count)
     }
 
     /// Returns an iterator over the fully positioned glyphs in the run.
+    ///
+    /// Glyph positions are in physical coordinates. In vertical writing
+    /// modes, glyphs advance downward and are centered on the baseline.
     pub fn positioned_glyphs(&'a self) -> impl Iterator<Item = Glyph> + 'a + Clone {
         let mut offset = self.offset;
-        let baseline = self.baseline;
+        let layout = self.run.layout;
+        let is_vertical = layout.writing_mode.is_vertical();
+        let baseline = physical_block_offset(layout, self.baseline);
         self.run
-            .visual_clusters()
-            .flat_map(|cluster| cluster.glyphs())
+            .visual_glyphs()
             .skip(self.glyph_start)
             .take(self.glyph_count)
             .map(move |mut g| {
-                g.x += offset;
-                g.y += baseline;
+                if is_vertical {
+                    g.x += baseline;
+                    g.y += offset;
+                } else {
+                    g.x += offset;
+                    g.y += baseline;
+                }
                 offset += g.advance;
                 g
             })
     }
 }
 
+/// Converts an offset along the block axis to a physical coordinate.
+fn physical_block_offset<B: Brush>(layout: &LayoutData<B>, offset: f32) -> f32 {
+    if layout.writing_mode == WritingMode::VerticalRl {
+        // Lines are stacked from the right edge.
+        layout.height - offset
+    } else {
+        offset
+    }
+}
+
+/// Inline box with its final position in the layout.
+#[derive(Copy, Clone, Debug)]
+pub struct PositionedInlineBox {
+    /// User defined identifier for the box.
+    pub id: u64,
+    /// Offset to the left edge of the box.
+    pub x: f32,
+    /// Offset to the top edge of the box.
+    pub y: f32,
+    /// Width of the box.
+    pub width: f32,
+    /// Height of the box.
+    pub height: f32,
+}
+
+/// Fully positioned item in a line.
 #[derive(Clone)]
-struct GlyphRunIter<'a, B: Brush> {
+pub enum PositionedLayoutItem<'a, B: Brush> {
+    /// Sequence of glyphs with the same style.
+    GlyphRun(GlyphRun<'a, B>),
+    /// Inline box.
+    InlineBox(PositionedInlineBox),
+}
+
+#[derive(Clone)]
+struct LineItemIter<'a, B: Brush> {
     line: Line<'a, B>,
-    run_index: usize,
+    item_index: usize,
     glyph_start: usize,
     offset: f32,
 }
 
-impl<'a, B: Brush> Iterator for GlyphRunIter<'a, B> {
-    type Item = GlyphRun<'a, B>;
+impl<'a, B: Brush> Iterator for LineItemIter<'a, B> {
+    type Item = PositionedLayoutItem<'a, B>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let run = self.line.get(self.run_index)?;
-            let mut iter = run
-                .visual_clusters()
-                .flat_map(|c| c.glyphs())
-                .skip(self.glyph_start);
+            let item = self.line.item_data().get(self.item_index)?;
+            let layout = self.line.layout;
+            let metrics = &self.line.data.metrics;
+            if item.kind == LayoutItemKind::InlineBox {
+                let inline_box = layout.inline_boxes.get(item.index)?;
+                let offset = self.offset + metrics.offset;
+                self.offset += item.advance;
+                self.item_index += 1;
+                self.glyph_start = 0;
+                let (x, y) = if layout.writing_mode.is_vertical() {
+                    let baseline = physical_block_offset(layout, metrics.baseline);
+                    (baseline - inline_box.width * 0.5, offset)
+                } else {
+                    (offset, metrics.baseline - inline_box.baseline)
+                };
+                return Some(PositionedLayoutItem::InlineBox(PositionedInlineBox {
+                    id: inline_box.id,
+                    x,
+                    y,
+                    width: inline_box.width,
+                    height: inline_box.height,
+                }));
+            }
+            let run = Run::new(layout, layout.runs.get(item.index)?, Some(item));
+            let mut iter = run.visual_glyphs().skip(self.glyph_start);
             if let Some(first) = iter.next() {
                 let mut advance = first.advance;
                 let style_index = first.style_index();
@@ -179,22 +276,22 @@ impl<'a, B: Brush> Iterator for GlyphRunIter<'a, B> {
                     glyph_count += 1;
                     advance += glyph.advance;
                 }
-                let style = run.layout.styles.get(style_index)?;
+                let style = layout.styles.get(style_index)?;
                 let glyph_start = self.glyph_start;
                 self.glyph_start += glyph_count;
                 let offset = self.offset;
                 self.offset += advance;
-                return Some(GlyphRun {
+                return Some(PositionedLayoutItem::GlyphRun(GlyphRun {
                     run,
                     style,
                     glyph_start,
                     glyph_count,
-                    offset: offset + self.line.data.metrics.offset,
-                    baseline: self.line.data.metrics.baseline,
+                    offset: offset + metrics.offset,
+                    baseline: metrics.baseline,
                     advance,
-                });
+                }));
             }
-            self.run_index += 1;
+            self.item_index += 1;
             self.glyph_start = 0;
         }
     }
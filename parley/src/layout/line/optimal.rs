@@ -0,0 +1,341 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Optimal (total-fit) line breaking.
+//!
+//! This is a simplified form of the Knuth-Plass algorithm. Every cluster
+//! boundary is a potential break, and the sequence of breaks that minimizes
+//! the total demerits of all lines in the paragraph is selected.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use super::greedy::{line_indent, LineState};
+use crate::layout::*;
+use crate::style::Brush;
+
+/// Demerits added to every line so that fewer lines are preferred.
+const LINE_PENALTY: f64 = 10.;
+
+/// Badness of a line that cannot be stretched to fill the available space.
+///
+/// This is much larger than the limit used by TeX so that very loose lines,
+/// which are common in narrow columns, remain distinguishable.
+const MAX_BADNESS: f64 = 1e6;
+
+/// Demerits for a break within a word. This is large enough that such breaks
+/// are only chosen when a word does not fit on a line by itself.
+const EMERGENCY_PENALTY: f64 = 1e16;
+
+/// Demerits for a line that overflows the available space.
+const OVERFLOW_PENALTY: f64 = 1e20;
+
+/// Penalty for a break at a hyphenation point.
+const HYPHEN_PENALTY: f64 = 50.;
+
+/// Fraction of the width of a space that it may be stretched by.
+const SPACE_STRETCH: f64 = 0.5;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum BreakKind {
+    Start,
+    Emergency,
+    Regular,
+    Mandatory,
+    End,
+}
+
+/// Position at which a line may be broken.
+#[derive(Copy, Clone)]
+struct Candidate {
+    kind: BreakKind,
+    /// First item of a line that starts at this candidate.
+    items_start: usize,
+    /// End of the item range of a line that ends at this candidate.
+    items_end: usize,
+    /// Cluster index of the break.
+    cluster: usize,
+    /// Total advance preceding the break.
+    x: f32,
+    /// Advance of the whitespace immediately preceding the break.
+    trailing: f32,
+    /// Total advance of spaces preceding the break.
+    space_advance: f32,
+    /// Advance of the spaces immediately preceding the break.
+    trailing_space_advance: f32,
+    /// Advance of the hyphen displayed if the line is broken here, or zero
+    /// if the break is not a hyphenation point.
+    hyphen: f32,
+}
+
+/// Computes the optimal set of lines for the paragraph, starting at the
+/// specified item and cluster.
+pub(super) fn break_optimal<B: Brush>(
+    layout: &LayoutData<B>,
+    start_item: usize,
+    start_cluster: usize,
+    max_advance: f32,
+) -> Vec<(LineState, BreakReason)> {
+    let candidates = collect_candidates(layout, start_item, start_cluster);
+    // Indentation of a line that starts at each candidate.
+    let indents: Vec<f32> = candidates
+        .iter()
+        .map(|candidate| line_indent(layout, candidate.items_start, candidate.cluster))
+        .collect();
+    let len = candidates.len();
+    let mut total = vec![f64::INFINITY; len];
+    let mut prev = vec![0; len];
+    total[0] = 0.;
+    let min_indent = indents.iter().copied().fold(0f32, f32::min) as f64;
+    let max_advance = max_advance as f64;
+    for b in 1..len {
+        let end = &candidates[b];
+        for a in (0..b).rev() {
+            let start = &candidates[a];
+            let width = (end.x - end.trailing - start.x + end.hyphen) as f64;
+            if width > max_advance - min_indent && a + 1 != b {
+                // Moving the start backward only makes the line wider.
+                break;
+            }
+            if total[a].is_finite() {
+                let max_advance = max_advance - indents[a] as f64;
+                let demerits = total[a] + line_demerits(start, end, width, max_advance);
+                if demerits < total[b] {
+                    total[b] = demerits;
+                    prev[b] = a;
+                }
+            }
+            if start.kind == BreakKind::Mandatory {
+                // Lines never span a mandatory break.
+                break;
+            }
+        }
+    }
+    let mut breaks = vec![];
+    let mut b = len - 1;
+    while b != 0 {
+        breaks.push(b);
+        b = prev[b];
+    }
+    let mut lines = Vec::with_capacity(breaks.len());
+    let mut a = 0;
+    for &b in breaks.iter().rev() {
+        let start = &candidates[a];
+        let end = &candidates[b];
+        let state = LineState {
+            x: end.x - start.x + end.hyphen,
+            items: start.items_start..end.items_end,
+            clusters: start.cluster..end.cluster,
+            skip_mandatory_break: false,
+            hyphenated: end.hyphen != 0.,
+            indent: indents[a],
+            span_offset: 0.,
+            span_width: max_advance as f32,
+        };
+        let reason = match end.kind {
+            BreakKind::Mandatory => BreakReason::Explicit,
+            BreakKind::Regular => BreakReason::Regular,
+            BreakKind::Emergency => BreakReason::Emergency,
+            _ => BreakReason::None,
+        };
+        lines.push((state, reason));
+        a = b;
+    }
+    lines
+}
+
+fn line_demerits(start: &Candidate, end: &Candidate, width: f64, max_advance: f64) -> f64 {
+    let mut demerits = 0.;
+    if width > max_advance {
+        demerits += OVERFLOW_PENALTY * (width - max_advance).max(1.);
+    }
+    if end.kind == BreakKind::Emergency {
+        demerits += EMERGENCY_PENALTY;
+    }
+    if end.hyphen != 0. {
+        demerits += HYPHEN_PENALTY * HYPHEN_PENALTY;
+    }
+    let badness = if matches!(end.kind, BreakKind::Mandatory | BreakKind::End) {
+        // The last line of a paragraph is never stretched.
+        0.
+    } else {
+        let stretch = (end.space_advance - end.trailing_space_advance - start.space_advance) as f64
+            * SPACE_STRETCH;
+        let slack = (max_advance - width).max(0.);
+        if slack <= 0. {
+            0.
+        } else if stretch <= 0. {
+            MAX_BADNESS
+        } else {
+            let ratio = slack / stretch;
+            (100. * ratio * ratio * ratio).min(MAX_BADNESS)
+        }
+    };
+    demerits + (LINE_PENALTY + badness) * (LINE_PENALTY + badness)
+}
+
+fn collect_candidates<B: Brush>(
+    layout: &LayoutData<B>,
+    start_item: usize,
+    start_cluster: usize,
+) -> Vec<Candidate> {
+    // Running state, updated as clusters are consumed.
+    let mut current = Candidate {
+        kind: BreakKind::Start,
+        items_start: start_item,
+        items_end: start_item,
+        cluster: start_cluster,
+        x: 0.,
+        trailing: 0.,
+        space_advance: 0.,
+        trailing_space_advance: 0.,
+        hyphen: 0.,
+    };
+    let mut candidates = vec![current];
+    // Inline boxes allow a break on either side.
+    let mut after_box = false;
+    for (i, item) in layout.items.iter().enumerate().skip(start_item) {
+        if item.kind == LayoutItemKind::InlineBox {
+            push_candidate(
+                &mut candidates,
+                Candidate {
+                    kind: BreakKind::Regular,
+                    items_start: i,
+                    items_end: i,
+                    ..current
+                },
+            );
+            current.x += layout.inline_box_advance(item.index);
+            current.trailing = 0.;
+            current.trailing_space_advance = 0.;
+            after_box = true;
+            continue;
+        }
+        let run = &layout.runs[item.index];
+        let cluster_start = run.cluster_range.start.max(start_cluster);
+        for j in cluster_start..run.cluster_range.end {
+            let cluster = &layout.clusters[j];
+            if !cluster.is_ligature_component() {
+                let mut kind = match cluster.info.boundary() {
+                    Boundary::Mandatory => BreakKind::Mandatory,
+                    Boundary::Line => BreakKind::Regular,
+                    _ => BreakKind::Emergency,
+                };
+                let mut hyphen = 0.;
+                if after_box {
+                    kind = kind.max(BreakKind::Regular);
+                    after_box = false;
+                } else if kind == BreakKind::Regular
+                    && j > 0
+                    && layout.clusters[j - 1].is_hyphenation_point()
+                {
+                    hyphen = layout.hyphen_advance(j - 1);
+                }
+                push_candidate(
+                    &mut candidates,
+                    Candidate {
+                        kind,
+                        items_start: i,
+                        items_end: i + 1,
+                        cluster: j,
+                        hyphen,
+                        ..current
+                    },
+                );
+            }
+            current.x += cluster.advance;
+            if cluster.info.is_whitespace() {
+                current.trailing += cluster.advance;
+            } else {
+                current.trailing = 0.;
+                current.trailing_space_advance = 0.;
+            }
+            if cluster.info.whitespace().is_space_or_nbsp() {
+                current.space_advance += cluster.advance;
+                current.trailing_space_advance += cluster.advance;
+            }
+        }
+        current.cluster = current.cluster.max(run.cluster_range.end);
+    }
+    push_candidate(
+        &mut candidates,
+        Candidate {
+            kind: BreakKind::End,
+            items_start: layout.items.len(),
+            items_end: layout.items.len(),
+            cluster: layout.clusters.len(),
+            ..current
+        },
+    );
+    candidates
+}
+
+fn push_candidate(candidates: &mut Vec<Candidate>, candidate: Candidate) {
+    let last = candidates.last_mut().unwrap();
+    if last.cluster == candidate.cluster && last.x == candidate.x {
+        // Nothing separates this break from the previous one.
+        if last.kind != BreakKind::Start {
+            last.kind = last.kind.max(candidate.kind);
+        }
+        return;
+    }
+    candidates.push(candidate);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::context::LayoutContext;
+    use crate::layout::{Alignment, BreakStrategy};
+    use crate::testing::{self, line_texts, Content};
+
+    /// Returns the lines of the text broken with the specified strategy.
+    fn lines(text: &str, max_advance: f32, strategy: BreakStrategy) -> Vec<&str> {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut layout = testing::build(&mut fcx, &mut lcx, text, &Content::default());
+        layout.break_all_lines_with_strategy(Some(max_advance), Alignment::Start, strategy);
+        line_texts(&layout, text)
+    }
+
+    #[test]
+    fn balances_lines() {
+        let text = "a bb ccc dddd";
+        assert_eq!(
+            lines(text, 70., BreakStrategy::Greedy),
+            ["a bb ", "ccc ", "dddd"]
+        );
+        assert_eq!(
+            lines(text, 70., BreakStrategy::Optimal),
+            ["a ", "bb ccc ", "dddd"]
+        );
+    }
+
+    #[test]
+    fn single_line() {
+        let text = "ab cd ef";
+        assert_eq!(lines(text, 1000., BreakStrategy::Optimal), [text]);
+        assert_eq!(lines("", 50., BreakStrategy::Optimal), [""]);
+    }
+
+    #[test]
+    fn mandatory_breaks() {
+        assert_eq!(
+            lines("ab cd\nef gh ij", 50., BreakStrategy::Optimal),
+            ["ab cd", "ef gh ", "ij"]
+        );
+    }
+
+    #[test]
+    fn emergency_breaks() {
+        // Words are only broken when they do not fit on a line by themselves.
+        assert_eq!(
+            lines("abcdefgh ij", 50., BreakStrategy::Optimal),
+            ["abcde", "fgh ", "ij"]
+        );
+        // Hyphenation points are preferred to breaks within words.
+        assert_eq!(
+            lines("ab\u{AD}cdef gh", 50., BreakStrategy::Optimal),
+            ["ab\u{AD}", "cdef ", "gh"]
+        );
+    }
+}
@@ -4,33 +4,61 @@
 //! Layout types.
 
 mod cluster;
-mod line;
+pub(crate) mod line;
 mod run;
 
 pub(crate) mod data;
 
 pub mod cursor;
 
-use super::style::Brush;
+use super::style::{Brush, LineHeight, TabSize};
 use crate::Font;
 use core::ops::Range;
 use data::*;
-use swash::text::cluster::{Boundary, ClusterInfo};
+use swash::text::cluster::{Boundary, ClusterInfo, Whitespace};
 use swash::{GlyphId, NormalizedCoord, Synthesis};
 
-pub use cursor::Cursor;
+pub use cursor::{Affinity, Cursor, Selection};
 pub use line::greedy::BreakLines;
-pub use line::{GlyphRun, LineMetrics};
+pub use line::{GlyphRun, LineMetrics, PositionedInlineBox, PositionedLayoutItem};
 pub use run::RunMetrics;
 
 /// Alignment of a layout.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum Alignment {
+    /// Lines are aligned to the start edge of the paragraph: the left edge
+    /// for left-to-right paragraphs and the right edge for right-to-left
+    /// paragraphs.
     Start,
+    /// Lines are centered.
     Middle,
+    /// Lines are aligned to the end edge of the paragraph.
     End,
+    /// Lines are stretched to fill the available space with the
+    /// [`TextJustify`](crate::style::TextJustify) method of the paragraph.
+    /// Lines that end a paragraph or precede an explicit line break are
+    /// aligned as specified by
+    /// [`TextAlignLast`](crate::style::StyleProperty::TextAlignLast), which
+    /// aligns them to the start edge by default.
     Justified,
+    /// Lines are aligned to the left edge regardless of direction.
+    Left,
+    /// Lines are aligned to the right edge regardless of direction.
+    Right,
+}
+
+impl Alignment {
+    /// Returns the fraction of the free space on a line that precedes the
+    /// content on the left for a paragraph with the specified direction.
+    pub(crate) fn left_fraction(self, is_rtl: bool) -> f32 {
+        match (self, is_rtl) {
+            (Self::Left, _) | (Self::Start | Self::Justified, false) | (Self::End, true) => 0.,
+            (Self::Right, _) | (Self::Start | Self::Justified, true) | (Self::End, false) => 1.,
+            (Self::Middle, _) => 0.5,
+        }
+    }
 }
 
 impl Default for Alignment {
@@ -39,6 +67,49 @@ impl Default for Alignment {
     }
 }
 
+/// Strategy for selecting line break positions.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum BreakStrategy {
+    /// Fills each line with as much content as possible before moving to
+    /// the next line.
+    #[default]
+    Greedy,
+    /// Minimizes the total badness of all lines in the paragraph, producing
+    /// more even line lengths at a higher computational cost.
+    Optimal,
+}
+
+/// Treatment of content that does not fit within the maximum number of
+/// lines.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum TextOverflow {
+    /// Lines beyond the maximum are removed.
+    #[default]
+    Clip,
+    /// Lines beyond the maximum are removed and the end of the last line is
+    /// replaced with an ellipsis.
+    Ellipsis,
+}
+
+/// Direction in which lines and the text within them progress.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum WritingMode {
+    /// Text flows left to right and lines are stacked top to bottom.
+    #[default]
+    HorizontalTb,
+    /// Text flows top to bottom and lines are stacked right to left.
+    VerticalRl,
+    /// Text flows top to bottom and lines are stacked left to right.
+    VerticalLr,
+}
+
+impl WritingMode {
+    /// Returns true if text flows vertically.
+    pub fn is_vertical(self) -> bool {
+        self != Self::HorizontalTb
+    }
+}
+
 /// Text layout.
 #[derive(Clone)]
 pub struct Layout<B: Brush> {
@@ -61,20 +132,78 @@ impl<B: Brush> Layout<B> {
         &self.data.styles
     }
 
+    /// Returns the inline boxes for the layout, sorted by text index.
+    pub fn inline_boxes(&self) -> &[InlineBox] {
+        &self.data.inline_boxes
+    }
+
+    /// Returns the range of text that is being composed by an input method.
+    pub fn preedit_range(&self) -> Option<Range<usize>> {
+        self.data.preedit.clone()
+    }
+
+    /// Returns the writing mode of the layout.
+    pub fn writing_mode(&self) -> WritingMode {
+        self.data.writing_mode
+    }
+
     /// Returns the width of the layout.
+    ///
+    /// In vertical writing modes, this is the total width of the stacked
+    /// lines.
     pub fn width(&self) -> f32 {
-        self.data.width
+        if self.data.writing_mode.is_vertical() {
+            self.data.height
+        } else {
+            self.data.width
+        }
     }
 
     /// Returns the width of the layout, including the width of any trailing
     /// whitespace.
+    ///
+    /// Trailing whitespace extends along the inline axis, so in vertical
+    /// writing modes this is the same as [`width`](Self::width).
     pub fn full_width(&self) -> f32 {
-        self.data.full_width
+        if self.data.writing_mode.is_vertical() {
+            self.data.height
+        } else {
+            self.data.full_width
+        }
     }
 
     /// Returns the height of the layout.
+    ///
+    /// In vertical writing modes, this is the advance of the longest line,
+    /// excluding trailing whitespace.
     pub fn height(&self) -> f32 {
-        self.data.height
+        if self.data.writing_mode.is_vertical() {
+            self.data.width
+        } else {
+            self.data.height
+        }
+    }
+
+    /// Returns the min-content width of the layout: the advance of the widest
+    /// sequence of text and inline boxes that contains no line break
+    /// opportunity, excluding trailing whitespace.
+    ///
+    /// Breaking lines with a maximum advance of at least this value never
+    /// requires breaking within a word. Like line breaking, this is measured
+    /// along the inline axis, which is vertical in vertical writing modes.
+    pub fn min_content_width(&self) -> f32 {
+        self.data.min_content_width
+    }
+
+    /// Returns the max-content width of the layout: the advance of the
+    /// longest line when lines are only broken at explicit line breaks,
+    /// excluding trailing whitespace.
+    ///
+    /// Breaking lines with a maximum advance of at least this value produces
+    /// no additional lines. Like line breaking, this is measured along the
+    /// inline axis, which is vertical in vertical writing modes.
+    pub fn max_content_width(&self) -> f32 {
+        self.data.max_content_width
     }
 
     /// Returns the number of lines in the layout.
@@ -109,9 +238,44 @@ impl<B: Brush> Layout<B> {
     }
 
     /// Breaks all lines with the specified maximum advance and alignment.
+    ///
+    /// After an incremental rebuild with
+    /// [`RangedBuilder::build_into_edited`](crate::context::RangedBuilder::build_into_edited),
+    /// only the lines invalidated by the edit are computed if the maximum
+    /// advance and alignment are the same as in the previous call.
     pub fn break_all_lines(&mut self, max_advance: Option<f32>, alignment: Alignment) {
-        self.break_lines()
-            .break_remaining(max_advance.unwrap_or(f32::MAX), alignment);
+        self.break_all_lines_with_strategy(max_advance, alignment, BreakStrategy::Greedy);
+    }
+
+    /// Breaks all lines with the specified maximum advance, alignment and
+    /// line breaking strategy.
+    ///
+    /// Lines are only computed incrementally with the greedy strategy, as
+    /// described for [`break_all_lines`](Self::break_all_lines).
+    pub fn break_all_lines_with_strategy(
+        &mut self,
+        max_advance: Option<f32>,
+        alignment: Alignment,
+        strategy: BreakStrategy,
+    ) {
+        let max_advance = max_advance.unwrap_or(f32::MAX);
+        let params = Some((max_advance, alignment));
+        if strategy == BreakStrategy::Greedy && self.data.break_params == params {
+            if let Some(gap) = self.data.line_gap.take() {
+                // Only the lines invalidated by an edit need to be computed.
+                BreakLines::for_gap(&mut self.data, gap).break_remaining(max_advance, alignment);
+                return;
+            }
+        }
+        match strategy {
+            BreakStrategy::Greedy => self.break_lines().break_remaining(max_advance, alignment),
+            BreakStrategy::Optimal => self
+                .break_lines()
+                .break_remaining_optimal(max_advance, alignment),
+        }
+        if strategy == BreakStrategy::Greedy {
+            self.data.break_params = params;
+        }
     }
 
     /// Returns an iterator over the runs in the layout.
@@ -137,7 +301,7 @@ impl<B: Brush> Default for Layout<B> {
 pub struct Run<'a, B: Brush> {
     layout: &'a LayoutData<B>,
     data: &'a RunData,
-    line_data: Option<&'a LineRunData>,
+    line_data: Option<&'a LineItemData>,
 }
 
 /// Atomic unit of text.
@@ -171,6 +335,42 @@ pub struct Line<'a, B: Brush> {
     data: &'a LineData,
 }
 
+/// Box of fixed size that is laid out inline with the text, such as an
+/// image or a widget.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct InlineBox {
+    /// User defined identifier for the box.
+    pub id: u64,
+    /// Byte offset into the source text at which the box is placed. The box
+    /// precedes the character at this offset.
+    pub index: usize,
+    /// Width of the box.
+    pub width: f32,
+    /// Height of the box.
+    pub height: f32,
+    /// Offset from the top of the box to the baseline that will be aligned
+    /// with the baseline of the line.
+    pub baseline: f32,
+}
+
+impl InlineBox {
+    /// Creates a new inline box that sits on the baseline of the line.
+    pub fn new(id: u64, index: usize, width: f32, height: f32) -> Self {
+        Self {
+            id,
+            index,
+            width,
+            height,
+            baseline: height,
+        }
+    }
+
+    /// Returns the extent of the box below the baseline.
+    pub fn descent(&self) -> f32 {
+        (self.height - self.baseline).max(0.)
+    }
+}
+
 /// Style properties.
 #[derive(Clone, Debug)]
 pub struct Style<B: Brush> {
@@ -180,8 +380,15 @@ pub struct Style<B: Brush> {
     pub underline: Option<Decoration<B>>,
     /// Strikethrough decoration.
     pub strikethrough: Option<Decoration<B>>,
-    /// Multiplicative line height factor.
-    pub(crate) line_height: f32,
+    /// Underline decoration for text that is being composed by an input
+    /// method. This uses the brush and metrics of the underline, if any.
+    pub preedit: Option<Decoration<B>>,
+    /// Height of the lines containing the text.
+    pub(crate) line_height: LineHeight,
+    /// Distance between the default tab stops.
+    pub(crate) tab_size: TabSize,
+    /// Range of the explicit tab stops in the layout.
+    pub(crate) tab_stops: Range<usize>,
 }
 
 /// Underline or strikethrough decoration.
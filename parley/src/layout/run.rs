@@ -7,7 +7,7 @@ impl<'a, B: Brush> Run<'a, B> {
     pub(crate) fn new(
         layout: &'a LayoutData<B>,
         data: &'a RunData,
-        line_data: Option<&'a LineRunData>,
+        line_data: Option<&'a LineItemData>,
     ) -> Self {
         Self {
             layout,
@@ -119,6 +119,55 @@ impl<'a, B: Brush> Run<'a, B> {
         }
     }
 
+    /// Returns true if a hyphen is displayed at the end of the run because
+    /// the line was broken at a hyphenation point.
+    pub fn is_hyphenated(&self) -> bool {
+        self.line_data.is_some_and(|d| d.is_hyphenated)
+    }
+
+    /// Returns true if the run displays the ellipsis at the end of a
+    /// truncated line.
+    pub fn is_ellipsis(&self) -> bool {
+        self.line_data.is_some_and(|d| d.is_ellipsis)
+    }
+
+    /// Returns an iterator over the glyphs in visual order, including the
+    /// hyphen displayed at the end of a hyphenated run and the glyphs of an
+    /// ellipsis.
+    pub(crate) fn visual_glyphs(&'a self) -> impl Iterator<Item = Glyph> + 'a + Clone {
+        let hyphen = self.hyphen_glyph();
+        let (before, after) = if self.is_rtl() {
+            (hyphen, None)
+        } else {
+            (None, hyphen)
+        };
+        let ellipsis = if self.is_ellipsis() {
+            &self.layout.ellipsis[..]
+        } else {
+            &[]
+        };
+        before
+            .into_iter()
+            .chain(self.visual_clusters().flat_map(|cluster| cluster.glyphs()))
+            .chain(after)
+            .chain(ellipsis.iter().copied())
+    }
+
+    fn hyphen_glyph(&self) -> Option<Glyph> {
+        let line_data = self.line_data.filter(|d| d.is_hyphenated)?;
+        let cluster = self
+            .layout
+            .clusters
+            .get(line_data.cluster_range.end.checked_sub(1)?)?;
+        Some(Glyph {
+            id: self.data.hyphen_id,
+            style_index: cluster.style_index,
+            x: 0.,
+            y: 0.,
+            advance: self.data.hyphen_advance,
+        })
+    }
+
     pub(crate) fn data(&self) -> &'a RunData {
         self.data
     }
@@ -22,7 +22,13 @@ mod shape;
 mod swash_convert;
 mod util;
 
+#[cfg(all(test, feature = "std"))]
+mod testing;
+
 pub mod context;
+#[cfg(feature = "std")]
+pub mod editor;
+pub mod hyphenation;
 pub mod layout;
 pub mod style;
 
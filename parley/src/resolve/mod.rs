@@ -10,10 +10,11 @@ pub mod tree;
 use alloc::{vec, vec::Vec};
 
 use super::style::{
-    Brush, FontFamily, FontFeature, FontSettings, FontStack, FontStretch, FontStyle, FontVariation,
-    FontWeight, StyleProperty,
+    Brush, Direction, FontFamily, FontFeature, FontSettings, FontStack, FontStretch, FontStyle,
+    FontVariation, FontWeight, Hyphens, LineHeight, StyleProperty, TabSize, TabStop, TextJustify,
 };
 use crate::font::FontContext;
+use crate::layout::Alignment;
 use crate::util::nearly_eq;
 use fontique::FamilyId;
 use swash::text::Language;
@@ -102,9 +103,11 @@ pub struct ResolveContext {
     families: Cache<FamilyId>,
     variations: Cache<Setting<f32>>,
     features: Cache<Setting<u16>>,
+    tab_stops: Cache<TabStop>,
     tmp_families: Vec<FamilyId>,
     tmp_variations: Vec<Setting<f32>>,
     tmp_features: Vec<Setting<u16>>,
+    tmp_tab_stops: Vec<TabStop>,
 }
 
 impl ResolveContext {
@@ -135,9 +138,29 @@ impl ResolveContext {
             }
             StyleProperty::StrikethroughSize(value) => StrikethroughSize(value.map(|x| x * scale)),
             StyleProperty::StrikethroughBrush(value) => StrikethroughBrush(value.clone()),
-            StyleProperty::LineHeight(value) => LineHeight(*value),
+            StyleProperty::LineHeight(value) => LineHeight(match *value {
+                crate::style::LineHeight::Absolute(height) => {
+                    crate::style::LineHeight::Absolute(height * scale)
+                }
+                height => height,
+            }),
             StyleProperty::WordSpacing(value) => WordSpacing(*value * scale),
             StyleProperty::LetterSpacing(value) => LetterSpacing(*value * scale),
+            StyleProperty::Hyphens(value) => Hyphens(*value),
+            StyleProperty::TextAlign(value) => TextAlign(*value),
+            StyleProperty::TextAlignLast(value) => TextAlignLast(*value),
+            StyleProperty::TextJustify(value) => TextJustify(*value),
+            StyleProperty::Direction(value) => Direction(*value),
+            StyleProperty::TextIndent(value) => TextIndent(*value * scale),
+            StyleProperty::HangingIndent(value) => HangingIndent(*value * scale),
+            StyleProperty::LineHeightStep(value) => LineHeightStep(*value * scale),
+            StyleProperty::TabSize(value) => TabSize(match *value {
+                crate::style::TabSize::Absolute(size) => {
+                    crate::style::TabSize::Absolute(size * scale)
+                }
+                size => size,
+            }),
+            StyleProperty::TabStops(value) => TabStops(self.resolve_tab_stops(value, scale)),
         }
     }
 
@@ -241,6 +264,23 @@ impl ResolveContext {
         resolved
     }
 
+    /// Resolves a list of tab stops, sorting them by position.
+    pub fn resolve_tab_stops(&mut self, stops: &[TabStop], scale: f32) -> Resolved<TabStop> {
+        if stops.is_empty() {
+            return Resolved::default();
+        }
+        self.tmp_tab_stops.clear();
+        self.tmp_tab_stops.extend(stops.iter().map(|stop| TabStop {
+            position: stop.position * scale,
+            ..*stop
+        }));
+        self.tmp_tab_stops
+            .sort_by(|a, b| a.position.total_cmp(&b.position));
+        let resolved = self.tab_stops.insert(&self.tmp_tab_stops);
+        self.tmp_tab_stops.clear();
+        resolved
+    }
+
     /// Returns the list of font families for the specified handle.
     pub fn stack(&self, stack: Resolved<FamilyId>) -> Option<&[FamilyId]> {
         self.families.get(stack)
@@ -256,11 +296,17 @@ impl ResolveContext {
         self.features.get(features)
     }
 
+    /// Returns the list of tab stops for the specified handle.
+    pub fn tab_stops(&self, stops: Resolved<TabStop>) -> Option<&[TabStop]> {
+        self.tab_stops.get(stops)
+    }
+
     /// Clears the resources in the context.
     pub fn clear(&mut self) {
         self.families.clear();
         self.variations.clear();
         self.features.clear();
+        self.tab_stops.clear();
     }
 }
 
@@ -301,12 +347,34 @@ pub enum ResolvedProperty<B: Brush> {
     StrikethroughSize(Option<f32>),
     /// Brush for rendering the strikethrough decoration.
     StrikethroughBrush(Option<B>),
-    /// Line height multiplier.
-    LineHeight(f32),
+    /// Line height.
+    LineHeight(LineHeight),
     /// Extra spacing between words.
     WordSpacing(f32),
     /// Extra spacing between letters.
     LetterSpacing(f32),
+    /// Hyphenation mode.
+    Hyphens(Hyphens),
+    /// Alignment of the paragraph.
+    TextAlign(Option<Alignment>),
+    /// Alignment of the last line of the paragraph.
+    TextAlignLast(Option<Alignment>),
+    /// Justification method of the paragraph.
+    TextJustify(TextJustify),
+    /// Base direction of the paragraph.
+    Direction(Direction),
+    /// Indentation of the first line of the paragraph.
+    TextIndent(f32),
+    /// Indentation of the remaining lines of the paragraph.
+    HangingIndent(f32),
+    /// Step to which the height of the lines of the paragraph is rounded.
+    LineHeightStep(f32),
+    /// Distance between the default tab stops.
+    TabSize(TabSize),
+    /// Explicit tab stops.
+    TabStops(Resolved<TabStop>),
+    /// Text that is being composed by an input method.
+    Preedit(bool),
 }
 
 /// Flattened group of style properties.
@@ -334,12 +402,34 @@ pub struct ResolvedStyle<B: Brush> {
     pub underline: ResolvedDecoration<B>,
     /// Strikethrough decoration.
     pub strikethrough: ResolvedDecoration<B>,
-    /// Line height multiplier.
-    pub line_height: f32,
+    /// Line height.
+    pub line_height: LineHeight,
     /// Extra spacing between words.
     pub word_spacing: f32,
     /// Extra spacing between letters.
     pub letter_spacing: f32,
+    /// Hyphenation mode.
+    pub hyphens: Hyphens,
+    /// Alignment of the paragraph.
+    pub text_align: Option<Alignment>,
+    /// Alignment of the last line of the paragraph.
+    pub text_align_last: Option<Alignment>,
+    /// Justification method of the paragraph.
+    pub text_justify: TextJustify,
+    /// Base direction of the paragraph.
+    pub direction: Direction,
+    /// Indentation of the first line of the paragraph.
+    pub text_indent: f32,
+    /// Indentation of the remaining lines of the paragraph.
+    pub hanging_indent: f32,
+    /// Step to which the height of the lines of the paragraph is rounded.
+    pub line_height_step: f32,
+    /// Distance between the default tab stops.
+    pub tab_size: TabSize,
+    /// Explicit tab stops.
+    pub tab_stops: Resolved<TabStop>,
+    /// True if the text is being composed by an input method.
+    pub preedit: bool,
 }
 
 impl<B: Brush> Default for ResolvedStyle<B> {
@@ -356,9 +446,20 @@ impl<B: Brush> Default for ResolvedStyle<B> {
             brush: Default::default(),
             underline: Default::default(),
             strikethrough: Default::default(),
-            line_height: 1.,
+            line_height: Default::default(),
             word_spacing: 0.,
             letter_spacing: 0.,
+            hyphens: Default::default(),
+            text_align: None,
+            text_align_last: None,
+            text_justify: Default::default(),
+            direction: Default::default(),
+            text_indent: 0.,
+            hanging_indent: 0.,
+            line_height_step: 0.,
+            tab_size: Default::default(),
+            tab_stops: Default::default(),
+            preedit: false,
         }
     }
 }
@@ -388,6 +489,17 @@ impl<B: Brush> ResolvedStyle<B> {
             LineHeight(value) => self.line_height = value,
             WordSpacing(value) => self.word_spacing = value,
             LetterSpacing(value) => self.letter_spacing = value,
+            Hyphens(value) => self.hyphens = value,
+            TextAlign(value) => self.text_align = value,
+            TextAlignLast(value) => self.text_align_last = value,
+            TextJustify(value) => self.text_justify = value,
+            Direction(value) => self.direction = value,
+            TextIndent(value) => self.text_indent = value,
+            HangingIndent(value) => self.hanging_indent = value,
+            LineHeightStep(value) => self.line_height_step = value,
+            TabSize(value) => self.tab_size = value,
+            TabStops(value) => self.tab_stops = value,
+            Preedit(value) => self.preedit = value,
         }
     }
 
@@ -411,9 +523,28 @@ impl<B: Brush> ResolvedStyle<B> {
             StrikethroughOffset(value) => self.strikethrough.offset == *value,
             StrikethroughSize(value) => self.strikethrough.size == *value,
             StrikethroughBrush(value) => self.strikethrough.brush == *value,
-            LineHeight(value) => nearly_eq(self.line_height, *value),
+            LineHeight(value) => {
+                use crate::style::LineHeight::*;
+                match (self.line_height, *value) {
+                    (MetricsRelative(a), MetricsRelative(b))
+                    | (FontSizeRelative(a), FontSizeRelative(b))
+                    | (Absolute(a), Absolute(b)) => nearly_eq(a, b),
+                    _ => false,
+                }
+            }
             WordSpacing(value) => nearly_eq(self.word_spacing, *value),
             LetterSpacing(value) => nearly_eq(self.letter_spacing, *value),
+            Hyphens(value) => self.hyphens == *value,
+            TextAlign(value) => self.text_align == *value,
+            TextAlignLast(value) => self.text_align_last == *value,
+            TextJustify(value) => self.text_justify == *value,
+            Direction(value) => self.direction == *value,
+            TextIndent(value) => nearly_eq(self.text_indent, *value),
+            HangingIndent(value) => nearly_eq(self.hanging_indent, *value),
+            LineHeightStep(value) => nearly_eq(self.line_height_step, *value),
+            TabSize(value) => self.tab_size == *value,
+            TabStops(value) => self.tab_stops == *value,
+            Preedit(value) => self.preedit == *value,
         }
     }
 }
@@ -2,3 +2,230 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
 //! Hierarchical tree based style application.
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+
+use super::range::RangedStyle;
+use super::*;
+
+/// Builder for constructing an ordered sequence of non-overlapping ranged
+/// styles from a tree of nested style spans and text.
+#[derive(Clone)]
+pub struct TreeStyleBuilder<B: Brush> {
+    /// Resolved styles of the currently open spans. The first entry is the
+    /// root style.
+    spans: Vec<ResolvedStyle<B>>,
+    /// Flattened styles for the text pushed so far.
+    styles: Vec<RangedStyle<B>>,
+    text: String,
+}
+
+impl<B: Brush> Default for TreeStyleBuilder<B> {
+    fn default() -> Self {
+        Self {
+            spans: vec![ResolvedStyle::default()],
+            styles: vec![],
+            text: String::new(),
+        }
+    }
+}
+
+impl<B: Brush> TreeStyleBuilder<B> {
+    /// Prepares the builder for accepting a new tree of spans and text.
+    pub fn begin(&mut self) {
+        self.spans.clear();
+        self.spans.push(ResolvedStyle::default());
+        self.styles.clear();
+        self.text.clear();
+    }
+
+    /// Returns the length of the text pushed so far.
+    pub fn text_len(&self) -> usize {
+        self.text.len()
+    }
+
+    /// Pushes a property that applies to the root style.
+    ///
+    /// Spans that are already open and text that has already been pushed
+    /// are not affected.
+    pub fn push_default(&mut self, property: ResolvedProperty<B>) {
+        self.spans[0].apply(property);
+    }
+
+    /// Opens a new span that inherits the style of the current span,
+    /// modified by the specified properties.
+    pub fn push_style_span(&mut self, properties: impl IntoIterator<Item = ResolvedProperty<B>>) {
+        let mut style = self.spans[self.spans.len() - 1].clone();
+        for property in properties {
+            style.apply(property);
+        }
+        self.spans.push(style);
+    }
+
+    /// Closes the current span, returning to the style of its parent. The
+    /// root span is never removed.
+    pub fn pop_style_span(&mut self) {
+        if self.spans.len() > 1 {
+            self.spans.pop();
+        }
+    }
+
+    /// Appends text with the style of the current span.
+    pub fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let style = &self.spans[self.spans.len() - 1];
+        let start = self.text.len();
+        self.text.push_str(text);
+        let end = self.text.len();
+        match self.styles.last_mut() {
+            Some(last) if last.style == *style => last.range.end = end,
+            _ => self.styles.push(RangedStyle {
+                style: style.clone(),
+                range: start..end,
+            }),
+        }
+    }
+
+    /// Computes the sequence of ranged styles and returns the full text.
+    ///
+    /// All spans except the root span are closed, so the builder can be
+    /// reused for another tree with the same root style.
+    pub fn finish(&mut self, styles: &mut Vec<RangedStyle<B>>) -> String {
+        if self.styles.is_empty() {
+            // Ensure that empty text still has a style.
+            styles.push(RangedStyle {
+                style: self.spans[0].clone(),
+                range: 0..0,
+            });
+        }
+        styles.append(&mut self.styles);
+        self.spans.truncate(1);
+        core::mem::take(&mut self.text)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::context::LayoutContext;
+    use crate::layout::{Alignment, Layout, PositionedLayoutItem};
+    use crate::style::StyleProperty;
+    use crate::testing::{self, Brush};
+
+    const RED: Brush = [255, 0, 0, 255];
+    const BLUE: Brush = [0, 0, 255, 255];
+
+    /// Returns the number of glyphs, brush and font size of each glyph run.
+    fn glyph_runs(layout: &Layout<Brush>) -> Vec<(usize, Brush, f32)> {
+        let mut runs = vec![];
+        for line in layout.lines() {
+            for item in line.items() {
+                if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                    runs.push((
+                        glyph_run.positioned_glyphs().count(),
+                        glyph_run.style().brush,
+                        glyph_run.run().font_size(),
+                    ));
+                }
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn nested_spans() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut builder = testing::tree_builder(&mut fcx, &mut lcx);
+        builder.push_text("ab");
+        builder.push_style_span(&[StyleProperty::Brush(RED)]);
+        builder.push_text("cd");
+        builder.push_style_span(&[StyleProperty::FontSize(20.)]);
+        builder.push_text("ef");
+        builder.pop_style_span();
+        builder.push_text("gh");
+        // Spans with the same style as their parent do not split glyph
+        // runs.
+        builder.push_style_span(&[StyleProperty::Brush(RED)]);
+        builder.push_text("ij");
+        builder.pop_style_span();
+        builder.pop_style_span();
+        // The root span is never closed.
+        builder.pop_style_span();
+        builder.push_style_span(&[StyleProperty::Brush(BLUE)]);
+        builder.push_text("");
+        builder.pop_style_span();
+        builder.push_text("kl");
+        let (mut layout, text) = builder.build();
+        layout.break_all_lines(None, Alignment::Start);
+        assert_eq!(text, "abcdefghijkl");
+        let black = Brush::default();
+        assert_eq!(
+            glyph_runs(&layout),
+            [
+                (2, black, 10.),
+                (2, RED, 10.),
+                (2, RED, 20.),
+                (4, RED, 10.),
+                (2, black, 10.),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_tree() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut builder = testing::tree_builder(&mut fcx, &mut lcx);
+        builder.push_style_span(&[StyleProperty::FontSize(20.)]);
+        let (mut layout, text) = builder.build();
+        layout.break_all_lines(None, Alignment::Start);
+        assert!(text.is_empty());
+        // Empty text has the height of a line in the root style.
+        assert_eq!(layout.height(), 10.);
+    }
+
+    #[test]
+    fn push_default_after_text() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut builder = testing::tree_builder(&mut fcx, &mut lcx);
+        builder.push_text("ab");
+        builder.push_style_span(&[StyleProperty::FontSize(20.)]);
+        // Late root properties only apply to text pushed afterwards in the
+        // root span.
+        builder.push_default(&StyleProperty::Brush(RED));
+        builder.push_text("cd");
+        builder.pop_style_span();
+        builder.push_text("ef");
+        let (mut layout, _) = builder.build();
+        layout.break_all_lines(None, Alignment::Start);
+        let black = Brush::default();
+        assert_eq!(
+            glyph_runs(&layout),
+            [(2, black, 10.), (2, black, 20.), (2, RED, 10.)]
+        );
+    }
+
+    #[test]
+    fn reuse_after_build() {
+        let mut fcx = testing::font_context();
+        let mut lcx = LayoutContext::new();
+        let mut builder = testing::tree_builder(&mut fcx, &mut lcx);
+        builder.push_style_span(&[StyleProperty::Brush(RED)]);
+        builder.push_text("ab");
+        let (_, text) = builder.build();
+        assert_eq!(text, "ab");
+        // The next tree starts from the root span with the same root style.
+        builder.push_text("cde");
+        builder.push_style_span(&[StyleProperty::FontSize(20.)]);
+        builder.push_text("f");
+        let (mut layout, text) = builder.build();
+        layout.break_all_lines(None, Alignment::Start);
+        assert_eq!(text, "cdef");
+        let black = Brush::default();
+        assert_eq!(glyph_runs(&layout), [(3, black, 10.), (1, black, 20.)]);
+    }
+}
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
 #[cfg(feature = "std")]
-use super::layout::Layout;
+use super::layout::{InlineBox, Layout};
 use super::resolve::range::RangedStyle;
 use super::resolve::{ResolveContext, Resolved};
 use super::style::{Brush, FontFeature, FontVariation};
@@ -19,6 +19,19 @@ use swash::text::cluster::{CharCluster, CharInfo, Token};
 use swash::text::{Language, Script};
 use swash::{FontRef, Synthesis};
 
+#[cfg(feature = "std")]
+use super::layout::data::{ClusterData, LayoutData, LayoutItem, RunData};
+#[cfg(feature = "std")]
+use super::layout::Glyph;
+#[cfg(feature = "std")]
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "std")]
+use core::mem::size_of;
+#[cfg(feature = "std")]
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
 struct Item {
     style_index: u16,
     size: f32,
@@ -37,90 +50,146 @@ pub fn shape_text<'a, B: Brush>(
     rcx: &'a ResolveContext,
     mut fq: Query<'a>,
     styles: &'a [RangedStyle<B>],
+    inline_boxes: &[InlineBox],
     infos: &[(CharInfo, u16)],
     levels: &[u8],
     scx: &mut ShapeContext,
+    cache: &mut ShapeCache<B>,
     text: &str,
+    range: Range<usize>,
     layout: &mut Layout<B>,
 ) {
-    if text.is_empty() || styles.is_empty() {
+    if range.is_empty() || styles.is_empty() {
         return;
     }
-    let mut style = &styles[0].style;
+    cache.serial += 1;
+    let char_start = text[..range.start].chars().count();
+    // Index of the next inline box to be emitted.
+    let mut box_index = inline_boxes.partition_point(|inline_box| inline_box.index < range.start);
+    // Index of the next paragraph to be started.
+    let mut paragraph_index = layout
+        .data
+        .paragraphs
+        .partition_point(|paragraph| paragraph.text_range.start <= range.start);
+    let style_index = infos[char_start].1;
+    let mut style = &styles[style_index as usize].style;
     let mut item = Item {
-        style_index: 0,
+        style_index,
         size: style.font_size,
-        level: levels.first().copied().unwrap_or(0),
-        script: infos
-            .iter()
-            .map(|x| x.0.script())
-            .find(|&script| real_script(script))
-            .unwrap_or(Script::Latin),
+        level: levels.get(char_start).copied().unwrap_or(0),
+        script: paragraph_script(&infos[char_start..]),
         locale: style.locale,
         variations: style.font_variations,
         features: style.font_features,
         word_spacing: style.word_spacing,
         letter_spacing: style.letter_spacing,
     };
-    let mut char_range = 0..0;
-    let mut text_range = 0..0;
+    let mut char_range = char_start..char_start;
+    let mut text_range = range.start..range.start;
+    let is_vertical = layout.data.writing_mode.is_vertical();
+    // Feature settings for vertical layouts, which additionally enable the
+    // vertical alternates.
+    let mut vertical_features = vec![];
     macro_rules! shape_item {
         () => {
             let item_text = &text[text_range.clone()];
-            let item_infos = &infos[char_range.start..];
-            let first_style_index = item_infos[0].1;
-            let mut fs = FontSelector::new(
-                &mut fq,
-                rcx,
-                styles,
-                first_style_index,
-                item.script,
-                item.locale,
-            );
-            let options = partition::SimpleShapeOptions {
-                size: item.size,
-                script: item.script,
-                language: item.locale,
-                direction: if item.level & 1 != 0 {
-                    Direction::RightToLeft
-                } else {
-                    Direction::LeftToRight
-                },
-                variations: rcx.variations(item.variations).unwrap_or(&[]),
-                features: rcx.features(item.features).unwrap_or(&[]),
-                insert_dotted_circles: false,
+            let item_infos = &infos[char_range.clone()];
+            let first_run = layout.data.runs.len();
+            // Reuse the runs of an identical item from a previous layout.
+            let is_cached = cache.is_enabled() && {
+                cache.set_key(rcx, styles, &item, item_text, item_infos, is_vertical);
+                cache.get(&mut layout.data, text_range.start)
             };
-            partition::shape(
-                scx,
-                &mut fs,
-                &options,
-                item_text.char_indices().zip(item_infos).map(
-                    |((offset, ch), (info, style_index))| Token {
-                        ch,
-                        offset: (text_range.start + offset) as u32,
-                        len: ch.len_utf8() as u8,
-                        info: *info,
-                        data: *style_index as _,
+            if !is_cached {
+                let first_style_index = item_infos[0].1;
+                let mut fs = FontSelector::new(
+                    &mut fq,
+                    rcx,
+                    styles,
+                    first_style_index,
+                    item.script,
+                    item.locale,
+                );
+                let mut features = rcx.features(item.features).unwrap_or(&[]);
+                if is_vertical {
+                    vertical_features.clear();
+                    vertical_features.extend_from_slice(features);
+                    for tag in [b"vert", b"vrt2"] {
+                        vertical_features.push(swash::Setting {
+                            tag: swash::tag_from_bytes(tag),
+                            value: 1,
+                        });
+                    }
+                    features = &vertical_features;
+                }
+                let options = partition::SimpleShapeOptions {
+                    size: item.size,
+                    script: item.script,
+                    language: item.locale,
+                    direction: if item.level & 1 != 0 {
+                        Direction::RightToLeft
+                    } else {
+                        Direction::LeftToRight
                     },
-                ),
-                |font, shaper| {
-                    layout.data.push_run(
-                        Font::new(font.font.blob.clone(), font.font.index),
-                        item.size,
-                        font.synthesis,
-                        shaper,
-                        item.level,
-                        item.word_spacing,
-                        item.letter_spacing,
-                    );
-                },
-            );
+                    variations: rcx.variations(item.variations).unwrap_or(&[]),
+                    features,
+                    insert_dotted_circles: false,
+                };
+                partition::shape(
+                    scx,
+                    &mut fs,
+                    &options,
+                    item_text.char_indices().zip(item_infos).map(
+                        |((offset, ch), (info, style_index))| Token {
+                            ch,
+                            offset: (text_range.start + offset) as u32,
+                            len: ch.len_utf8() as u8,
+                            info: *info,
+                            data: *style_index as _,
+                        },
+                    ),
+                    |font, shaper| {
+                        layout.data.push_run(
+                            Font::new(font.font.blob.clone(), font.font.index),
+                            item.size,
+                            font.synthesis,
+                            shaper,
+                            item.level,
+                            item.word_spacing,
+                            item.letter_spacing,
+                        );
+                    },
+                );
+                cache.insert(
+                    &layout.data,
+                    first_run..layout.data.runs.len(),
+                    text_range.start,
+                );
+            }
         };
     }
-    for ((char_index, ch), (info, style_index)) in text.chars().enumerate().zip(infos) {
+    for ((char_index, (byte_index, ch)), (info, style_index)) in text[range.clone()]
+        .char_indices()
+        .enumerate()
+        .map(|(i, (offset, ch))| (char_start + i, (range.start + offset, ch)))
+        .zip(&infos[char_start..])
+    {
         let mut break_run = false;
+        // Paragraphs are shaped independently so that they can be reshaped
+        // individually after an edit.
+        let at_paragraph = layout
+            .data
+            .paragraphs
+            .get(paragraph_index)
+            .is_some_and(|paragraph| paragraph.text_range.start == byte_index);
         let mut script = info.script();
-        if !real_script(script) {
+        if at_paragraph {
+            paragraph_index += 1;
+            break_run = true;
+            if !real_script(script) {
+                script = paragraph_script(&infos[char_index..]);
+            }
+        } else if !real_script(script) {
             script = item.script;
         }
         let level = levels.get(char_index).copied().unwrap_or(0);
@@ -137,8 +206,22 @@ pub fn shape_text<'a, B: Brush>(
                 break_run = true;
             }
         }
-        if break_run || level != item.level || script != item.script {
-            shape_item!();
+        // Runs are split at inline boxes so that boxes can be placed between
+        // them during line breaking.
+        let at_box = inline_boxes
+            .get(box_index)
+            .is_some_and(|inline_box| inline_box.index <= byte_index);
+        if at_box || break_run || level != item.level || script != item.script {
+            if !text_range.is_empty() {
+                shape_item!();
+            }
+            while let Some(inline_box) = inline_boxes.get(box_index) {
+                if inline_box.index > byte_index {
+                    break;
+                }
+                layout.data.push_inline_box(box_index, level);
+                box_index += 1;
+            }
             item.size = style.font_size;
             item.level = level;
             item.script = script;
@@ -154,12 +237,381 @@ pub fn shape_text<'a, B: Brush>(
     if !text_range.is_empty() {
         shape_item!();
     }
+    if range.end == text.len() {
+        // Emit any boxes placed at the end of the text.
+        for index in box_index..inline_boxes.len() {
+            layout.data.push_inline_box(index, item.level);
+        }
+    }
+}
+
+/// Cache of shaped items, keyed by the text of each item and all of the
+/// style properties that affect shaping.
+#[cfg(feature = "std")]
+pub struct ShapeCache<B: Brush> {
+    entries: HashMap<ShapeKey, CacheEntry<B>>,
+    /// Key of the item being shaped.
+    key: ShapeKey,
+    /// Indices of the styles of the item being shaped, in order of first
+    /// use. Cached clusters and glyphs refer to styles by their position in
+    /// this list.
+    styles: Vec<u16>,
+    /// Maximum total size of the entries in bytes.
+    capacity: usize,
+    /// Current total size of the entries in bytes.
+    size: usize,
+    /// Number of layouts that have been shaped.
+    serial: u64,
+}
+
+#[cfg(feature = "std")]
+impl<B: Brush> ShapeCache<B> {
+    /// Returns true if the cache has a nonzero capacity.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    /// Returns the maximum total size of the cache in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the maximum total size of the cache in bytes, evicting the least
+    /// recently used entries if necessary.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(capacity);
+    }
+
+    /// Removes all entries that have not been used in the last `max_age`
+    /// layouts.
+    pub fn prune(&mut self, max_age: u64) {
+        let serial = self.serial;
+        let mut size = self.size;
+        self.entries.retain(|_, entry| {
+            let keep = serial.saturating_sub(entry.serial) < max_age;
+            if !keep {
+                size -= entry.size;
+            }
+            keep
+        });
+        self.size = size;
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    /// Sets the generation of the font collection used for shaping, so that
+    /// items shaped with a different set of fonts are not reused.
+    pub fn set_generation(&mut self, generation: u64) {
+        self.key.generation = generation;
+    }
+
+    /// Sets the key for the item with the specified text and character
+    /// information.
+    fn set_key(
+        &mut self,
+        rcx: &ResolveContext,
+        styles: &[RangedStyle<B>],
+        item: &Item,
+        text: &str,
+        infos: &[(CharInfo, u16)],
+        is_vertical: bool,
+    ) {
+        let key = &mut self.key;
+        key.text.clear();
+        key.text.push_str(text);
+        key.chars.clear();
+        key.fonts.clear();
+        key.families.clear();
+        self.styles.clear();
+        for (info, style_index) in infos {
+            let index = match self.styles.iter().position(|index| index == style_index) {
+                Some(index) => index,
+                None => {
+                    let style = &styles[*style_index as usize].style;
+                    let families = rcx.stack(style.font_stack).unwrap_or(&[]);
+                    key.families.extend_from_slice(families);
+                    key.fonts.push((
+                        families.len(),
+                        fontique::Attributes {
+                            stretch: style.font_stretch,
+                            weight: style.font_weight,
+                            style: style.font_style,
+                        },
+                    ));
+                    self.styles.push(*style_index);
+                    self.styles.len() - 1
+                }
+            };
+            key.chars.push((info.boundary() as u8, index as u16));
+        }
+        key.size = item.size;
+        key.script = item.script;
+        key.level = item.level;
+        key.locale = item.locale;
+        key.variations.clear();
+        key.variations.extend(
+            rcx.variations(item.variations)
+                .unwrap_or(&[])
+                .iter()
+                .map(|setting| (setting.tag, setting.value)),
+        );
+        key.features.clear();
+        key.features.extend(
+            rcx.features(item.features)
+                .unwrap_or(&[])
+                .iter()
+                .map(|setting| (setting.tag, setting.value)),
+        );
+        key.word_spacing = item.word_spacing;
+        key.letter_spacing = item.letter_spacing;
+        key.is_vertical = is_vertical;
+    }
+
+    /// Appends the runs cached for the current key to the layout, placing
+    /// them at the specified text offset. Returns false if there is no entry
+    /// for the key.
+    fn get(&mut self, data: &mut LayoutData<B>, offset: usize) -> bool {
+        let Some(entry) = self.entries.get_mut(&self.key) else {
+            return false;
+        };
+        entry.serial = self.serial;
+        let styles = &self.styles;
+        data.copy_runs(
+            &entry.data,
+            0..entry.data.runs.len(),
+            |text_offset| text_offset + offset,
+            |index| styles[index as usize],
+        );
+        true
+    }
+
+    /// Stores the specified runs of the layout, which were shaped from the
+    /// item at the specified text offset, under the current key.
+    fn insert(&mut self, data: &LayoutData<B>, runs: Range<usize>, offset: usize) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut entry = CacheEntry {
+            data: LayoutData::default(),
+            size: 0,
+            serial: self.serial,
+        };
+        let styles = &self.styles;
+        entry.data.copy_runs(
+            data,
+            runs,
+            |text_offset| text_offset - offset,
+            |index| {
+                styles
+                    .iter()
+                    .position(|style_index| *style_index == index)
+                    .unwrap_or_default() as u16
+            },
+        );
+        let key = &self.key;
+        let data = &entry.data;
+        entry.size = size_of::<(ShapeKey, CacheEntry<B>)>()
+            + key.text.len()
+            + key.chars.len() * size_of::<(u8, u16)>()
+            + key.fonts.len() * size_of::<(usize, fontique::Attributes)>()
+            + key.families.len() * size_of::<fontique::FamilyId>()
+            + key.variations.len() * size_of::<(swash::Tag, f32)>()
+            + key.features.len() * size_of::<(swash::Tag, u16)>()
+            + data.fonts.len() * size_of::<Font>()
+            + data.coords.len() * size_of::<i16>()
+            + data.items.len() * size_of::<LayoutItem>()
+            + data.runs.len() * size_of::<RunData>()
+            + data.clusters.len() * size_of::<ClusterData>()
+            + data.glyphs.len() * size_of::<Glyph>();
+        if entry.size > self.capacity {
+            return;
+        }
+        self.size += entry.size;
+        if let Some(old) = self.entries.insert(self.key.clone(), entry) {
+            self.size -= old.size;
+        }
+        if self.size > self.capacity {
+            // Evict down to three quarters of the capacity so that the cost
+            // of eviction is amortized over several insertions.
+            self.evict(self.capacity / 4 * 3);
+        }
+    }
+
+    /// Evicts the least recently used entries until the total size is at
+    /// most `size`.
+    fn evict(&mut self, size: usize) {
+        if self.size <= size {
+            return;
+        }
+        let mut ages: Vec<_> = self
+            .entries
+            .values()
+            .map(|entry| (entry.serial, entry.size))
+            .collect();
+        ages.sort_unstable_by(|a, b| b.cmp(a));
+        // Keep the most recently used entries that fit, breaking at the
+        // first serial that does not fit entirely so that all entries with
+        // the same serial are treated alike.
+        let mut kept = 0;
+        let mut min_serial = u64::MAX;
+        let mut i = 0;
+        while i < ages.len() {
+            let serial = ages[i].0;
+            let group_size: usize = ages[i..]
+                .iter()
+                .take_while(|age| age.0 == serial)
+                .map(|age| age.1)
+                .sum();
+            if kept + group_size > size {
+                break;
+            }
+            kept += group_size;
+            min_serial = serial;
+            i += ages[i..].iter().take_while(|age| age.0 == serial).count();
+        }
+        if i == ages.len() {
+            return;
+        }
+        self.entries.retain(|_, entry| entry.serial >= min_serial);
+        self.size = kept;
+    }
+}
+
+#[cfg(feature = "std")]
+impl<B: Brush> Default for ShapeCache<B> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            key: ShapeKey::default(),
+            styles: vec![],
+            capacity: 0,
+            size: 0,
+            serial: 0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+struct CacheEntry<B: Brush> {
+    /// Runs shaped from the item with text offsets relative to the start of
+    /// the item.
+    data: LayoutData<B>,
+    /// Approximate size of the entry in bytes.
+    size: usize,
+    /// Serial of the last layout that used the entry.
+    serial: u64,
+}
+
+/// Input for shaping an item.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+struct ShapeKey {
+    text: String,
+    /// Boundary of each character and the position of its style in the
+    /// list of fonts.
+    chars: Vec<(u8, u16)>,
+    /// Number of families in the font stack and font attributes of each
+    /// style.
+    fonts: Vec<(usize, fontique::Attributes)>,
+    /// Concatenated font stacks of all styles.
+    families: Vec<fontique::FamilyId>,
+    size: f32,
+    script: Script,
+    level: u8,
+    locale: Option<Language>,
+    variations: Vec<(swash::Tag, f32)>,
+    features: Vec<(swash::Tag, u16)>,
+    word_spacing: f32,
+    letter_spacing: f32,
+    is_vertical: bool,
+    /// Generation of the font collection.
+    generation: u64,
+}
+
+#[cfg(feature = "std")]
+impl Default for ShapeKey {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            chars: vec![],
+            fonts: vec![],
+            families: vec![],
+            size: 0.,
+            script: Script::Unknown,
+            level: 0,
+            locale: None,
+            variations: vec![],
+            features: vec![],
+            word_spacing: 0.,
+            letter_spacing: 0.,
+            is_vertical: false,
+            generation: 0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl PartialEq for ShapeKey {
+    fn eq(&self, other: &Self) -> bool {
+        // Floats are compared by their bits to be consistent with the hash.
+        self.text == other.text
+            && self.chars == other.chars
+            && self.fonts == other.fonts
+            && self.families == other.families
+            && self.size.to_bits() == other.size.to_bits()
+            && self.script == other.script
+            && self.level == other.level
+            && self.locale == other.locale
+            && self.variations.len() == other.variations.len()
+            && self
+                .variations
+                .iter()
+                .zip(&other.variations)
+                .all(|(a, b)| a.0 == b.0 && a.1.to_bits() == b.1.to_bits())
+            && self.features == other.features
+            && self.word_spacing.to_bits() == other.word_spacing.to_bits()
+            && self.letter_spacing.to_bits() == other.letter_spacing.to_bits()
+            && self.is_vertical == other.is_vertical
+            && self.generation == other.generation
+    }
+}
+
+#[cfg(feature = "std")]
+impl Eq for ShapeKey {}
+
+#[cfg(feature = "std")]
+impl Hash for ShapeKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The remaining fields are rarely the only difference between items
+        // with the same text.
+        self.text.hash(state);
+        self.chars.hash(state);
+        self.size.to_bits().hash(state);
+        self.level.hash(state);
+    }
 }
 
 fn real_script(script: Script) -> bool {
     script != Script::Common && script != Script::Unknown && script != Script::Inherited
 }
 
+/// Returns the first real script of the paragraph beginning with the
+/// specified characters.
+#[cfg(feature = "std")]
+fn paragraph_script(infos: &[(CharInfo, u16)]) -> Script {
+    infos
+        .iter()
+        .take_while(|info| info.0.bidi_class() != swash::text::BidiClass::B)
+        .map(|info| info.0.script())
+        .find(|&script| real_script(script))
+        .unwrap_or(Script::Latin)
+}
+
 struct FontSelector<'a, 'b, B: Brush> {
     query: &'b mut Query<'a>,
     fonts_id: Option<usize>,
@@ -247,6 +699,9 @@ impl<'a, 'b, B: Brush> partition::Selector for FontSelector<'a, 'b, B> {
             self.variations = variations;
             self.features = features;
         }
+        // Fonts with unicode ranges that exclude the cluster are skipped
+        // without loading their data.
+        self.query.set_chars(cluster.chars().iter().map(|ch| ch.ch));
         let mut selected_font = None;
         self.query.matches_with(|font| {
             if let Ok(font_ref) = skrifa::FontRef::from_index(font.blob.as_ref(), font.index) {
@@ -308,3 +763,50 @@ impl partition::SelectedFont for SelectedFont {
         Some(self.synthesis)
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::context::LayoutContext;
+    use crate::style::{FontFamily, FontStack, StyleProperty};
+    use crate::testing::{self, Brush};
+
+    #[test]
+    fn unicode_ranges() {
+        // Both fonts contain all characters of the text.
+        let descriptors = [0x61..=0x7A, 0x41..=0x5A].map(|ranges| {
+            let mut descriptor = testing::descriptor("Subset");
+            descriptor.unicode_ranges = Some(vec![ranges]);
+            descriptor
+        });
+        let mut fcx = testing::font_context_with(&descriptors);
+        let mut lcx = LayoutContext::<Brush>::new();
+        let mut builder = lcx.ranged_builder(&mut fcx, "abCDe", 1.);
+        builder.push_default(&StyleProperty::FontStack(FontStack::Single(
+            FontFamily::Named("Subset"),
+        )));
+        let layout = builder.build();
+        let ranges: Vec<_> = layout.runs().map(|run| run.text_range()).collect();
+        assert_eq!(ranges, [0..2, 2..4, 4..5]);
+        let fonts: Vec<_> = layout.runs().map(|run| run.font().data.id()).collect();
+        assert_eq!(fonts[0], fonts[2]);
+        assert_ne!(fonts[0], fonts[1]);
+    }
+
+    #[test]
+    fn shape_key_floats() {
+        use super::ShapeKey;
+        use std::collections::HashSet;
+        let key = |size: f32, letter_spacing: f32| ShapeKey {
+            size,
+            letter_spacing,
+            ..ShapeKey::default()
+        };
+        // Keys that hash differently are never equal, and every key equals
+        // itself.
+        assert!(key(0., 0.) != key(-0., 0.));
+        assert!(key(0., 0.) != key(0., -0.));
+        let keys = HashSet::from([key(0., 0.), key(-0., 0.), key(f32::NAN, f32::NAN)]);
+        assert_eq!(keys.len(), 3);
+        assert!(keys.contains(&key(f32::NAN, f32::NAN)));
+    }
+}
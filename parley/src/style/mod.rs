@@ -7,12 +7,27 @@ mod brush;
 mod font;
 
 pub use brush::*;
+
+use crate::layout::Alignment;
 pub use font::{
     FontFamily, FontFeature, FontSettings, FontStack, FontStretch, FontStyle, FontVariation,
     FontWeight, GenericFamily,
 };
 
 /// Properties that define a style.
+///
+/// [`TextAlign`], [`TextAlignLast`], [`TextJustify`], [`Direction`],
+/// [`TextIndent`], [`HangingIndent`] and [`LineHeightStep`] apply to whole
+/// paragraphs and are taken from the style of the first character of each
+/// paragraph.
+///
+/// [`TextAlign`]: Self::TextAlign
+/// [`TextAlignLast`]: Self::TextAlignLast
+/// [`TextJustify`]: Self::TextJustify
+/// [`Direction`]: Self::Direction
+/// [`TextIndent`]: Self::TextIndent
+/// [`HangingIndent`]: Self::HangingIndent
+/// [`LineHeightStep`]: Self::LineHeightStep
 #[derive(Clone, PartialEq, Debug)]
 pub enum StyleProperty<'a, B: Brush> {
     /// Font family stack.
@@ -49,10 +64,172 @@ pub enum StyleProperty<'a, B: Brush> {
     StrikethroughSize(Option<f32>),
     /// Brush for rendering the strikethrough decoration.
     StrikethroughBrush(Option<B>),
-    /// Line height multiplier.
-    LineHeight(f32),
+    /// Height of the lines containing the text.
+    LineHeight(LineHeight),
     /// Extra spacing between words.
     WordSpacing(f32),
     /// Extra spacing between letters.
     LetterSpacing(f32),
+    /// Controls how words may be hyphenated when breaking lines.
+    Hyphens(Hyphens),
+    /// Alignment of the lines of a paragraph, or `None` for the layout alignment.
+    TextAlign(Option<Alignment>),
+    /// Alignment of the last line of a paragraph, or `None` to follow `TextAlign`.
+    TextAlignLast(Option<Alignment>),
+    /// Method used to stretch justified lines.
+    TextJustify(TextJustify),
+    /// Base direction of a paragraph.
+    Direction(Direction),
+    /// Indentation of the first line of a paragraph from its start edge.
+    TextIndent(f32),
+    /// Indentation of all lines of a paragraph except the first.
+    HangingIndent(f32),
+    /// Step to which line heights are rounded up, or zero to disable rounding.
+    LineHeightStep(f32),
+    /// Distance between the default tab stops.
+    TabSize(TabSize),
+    /// Explicit tab stops, which precede the default tab stops.
+    ///
+    /// The stops of a tab are taken from the style of the tab character.
+    /// Stops are only applied by the greedy line breaking strategy. Other
+    /// strategies advance each tab to the first default tab stop.
+    TabStops(&'a [TabStop]),
+}
+
+/// Height of a line.
+///
+/// <https://developer.mozilla.org/en-US/docs/Web/CSS/line-height>
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LineHeight {
+    /// Multiple of the ascent, descent and line gap of the font.
+    MetricsRelative(f32),
+    /// Multiple of the font size. The difference from the ascent and
+    /// descent of the font is split evenly above and below the text.
+    FontSizeRelative(f32),
+    /// Absolute height. The difference from the ascent and descent of the
+    /// font is split evenly above and below the text.
+    Absolute(f32),
+}
+
+impl LineHeight {
+    /// Line height determined by the metrics of the font, which is the
+    /// `normal` line height of CSS.
+    pub const NORMAL: Self = Self::MetricsRelative(1.);
+}
+
+impl Default for LineHeight {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+impl From<f32> for LineHeight {
+    /// Converts a multiple of the font metrics, which is how line heights
+    /// were specified before other units were supported.
+    fn from(value: f32) -> Self {
+        Self::MetricsRelative(value)
+    }
+}
+
+/// Method used to stretch the lines of a justified paragraph.
+///
+/// <https://developer.mozilla.org/en-US/docs/Web/CSS/text-justify>
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum TextJustify {
+    /// Expands spaces, along with the space between the characters of
+    /// scripts that do not separate words with spaces, such as Chinese,
+    /// Japanese and Thai.
+    #[default]
+    Auto,
+    /// Expands spaces only.
+    InterWord,
+    /// Expands the space between all characters.
+    InterCharacter,
+    /// Elongates the connections between joined Arabic letters by inserting
+    /// tatweel glyphs. Spaces are expanded on lines without any such
+    /// connections.
+    Kashida,
+}
+
+/// Distance between the default tab stops, which are placed at multiples of
+/// this distance from the start edge of each line.
+///
+/// <https://developer.mozilla.org/en-US/docs/Web/CSS/tab-size>
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TabSize {
+    /// Multiple of the advance of a space in the font of the tab.
+    Spaces(f32),
+    /// Absolute distance.
+    Absolute(f32),
+}
+
+impl Default for TabSize {
+    fn default() -> Self {
+        Self::Spaces(8.)
+    }
+}
+
+/// Position at which the text following a tab is aligned.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TabStop {
+    /// Distance of the stop from the start edge of the line.
+    pub position: f32,
+    /// Alignment of the text following the tab relative to the stop.
+    pub alignment: TabAlignment,
+}
+
+impl TabStop {
+    /// Creates a new tab stop with the specified position and alignment.
+    pub fn new(position: f32, alignment: TabAlignment) -> Self {
+        Self {
+            position,
+            alignment,
+        }
+    }
+}
+
+/// Alignment of the text following a tab relative to its tab stop.
+///
+/// The aligned text extends up to the next tab or explicit line break.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum TabAlignment {
+    /// The text starts at the stop. This is a left tab in left-to-right
+    /// text.
+    #[default]
+    Start,
+    /// The text is centered on the stop.
+    Center,
+    /// The text ends at the stop. This is a right tab in left-to-right
+    /// text.
+    End,
+    /// The first occurrence of the specified separator in the text is
+    /// placed at the stop, or the text ends at the stop if there is none.
+    Decimal(char),
+}
+
+/// Base direction of a paragraph.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum Direction {
+    /// The direction is determined by the first strong directional
+    /// character in the paragraph, defaulting to left-to-right if there is
+    /// none.
+    #[default]
+    Auto,
+    /// Left-to-right.
+    LeftToRight,
+    /// Right-to-left.
+    RightToLeft,
+}
+
+/// Controls how words may be hyphenated when breaking lines.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum Hyphens {
+    /// Words are never hyphenated, even at soft hyphens.
+    None,
+    /// Words are only hyphenated at soft hyphens (U+00AD).
+    #[default]
+    Manual,
+    /// Words are hyphenated at soft hyphens and at the points suggested by
+    /// the hyphenator registered for the locale of the text.
+    Auto,
 }
@@ -0,0 +1,198 @@
+// Copyright 2024 the Parley Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Helpers shared by the unit tests.
+//!
+//! Layouts are built with the generated font in the `testdata` directory,
+//! which advances every character by one em, except for the soft hyphen.
+//! At the default test size of 10 each character is 10 units wide and each
+//! line is 10 units tall.
+
+use core::fmt::Write as _;
+use core::ops::Range;
+
+use fontique::{Collection, CollectionOptions, FontDescriptor};
+
+use crate::context::{LayoutContext, RangedBuilder, TreeBuilder};
+use crate::font::FontContext;
+use crate::layout::{Alignment, InlineBox, Layout, PositionedLayoutItem, WritingMode};
+use crate::style::{FontFamily, FontStack, StyleProperty};
+
+pub(crate) const FONT: &[u8] = include_bytes!("../../testdata/ParleyTest.ttf");
+pub(crate) const FAMILY: &str = "Parley Test";
+pub(crate) const FONT_SIZE: f32 = 10.;
+
+pub(crate) type Brush = [u8; 4];
+
+/// Returns a font context that only contains the test font.
+pub(crate) fn font_context() -> FontContext {
+    let mut fcx = font_context_with(&[]);
+    fcx.collection.register_fonts(FONT.to_vec());
+    fcx
+}
+
+/// Returns a font context that contains the test font registered with each
+/// of the descriptors.
+pub(crate) fn font_context_with(descriptors: &[FontDescriptor]) -> FontContext {
+    let mut collection = Collection::new(CollectionOptions {
+        shared: false,
+        system_fonts: false,
+    });
+    for descriptor in descriptors {
+        collection.register_fonts_with_descriptor(FONT.to_vec(), descriptor);
+    }
+    FontContext {
+        collection,
+        ..Default::default()
+    }
+}
+
+/// Returns a descriptor that adds the test font to the named family.
+pub(crate) fn descriptor(family_name: &str) -> FontDescriptor {
+    let mut descriptor = FontDescriptor::default();
+    descriptor.family_name = Some(family_name.into());
+    descriptor
+}
+
+/// Returns the properties that select the test font at the test size.
+pub(crate) fn default_style() -> [StyleProperty<'static, Brush>; 2] {
+    [
+        StyleProperty::FontStack(FontStack::Single(FontFamily::Named(FAMILY))),
+        StyleProperty::FontSize(FONT_SIZE),
+    ]
+}
+
+/// Content of a test layout in addition to its text.
+#[derive(Default)]
+pub(crate) struct Content<'a> {
+    /// Properties that apply to the full text, following the default style.
+    pub style: &'a [StyleProperty<'a, Brush>],
+    /// Properties that apply to ranges of the text.
+    pub spans: &'a [(StyleProperty<'a, Brush>, Range<usize>)],
+    /// Inline boxes, in any order.
+    pub boxes: &'a [InlineBox],
+    pub writing_mode: WritingMode,
+}
+
+impl<'a> Content<'a> {
+    /// Returns content with properties that apply to the full text.
+    pub fn styled(style: &'a [StyleProperty<'a, Brush>]) -> Self {
+        Self {
+            style,
+            ..Self::default()
+        }
+    }
+}
+
+/// Returns a ranged builder for the text with the test font and the given
+/// content.
+pub(crate) fn ranged_builder<'a>(
+    fcx: &'a mut FontContext,
+    lcx: &'a mut LayoutContext<Brush>,
+    text: &'a str,
+    content: &Content,
+) -> RangedBuilder<'a, Brush, &'a str> {
+    let mut builder = lcx.ranged_builder(fcx, text, 1.);
+    for property in default_style().iter().chain(content.style) {
+        builder.push_default(property);
+    }
+    for (property, range) in content.spans {
+        builder.push(property, range.clone());
+    }
+    for inline_box in content.boxes {
+        builder.push_inline_box(*inline_box);
+    }
+    builder.set_writing_mode(content.writing_mode);
+    builder
+}
+
+/// Returns a tree builder with the test font.
+pub(crate) fn tree_builder<'a>(
+    fcx: &'a mut FontContext,
+    lcx: &'a mut LayoutContext<Brush>,
+) -> TreeBuilder<'a, Brush> {
+    let mut builder = lcx.tree_builder(fcx, 1.);
+    for property in &default_style() {
+        builder.push_default(property);
+    }
+    builder
+}
+
+/// Builds a layout of the text with the test font and the given content,
+/// without breaking it into lines.
+pub(crate) fn build(
+    fcx: &mut FontContext,
+    lcx: &mut LayoutContext<Brush>,
+    text: &str,
+    content: &Content,
+) -> Layout<Brush> {
+    ranged_builder(fcx, lcx, text, content).build()
+}
+
+/// Builds a layout with new font and layout contexts, without breaking it
+/// into lines.
+pub(crate) fn build_standalone(text: &str, content: &Content) -> Layout<Brush> {
+    build(
+        &mut font_context(),
+        &mut LayoutContext::new(),
+        text,
+        content,
+    )
+}
+
+/// Builds a layout and breaks it into lines with the greedy strategy.
+pub(crate) fn build_and_break(
+    text: &str,
+    content: &Content,
+    max_advance: Option<f32>,
+    alignment: Alignment,
+) -> Layout<Brush> {
+    let mut layout = build_standalone(text, content);
+    layout.break_all_lines(max_advance, alignment);
+    layout
+}
+
+/// Returns the text of each line, excluding trailing line breaks.
+pub(crate) fn line_texts<'a>(layout: &Layout<Brush>, text: &'a str) -> Vec<&'a str> {
+    layout
+        .lines()
+        .map(|line| text[line.text_range()].trim_end_matches(['\n', '\r']))
+        .collect()
+}
+
+/// Returns a description of the lines, runs and glyphs of a layout, which is
+/// used to compare layouts in tests.
+pub(crate) fn dump(layout: &Layout<Brush>) -> String {
+    let mut out = String::new();
+    writeln!(out, "size {} x {}", layout.width(), layout.height()).unwrap();
+    for line in layout.lines() {
+        writeln!(out, "line {:?} {:?}", line.text_range(), line.metrics()).unwrap();
+        let runs: Vec<_> = (0..line.len())
+            .map(|index| line.get(index).unwrap().text_range())
+            .collect();
+        writeln!(out, "  runs {runs:?}").unwrap();
+        for item in line.items() {
+            match item {
+                PositionedLayoutItem::GlyphRun(glyph_run) => {
+                    let run = glyph_run.run();
+                    writeln!(
+                        out,
+                        "  run {:?} offset {} baseline {} advance {}",
+                        run.text_range(),
+                        glyph_run.offset(),
+                        glyph_run.baseline(),
+                        glyph_run.advance(),
+                    )
+                    .unwrap();
+                    for glyph in glyph_run.positioned_glyphs() {
+                        writeln!(out, "    {glyph:?}").unwrap();
+                    }
+                }
+                PositionedLayoutItem::InlineBox(inline_box) => {
+                    writeln!(out, "  box {inline_box:?}").unwrap();
+                }
+            }
+        }
+    }
+    out
+}
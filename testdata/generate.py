@@ -0,0 +1,731 @@
+#!/usr/bin/env python3
+# Copyright 2024 the Parley Authors
+# SPDX-License-Identifier: Apache-2.0 OR MIT
+
+"""Generates the fonts used by the fontique and parley tests.
+
+ParleyTest.ttf is a small TrueType font with square-ish outlines. Every glyph
+advances by 1000 units on a 1000 unit em except for the soft hyphen, which
+has no advance. The ascent is 800 units and the descent 200 units, so text
+set at size 10 has 10 pixel wide characters and 10 pixel tall lines.
+
+ParleyTest.woff and ParleyTest.woff2 contain the same font. The TrueType file
+is written so that decoding either of them reproduces it byte for byte: table
+data is stored in the order of the WOFF2 table directory, glyphs are padded to
+four bytes and point flags use repeat runs wherever possible.
+
+The WOFF2 file uses uncompressed Brotli meta-blocks so that this script has no
+dependencies outside of the standard library.
+
+Run this script from any directory to regenerate the files next to it.
+"""
+
+import os
+import struct
+import zlib
+
+UPEM = 1000
+ADVANCE = 1000
+ASCENT = 800
+DESCENT = -200
+
+FAMILY = "Parley Test"
+SUBFAMILY = "Regular"
+
+FI = 2
+FI_ADVANCE = 1000
+FFI = 3
+FFI_ADVANCE = 2400
+
+SOFT_HYPHEN = 0x00AD
+# Characters without outlines.
+EMPTY = {0x0020, 0x00A0, SOFT_HYPHEN, 0x3000}
+
+CODEPOINTS = sorted(
+    set(range(0x20, 0x7F))
+    | {0x00A0, SOFT_HYPHEN, 0x00C4, 0x00C5}
+    # Hebrew letters.
+    | set(range(0x05D0, 0x05EB))
+    # Arabic letters and tatweel.
+    | set(range(0x0621, 0x063B))
+    | set(range(0x0640, 0x064B))
+    | {0x2010, 0x2026}
+    | {0x3000, 0x3001, 0x3002}
+    | set(range(0x4E00, 0x4E10))
+)
+
+# Composite glyph flags.
+ARG_1_AND_2_ARE_WORDS = 0x0001
+ARGS_ARE_XY_VALUES = 0x0002
+WE_HAVE_A_SCALE = 0x0008
+MORE_COMPONENTS = 0x0020
+WE_HAVE_AN_X_AND_Y_SCALE = 0x0040
+WE_HAVE_A_TWO_BY_TWO = 0x0080
+WE_HAVE_INSTRUCTIONS = 0x0100
+
+
+class Glyph:
+    def __init__(self, advance=ADVANCE):
+        self.advance = advance
+        # List of contours, each a list of (x, y, on_curve).
+        self.contours = []
+        self.instructions = b""
+        # Explicit bounding box; computed from the points if None.
+        self.bbox = None
+        # List of (glyph_id, flags, argument and transform bytes).
+        self.components = []
+
+    def is_empty(self):
+        return not self.contours and not self.components
+
+    def points(self):
+        return [p for contour in self.contours for p in contour]
+
+    def computed_bbox(self):
+        points = self.points()
+        xs = [p[0] for p in points]
+        ys = [p[1] for p in points]
+        return (min(xs), min(ys), max(xs), max(ys))
+
+    def header_bbox(self):
+        return self.bbox if self.bbox is not None else self.computed_bbox()
+
+
+def simple_glyph(gid):
+    glyph = Glyph()
+    x0 = 50 + (gid * 7) % 40
+    x1 = 950 - (gid * 13) % 60
+    y0 = (gid * 5) % 30 - 10
+    y1 = 650 + (gid * 11) % 100
+    glyph.contours.append(
+        [
+            (x0, y0, True),
+            (x0, y1, True),
+            (x1, y1, False),
+            (x1, y0 + 300, True),
+            (x1 - 200, y0, True),
+            (x0 + 10, y0 + 5, True),
+            (x0 + 40, y0 + 30, False),
+        ]
+    )
+    return glyph
+
+
+def build_glyphs():
+    """Returns the glyph list and the character map."""
+    glyphs = []
+    notdef = Glyph()
+    notdef.contours = [
+        [(50, 0, True), (50, 800, True), (950, 800, True), (950, 0, True)],
+        [(100, 50, True), (900, 50, True), (900, 750, True), (100, 750, True)],
+    ]
+    glyphs.append(notdef)
+    # The soft hyphen has no advance so that unbroken words are not widened.
+    glyphs.append(Glyph(advance=0))
+    cmap = {SOFT_HYPHEN: 1}
+    # Ligatures of "fi" and "ffi" that are narrower and wider than their
+    # components.
+    for advance in [FI_ADVANCE, FFI_ADVANCE]:
+        glyph = simple_glyph(len(glyphs))
+        glyph.advance = advance
+        glyphs.append(glyph)
+    for cp in CODEPOINTS:
+        if cp == SOFT_HYPHEN:
+            continue
+        gid = len(glyphs)
+        cmap[cp] = gid
+        glyphs.append(Glyph() if cp in EMPTY else simple_glyph(gid))
+    # A glyph with instructions.
+    glyphs[cmap[ord("A")]].instructions = bytes([0xB0, 0x01, 0x1D])
+    # A glyph with two contours.
+    o = glyphs[cmap[ord("O")]]
+    o.contours = [
+        [(60, 0, True), (60, 700, True), (940, 700, True), (940, 0, True)],
+        [(160, 100, True), (840, 100, True), (840, 600, True), (160, 600, True)],
+    ]
+    # A glyph with large coordinates.
+    glyphs[cmap[ord("@")]].contours = [
+        [(20, -1500, True), (5000, 3000, False), (5000, -1500, True), (20, 2500, True)]
+    ]
+    # A glyph with an explicit bounding box larger than its points.
+    w = glyphs[cmap[ord("W")]]
+    bbox = w.computed_bbox()
+    w.bbox = (bbox[0] - 5, bbox[1] - 5, bbox[2] + 5, bbox[3] + 5)
+    # Composite glyphs.
+    period = cmap[ord(".")]
+    ellipsis = glyphs[cmap[0x2026]] = Glyph()
+    ellipsis.components = [
+        (
+            period,
+            ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | MORE_COMPONENTS,
+            struct.pack(">hh", 0, 0),
+        ),
+        (
+            period,
+            ARGS_ARE_XY_VALUES | WE_HAVE_A_SCALE | MORE_COMPONENTS,
+            struct.pack(">bbh", 100, 0, 0x4000),
+        ),
+        (
+            period,
+            ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | WE_HAVE_INSTRUCTIONS,
+            struct.pack(">hh", 400, 0),
+        ),
+    ]
+    ellipsis.instructions = bytes([0xB0, 0x00])
+    bbox = glyphs[period].computed_bbox()
+    ellipsis.bbox = (bbox[0], bbox[1], bbox[2] + 400, bbox[3])
+    a = cmap[ord("A")]
+    for cp, flags, transform in [
+        (0x00C4, WE_HAVE_A_TWO_BY_TWO, struct.pack(">hhhh", 0x4000, 0, 0, 0x4000)),
+        (0x00C5, WE_HAVE_AN_X_AND_Y_SCALE, struct.pack(">hh", 0x4000, 0x4000)),
+    ]:
+        glyph = glyphs[cmap[cp]] = Glyph()
+        glyph.components = [(a, ARGS_ARE_XY_VALUES | flags, struct.pack(">bb", 0, 0) + transform)]
+        glyph.bbox = glyphs[a].computed_bbox()
+    return glyphs, cmap
+
+
+def encode_points(points):
+    """Encodes simple glyph flags and coordinates."""
+    flags = bytearray()
+    xs = bytearray()
+    ys = bytearray()
+    last_flag = None
+    repeat_count = 0
+    x = y = 0
+
+    def encode(delta, short, same_or_positive, coords):
+        if delta == 0:
+            return same_or_positive
+        if -255 <= delta <= 255:
+            coords.append(abs(delta))
+            return short | (same_or_positive if delta > 0 else 0)
+        coords.extend(struct.pack(">h", delta))
+        return 0
+
+    for px, py, on_curve in points:
+        flag = 0x01 if on_curve else 0
+        flag |= encode(px - x, 0x02, 0x10, xs)
+        flag |= encode(py - y, 0x04, 0x20, ys)
+        x, y = px, py
+        if last_flag == flag and repeat_count != 255:
+            if repeat_count == 0:
+                flags[-1] |= 0x08
+                flags.append(1)
+            else:
+                flags[-1] += 1
+            repeat_count += 1
+        else:
+            flags.append(flag)
+            last_flag = flag
+            repeat_count = 0
+    return bytes(flags + xs + ys)
+
+
+def pad4(data):
+    return data + b"\0" * (-len(data) % 4)
+
+
+def build_glyf(glyphs):
+    glyf = bytearray()
+    offsets = []
+    for glyph in glyphs:
+        offsets.append(len(glyf))
+        if glyph.is_empty():
+            continue
+        if glyph.contours:
+            data = struct.pack(">h4h", len(glyph.contours), *glyph.header_bbox())
+            end = -1
+            for contour in glyph.contours:
+                end += len(contour)
+                data += struct.pack(">H", end)
+            data += struct.pack(">H", len(glyph.instructions)) + glyph.instructions
+            data += encode_points(glyph.points())
+        else:
+            data = struct.pack(">h4h", -1, *glyph.header_bbox())
+            for gid, flags, args in glyph.components:
+                data += struct.pack(">HH", flags, gid) + args
+            if glyph.instructions:
+                data += struct.pack(">H", len(glyph.instructions)) + glyph.instructions
+        glyf += pad4(data)
+    offsets.append(len(glyf))
+    loca = b"".join(struct.pack(">H", offset // 2) for offset in offsets)
+    return bytes(glyf), loca
+
+
+def x_min(glyph):
+    return 0 if glyph.is_empty() else glyph.header_bbox()[0]
+
+
+def num_h_metrics(glyphs):
+    count = len(glyphs)
+    while count > 1 and glyphs[count - 1].advance == glyphs[count - 2].advance:
+        count -= 1
+    return count
+
+
+def build_hmtx(glyphs):
+    count = num_h_metrics(glyphs)
+    data = b""
+    for index, glyph in enumerate(glyphs):
+        if index < count:
+            data += struct.pack(">H", glyph.advance)
+        data += struct.pack(">h", x_min(glyph))
+    return data
+
+
+def build_cmap(cmap):
+    # Group runs of consecutive code points with consecutive glyphs.
+    segments = []
+    for cp in sorted(cmap):
+        gid = cmap[cp]
+        if segments and segments[-1][1] == cp - 1 and segments[-1][2] + (cp - segments[-1][0]) == gid:
+            segments[-1][1] = cp
+        else:
+            segments.append([cp, cp, gid])
+    segments.append([0xFFFF, 0xFFFF, 0])
+    seg_count = len(segments)
+    entry_selector = seg_count.bit_length() - 1
+    search_range = 2 * (1 << entry_selector)
+    subtable = struct.pack(
+        ">4H", seg_count * 2, search_range, entry_selector, seg_count * 2 - search_range
+    )
+    subtable += b"".join(struct.pack(">H", s[1]) for s in segments)
+    subtable += struct.pack(">H", 0)
+    subtable += b"".join(struct.pack(">H", s[0]) for s in segments)
+    for start, _, gid in segments:
+        delta = 1 if start == 0xFFFF else (gid - start) % 0x10000
+        subtable += struct.pack(">H", delta)
+    subtable += b"\0\0" * seg_count
+    subtable = struct.pack(">3H", 4, len(subtable) + 6, 0) + subtable
+    header = struct.pack(">2H", 0, 2)
+    offset = 4 + 2 * 8
+    header += struct.pack(">2HI", 0, 3, offset)
+    header += struct.pack(">2HI", 3, 1, offset)
+    return header + subtable
+
+
+def build_gsub(cmap):
+    """Builds a table with a single liga feature for the fi and ffi ligatures."""
+    f, i = cmap[ord("f")], cmap[ord("i")]
+    lang_sys = struct.pack(">4H", 0, 0xFFFF, 1, 0)
+    script = struct.pack(">2H", 4, 0) + lang_sys
+    scripts = [b"DFLT", b"latn"]
+    script_list = struct.pack(">H", len(scripts))
+    for index, tag in enumerate(scripts):
+        script_list += tag + struct.pack(">H", 2 + 6 * len(scripts) + index * len(script))
+    script_list += script * len(scripts)
+    feature_list = struct.pack(">H", 1) + b"liga" + struct.pack(">H", 8)
+    feature_list += struct.pack(">3H", 0, 1, 0)
+    # The longest ligature is listed first so that it takes precedence.
+    ligatures = [(FFI, [f, i]), (FI, [i])]
+    ligature_set = struct.pack(">H", len(ligatures))
+    ligature_data = b""
+    for glyph, components in ligatures:
+        ligature_set += struct.pack(">H", 2 + 2 * len(ligatures) + len(ligature_data))
+        ligature_data += struct.pack(f">2H{len(components)}H", glyph, len(components) + 1, *components)
+    ligature_set += ligature_data
+    coverage = struct.pack(">3H", 1, 1, f)
+    subtable = struct.pack(">4H", 1, 8, 1, 8 + len(coverage)) + coverage + ligature_set
+    lookup = struct.pack(">4H", 4, 0, 1, 8) + subtable
+    lookup_list = struct.pack(">2H", 1, 4) + lookup
+    header_len = 10
+    script_offset = header_len
+    feature_offset = script_offset + len(script_list)
+    lookup_offset = feature_offset + len(feature_list)
+    header = struct.pack(">2H3H", 1, 0, script_offset, feature_offset, lookup_offset)
+    return header + script_list + feature_list + lookup_list
+
+
+def build_name():
+    names = [
+        (1, FAMILY),
+        (2, SUBFAMILY),
+        (4, f"{FAMILY} {SUBFAMILY}"),
+        (6, f"{FAMILY.replace(' ', '')}-{SUBFAMILY}"),
+    ]
+    strings = b""
+    records = b""
+    for name_id, value in names:
+        encoded = value.encode("utf-16-be")
+        records += struct.pack(">6H", 3, 1, 0x409, name_id, len(encoded), len(strings))
+        strings += encoded
+    return struct.pack(">3H", 0, len(names), 6 + len(records)) + records + strings
+
+
+def build_os2(cmap):
+    # Basic Latin, Latin-1 Supplement, Hebrew, Arabic, General Punctuation,
+    # CJK Symbols and Punctuation and CJK Unified Ideographs.
+    ranges = [0, 0, 0, 0]
+    for bit in [0, 1, 11, 13, 31, 48, 59]:
+        ranges[bit // 32] |= 1 << (bit % 32)
+    return struct.pack(
+        ">HhHHH10hh10s4I4sHHH3hHH2IhhHHH",
+        4,  # version
+        ADVANCE,  # xAvgCharWidth
+        400,  # usWeightClass
+        5,  # usWidthClass
+        0,  # fsType
+        650, 600, 0, 75,  # subscript
+        650, 600, 0, 350,  # superscript
+        50, 300,  # strikeout
+        0,  # sFamilyClass
+        bytes(10),  # panose
+        *ranges,
+        b"NONE",
+        0x00C0,  # fsSelection: REGULAR | USE_TYPO_METRICS
+        min(cmap),
+        max(cmap),
+        ASCENT, DESCENT, 0,  # typo metrics
+        ASCENT, -DESCENT,  # win metrics
+        1, 0,  # code page ranges: Latin 1
+        500, 700,  # x height and cap height
+        0, 0x20, 1,  # default char, break char, max context
+    )
+
+
+def build_font():
+    glyphs, cmap = build_glyphs()
+    glyf, loca = build_glyf(glyphs)
+    non_empty = [g for g in glyphs if not g.is_empty()]
+    bboxes = [g.header_bbox() for g in non_empty]
+    font_bbox = (
+        min(b[0] for b in bboxes),
+        min(b[1] for b in bboxes),
+        max(b[2] for b in bboxes),
+        max(b[3] for b in bboxes),
+    )
+    simple = [g for g in non_empty if g.contours]
+    composite = [g for g in non_empty if g.components]
+    head = struct.pack(
+        ">IIIIHHqq4hHHhhh",
+        0x00010000,
+        0x00010000,
+        0,  # checksum adjustment
+        0x5F0F3CF5,
+        0x0009,  # flags
+        UPEM,
+        0,
+        0,
+        *font_bbox,
+        0,  # macStyle
+        8,  # lowestRecPPEM
+        2,  # fontDirectionHint
+        0,  # indexToLocFormat
+        0,  # glyphDataFormat
+    )
+    hhea = struct.pack(
+        ">I3hH3h3h4hhH",
+        0x00010000,
+        ASCENT,
+        DESCENT,
+        0,
+        max(g.advance for g in glyphs),
+        min(b[0] for b in bboxes),
+        min(g.advance - g.header_bbox()[2] for g in non_empty),
+        max(b[2] for b in bboxes),
+        1,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        num_h_metrics(glyphs),
+    )
+    maxp = struct.pack(
+        ">I14H",
+        0x00010000,
+        len(glyphs),
+        max(len(g.points()) for g in simple),
+        max(len(g.contours) for g in simple),
+        max(sum(len(glyphs[c[0]].points()) for c in g.components) for g in composite),
+        max(sum(len(glyphs[c[0]].contours) for c in g.components) for g in composite),
+        2,  # maxZones
+        0,
+        0,
+        0,
+        0,
+        0,
+        max(len(g.instructions) for g in glyphs),
+        max(len(g.components) for g in glyphs),
+        1,  # maxComponentDepth
+    )
+    post = struct.pack(">IIhhIIIII", 0x00030000, 0, -100, 50, 0, 0, 0, 0, 0)
+    # Tables in the order that their data is stored. The loca table follows
+    # glyf as required by WOFF2.
+    return [
+        (b"OS/2", build_os2(cmap)),
+        (b"GSUB", build_gsub(cmap)),
+        (b"cmap", build_cmap(cmap)),
+        (b"glyf", glyf),
+        (b"loca", loca),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", build_hmtx(glyphs)),
+        (b"maxp", maxp),
+        (b"name", build_name()),
+        (b"post", post),
+    ], glyphs
+
+
+def checksum(data):
+    data = pad4(data)
+    return sum(struct.unpack(f">{len(data) // 4}I", data)) & 0xFFFFFFFF
+
+
+def write_sfnt(tables):
+    num_tables = len(tables)
+    entry_selector = num_tables.bit_length() - 1
+    search_range = (1 << entry_selector) * 16
+    out = struct.pack(
+        ">I4H", 0x00010000, num_tables, search_range, entry_selector, num_tables * 16 - search_range
+    )
+    offset = len(out) + 16 * num_tables
+    offsets = {}
+    for tag, data in tables:
+        offsets[tag] = offset
+        offset += len(pad4(data))
+    for tag, data in sorted(tables):
+        out += tag + struct.pack(">3I", checksum(data), offsets[tag], len(data))
+    head_offset = None
+    for tag, data in tables:
+        if tag == b"head":
+            head_offset = len(out)
+        out += pad4(data)
+    adjustment = (0xB1B0AFBA - checksum(out)) & 0xFFFFFFFF
+    return out[: head_offset + 8] + struct.pack(">I", adjustment) + out[head_offset + 12 :]
+
+
+def read_sfnt(sfnt):
+    """Returns the (tag, checksum, data) of each table in storage order."""
+    num_tables = struct.unpack(">H", sfnt[4:6])[0]
+    records = []
+    for index in range(num_tables):
+        record = sfnt[12 + index * 16 : 28 + index * 16]
+        tag = record[:4]
+        table_checksum, offset, length = struct.unpack(">3I", record[4:])
+        records.append((offset, tag, table_checksum, sfnt[offset : offset + length]))
+    return [record[1:] for record in sorted(records)]
+
+
+def write_woff(sfnt):
+    tables = read_sfnt(sfnt)
+    entries = []
+    data = b""
+    offset = 44 + 20 * len(tables)
+    for tag, table_checksum, table in tables:
+        compressed = zlib.compress(table, 9)
+        if len(compressed) >= len(table):
+            compressed = table
+        entries.append((tag, offset + len(data), len(compressed), len(table), table_checksum))
+        data += pad4(compressed)
+    directory = b"".join(
+        tag + struct.pack(">4I", *rest) for tag, *rest in sorted(entries)
+    )
+    length = 44 + len(directory) + len(data)
+    header = struct.pack(
+        ">4sIIHHIHHIIIII", b"wOFF", 0x00010000, length, len(tables), 0, len(sfnt), 1, 0, 0, 0, 0, 0, 0
+    )
+    return header + directory + data
+
+
+KNOWN_TAGS = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+]
+
+
+def base128(value):
+    out = [value & 0x7F]
+    value >>= 7
+    while value:
+        out.append(0x80 | (value & 0x7F))
+        value >>= 7
+    return bytes(reversed(out))
+
+
+def u255(value):
+    if value < 253:
+        return bytes([value])
+    if value < 506:
+        return bytes([255, value - 253])
+    if value < 762:
+        return bytes([254, value - 506])
+    return bytes([253]) + struct.pack(">H", value)
+
+
+def triplet(dx, dy, on_curve):
+    abs_x, abs_y = abs(dx), abs(dy)
+    flag = 0 if on_curve else 0x80
+    x_sign = 1 if dx >= 0 else 0
+    y_sign = 1 if dy >= 0 else 0
+    xy_signs = x_sign + 2 * y_sign
+    if dx == 0 and abs_y < 1280:
+        return flag + ((abs_y & 0xF00) >> 7) + y_sign, bytes([abs_y & 0xFF])
+    if dy == 0 and abs_x < 1280:
+        return flag + 10 + ((abs_x & 0xF00) >> 7) + x_sign, bytes([abs_x & 0xFF])
+    if abs_x < 65 and abs_y < 65:
+        flag += 20 + ((abs_x - 1) & 0x30) + (((abs_y - 1) & 0x30) >> 2) + xy_signs
+        return flag, bytes([(((abs_x - 1) & 0xF) << 4) | ((abs_y - 1) & 0xF)])
+    if abs_x < 769 and abs_y < 769:
+        flag += 84 + 12 * (((abs_x - 1) & 0x300) >> 8) + (((abs_y - 1) & 0x300) >> 6) + xy_signs
+        return flag, bytes([(abs_x - 1) & 0xFF, (abs_y - 1) & 0xFF])
+    if abs_x < 4096 and abs_y < 4096:
+        return flag + 120 + xy_signs, bytes(
+            [abs_x >> 4, ((abs_x & 0xF) << 4) | (abs_y >> 8), abs_y & 0xFF]
+        )
+    return flag + 124 + xy_signs, struct.pack(">HH", abs_x, abs_y)
+
+
+def transform_glyf(glyphs):
+    n_contours = b""
+    n_points = b""
+    flags = b""
+    glyph_stream = b""
+    composites = b""
+    bbox_bitmap = bytearray(((len(glyphs) + 31) >> 5) << 2)
+    bboxes = b""
+    instructions = b""
+    for index, glyph in enumerate(glyphs):
+        if glyph.is_empty():
+            n_contours += struct.pack(">h", 0)
+            continue
+        explicit_bbox = bool(glyph.components) or glyph.bbox is not None
+        if explicit_bbox:
+            bbox_bitmap[index >> 3] |= 0x80 >> (index & 7)
+            bboxes += struct.pack(">4h", *glyph.header_bbox())
+        if glyph.contours:
+            n_contours += struct.pack(">h", len(glyph.contours))
+            for contour in glyph.contours:
+                n_points += u255(len(contour))
+            x = y = 0
+            for px, py, on_curve in glyph.points():
+                flag, data = triplet(px - x, py - y, on_curve)
+                flags += bytes([flag])
+                glyph_stream += data
+                x, y = px, py
+            glyph_stream += u255(len(glyph.instructions))
+            instructions += glyph.instructions
+        else:
+            n_contours += struct.pack(">h", -1)
+            for gid, component_flags, args in glyph.components:
+                composites += struct.pack(">HH", component_flags, gid) + args
+            if glyph.instructions:
+                glyph_stream += u255(len(glyph.instructions))
+                instructions += glyph.instructions
+    bbox_stream = bytes(bbox_bitmap) + bboxes
+    streams = [n_contours, n_points, flags, glyph_stream, composites, bbox_stream, instructions]
+    header = struct.pack(">HHHH", 0, 0, len(glyphs), 0)
+    header += b"".join(struct.pack(">I", len(stream)) for stream in streams)
+    return header + b"".join(streams)
+
+
+def transform_hmtx(glyphs):
+    # Both the proportional and monospaced side bearings match the minimum x
+    # coordinates, so only the advances are stored.
+    count = num_h_metrics(glyphs)
+    return bytes([0x03]) + b"".join(struct.pack(">H", g.advance) for g in glyphs[:count])
+
+
+def brotli_uncompressed(data):
+    """Wraps data in a Brotli stream made of uncompressed meta-blocks."""
+    bits = []
+
+    def write(value, count):
+        bits.extend((value >> i) & 1 for i in range(count))
+
+    out = bytearray()
+
+    def flush():
+        while len(bits) % 8:
+            bits.append(0)
+        for i in range(0, len(bits), 8):
+            out.append(sum(bit << j for j, bit in enumerate(bits[i : i + 8])))
+        bits.clear()
+
+    # A window of 2^16 - 16 bytes.
+    write(0, 1)
+    for start in range(0, len(data), 0x10000):
+        chunk = data[start : start + 0x10000]
+        # ISLAST, MNIBBLES (4), MLEN - 1 and ISUNCOMPRESSED.
+        write(0, 1)
+        write(0, 2)
+        write(len(chunk) - 1, 16)
+        write(1, 1)
+        flush()
+        out.extend(chunk)
+    # ISLAST and ISLASTEMPTY.
+    write(1, 1)
+    write(1, 1)
+    flush()
+    return bytes(out)
+
+
+def write_woff2(sfnt, glyphs):
+    tables = read_sfnt(sfnt)
+    directory = b""
+    stream = b""
+    for tag, _, table in tables:
+        if tag == b"glyf":
+            transformed = transform_glyf(glyphs)
+            version = 0
+        elif tag == b"loca":
+            transformed = b""
+            version = 0
+        elif tag == b"hmtx":
+            transformed = transform_hmtx(glyphs)
+            version = 1
+        else:
+            transformed = None
+            version = 0
+        flags = version << 6
+        if tag in KNOWN_TAGS:
+            directory += bytes([flags | KNOWN_TAGS.index(tag)])
+        else:
+            directory += bytes([flags | 63]) + tag
+        directory += base128(len(table))
+        if transformed is not None:
+            directory += base128(len(transformed))
+            stream += transformed
+        else:
+            stream += table
+    compressed = brotli_uncompressed(stream)
+    length = 48 + len(directory) + len(compressed)
+    header = struct.pack(
+        ">4sIIHHIIHHIIIII",
+        b"wOF2",
+        0x00010000,
+        length + (-length % 4),
+        len(tables),
+        0,
+        len(sfnt),
+        len(compressed),
+        1,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+    return pad4(header + directory + compressed)
+
+
+def main():
+    tables, glyphs = build_font()
+    sfnt = write_sfnt(tables)
+    directory = os.path.dirname(os.path.abspath(__file__))
+    outputs = {
+        "ParleyTest.ttf": sfnt,
+        "ParleyTest.woff": write_woff(sfnt),
+        "ParleyTest.woff2": write_woff2(sfnt, glyphs),
+    }
+    for name, data in outputs.items():
+        with open(os.path.join(directory, name), "wb") as f:
+            f.write(data)
+
+
+if __name__ == "__main__":
+    main()